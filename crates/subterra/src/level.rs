@@ -2,6 +2,7 @@
 
 pub mod read;
 pub mod udmf;
+pub mod write;
 pub mod znbx;
 
 use util::Id8;
@@ -11,7 +12,7 @@ use crate::EditorNum;
 /// Exists only to bundle multiple raw level data types to simplify other interfaces.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RawLevel<'r> {
-	pub linedefs: &'r [read::LineDefRaw],
+	pub linedefs: RawLineDefs<'r>,
 	pub nodes: &'r [read::NodeRaw],
 	pub sectors: &'r [read::SectorRaw],
 	pub segs: &'r [read::SegRaw],
@@ -28,6 +29,13 @@ pub enum RawThings<'r> {
 	Ext(&'r [read::ThingExtRaw]),
 }
 
+/// See [`RawLevel`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawLineDefs<'r> {
+	Doom(&'r [read::LineDefRaw]),
+	Ext(&'r [read::LineDefExtRaw]),
+}
+
 /// Certain important ["editor numbers"](https://zdoom.org/wiki/Editor_number).
 pub mod ednums {
 	use crate::EditorNum;
@@ -90,6 +98,8 @@ pub enum Error {
 	///
 	/// The containde slice will be something like `VERTEXES` or `THINGS`.
 	MalformedFile(&'static str),
+	/// See [`znbx::NodeBuilder`].
+	NodeBuild(&'static str),
 	/// No thingdef was defined as a player 1 starting location.
 	NoPlayer1Start,
 	TextmapParse(udmf::Error),
@@ -198,6 +208,9 @@ impl std::fmt::Display for Error {
 			Self::MalformedFile(name) => {
 				write!(f, "`{name}` has malformed contents")
 			}
+			Self::NodeBuild(details) => {
+				write!(f, "node builder error: {details}")
+			}
 			Self::NoPlayer1Start => {
 				write!(f, "no thingdef was defined as a player 1 starting location")
 			}
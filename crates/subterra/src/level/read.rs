@@ -4,6 +4,7 @@
 
 use std::ops::Range;
 
+use bytemuck::Zeroable;
 use util::{read_id8, Id8};
 
 use super::Error;
@@ -12,8 +13,8 @@ use super::Error;
 
 pub mod prelude {
 	pub use super::{
-		BspNodeChild, LineDefRaw, NodeRaw, SSectorRaw, SectorRaw, SegDirection, SegRaw, SideDefRaw,
-		ThingExtRaw, ThingFlags, ThingRaw, VertexRaw,
+		BspNodeChild, LineDefExtRaw, LineDefRaw, NodeRaw, SSectorRaw, SectorRaw, SegDirection,
+		SegRaw, SideDefRaw, ThingExtRaw, ThingFlags, ThingRaw, VertexRaw,
 	};
 }
 
@@ -89,6 +90,27 @@ impl LineDefRaw {
 	/// A possible value for [`Self::special`].
 	pub const POBJ_LINE_EXPLICIT: u16 = 5;
 
+	/// All arguments are in native endianness. See [`Self::set_flags`].
+	#[must_use]
+	pub fn new(
+		vertices: [u16; 2],
+		flags: LineFlags,
+		special: u16,
+		trigger: u16,
+		right_side: u16,
+		left_side: Option<u16>,
+	) -> Self {
+		let mut ret = Self::zeroed();
+		ret.set_start_vertex(vertices[0]);
+		ret.set_end_vertex(vertices[1]);
+		ret.set_flags(flags);
+		ret.set_special(special);
+		ret.set_trigger(trigger);
+		ret.set_right_side(right_side);
+		ret.set_left_side(left_side);
+		ret
+	}
+
 	/// To be used as an index into a slice of [`VertexRaw`].
 	#[must_use]
 	pub fn start_vertex(&self) -> u16 {
@@ -129,6 +151,37 @@ impl LineDefRaw {
 		let s = u16::from_le(self.left);
 		(s != 0xFFFF).then_some(s)
 	}
+
+	pub fn set_start_vertex(&mut self, vertex: u16) {
+		self.v_start = vertex.to_le();
+	}
+
+	pub fn set_end_vertex(&mut self, vertex: u16) {
+		self.v_end = vertex.to_le();
+	}
+
+	/// Only the first 13 flags (up to [`LineFlags::BLOCK_FLOATERS`])
+	/// can be represented by this format; the rest are discarded.
+	pub fn set_flags(&mut self, flags: LineFlags) {
+		self.flags = ((flags.bits() & 0x1FFF) as u16).to_le();
+	}
+
+	pub fn set_special(&mut self, special: u16) {
+		self.special = special.to_le();
+	}
+
+	pub fn set_trigger(&mut self, trigger: u16) {
+		self.trigger = trigger.to_le();
+	}
+
+	pub fn set_right_side(&mut self, side: u16) {
+		self.right = side.to_le();
+	}
+
+	/// `None` is written as `0xFFFF`.
+	pub fn set_left_side(&mut self, side: Option<u16>) {
+		self.left = side.unwrap_or(0xFFFF).to_le();
+	}
 }
 
 /// Casts a slice of raw bytes to line definitions (without allocating).
@@ -187,6 +240,201 @@ pub fn linedefs_lossy_mut(lump: &mut [u8]) -> Result<&mut [LineDefRaw], Error> {
 	Ok(bytemuck::cast_slice_mut(subslice))
 }
 
+// LINEDEFS, extended /////////////////////////////////////////////////////////
+
+/// See <https://doomwiki.org/wiki/Linedef#Hexen_format>. Acquired via [`linedefs_ext`].
+/// These are cast directly from the bytes of a WAD's lump;
+/// attached methods automatically convert from Little Endian.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, bytemuck::Zeroable, bytemuck::Pod)]
+pub struct LineDefExtRaw {
+	v_start: u16,
+	v_end: u16,
+	flags: u16,
+	special: u8,
+	args: [u8; 5],
+	right: u16,
+	left: u16,
+}
+
+impl LineDefExtRaw {
+	/// All arguments are in native endianness. See [`Self::set_flags`].
+	#[must_use]
+	pub fn new(
+		vertices: [u16; 2],
+		flags: LineFlags,
+		special: u8,
+		args: [u8; 5],
+		right_side: u16,
+		left_side: Option<u16>,
+	) -> Self {
+		let mut ret = Self::zeroed();
+		ret.set_start_vertex(vertices[0]);
+		ret.set_end_vertex(vertices[1]);
+		ret.set_flags(flags);
+		ret.set_special(special);
+		ret.set_args(args);
+		ret.set_right_side(right_side);
+		ret.set_left_side(left_side);
+		ret
+	}
+
+	/// To be used as an index into a slice of [`VertexRaw`].
+	#[must_use]
+	pub fn start_vertex(&self) -> u16 {
+		u16::from_le(self.v_start)
+	}
+
+	/// To be used as an index into a slice of [`VertexRaw`].
+	#[must_use]
+	pub fn end_vertex(&self) -> u16 {
+		u16::from_le(self.v_end)
+	}
+
+	/// The first 9 bits mean the same as in Doom's format. The line's activation
+	/// type (bits 10 to 12) becomes one of the `ALLOW_*` flags or [`LineFlags::IMPACT`],
+	/// along with its monster counterpart if bit 13 is set.
+	#[must_use]
+	pub fn flags(&self) -> LineFlags {
+		let f = u16::from_le(self.flags);
+		let mut flags = LineFlags::from_bits_truncate((f & 0x01FF) as u32);
+		let monsters = (f & (1 << 13)) != 0;
+
+		if (f & (1 << 9)) != 0 {
+			flags.insert(LineFlags::REPEAT_SPECIAL);
+		}
+
+		flags |= match (f >> 10) & 0b111 {
+			0 if monsters => LineFlags::ALLOW_PLAYER_CROSS | LineFlags::ALLOW_MONS_CROSS,
+			0 => LineFlags::ALLOW_PLAYER_CROSS,
+			1 if monsters => LineFlags::ALLOW_PLAYER_USE | LineFlags::ALLOW_MONS_USE,
+			1 => LineFlags::ALLOW_PLAYER_USE,
+			2 => LineFlags::ALLOW_MONS_CROSS,
+			3 => LineFlags::IMPACT,
+			4 if monsters => LineFlags::ALLOW_PLAYER_PUSH | LineFlags::ALLOW_MONS_PUSH,
+			4 => LineFlags::ALLOW_PLAYER_PUSH,
+			5 => LineFlags::ALLOW_PROJ_CROSS,
+			_ => LineFlags::empty(),
+		};
+
+		flags
+	}
+
+	#[must_use]
+	pub fn special(&self) -> u8 {
+		self.special
+	}
+
+	#[must_use]
+	pub fn args(&self) -> [u8; 5] {
+		self.args
+	}
+
+	/// a.k.a. the linedef's "front". To be used as an index into a slice of [`SideDefRaw`].
+	#[must_use]
+	pub fn right_side(&self) -> u16 {
+		u16::from_le(self.right)
+	}
+
+	/// a.k.a. the linedef's "back". To be used as an index into a slice of [`SideDefRaw`].
+	/// Returns `None` if the LE bytes of this value match the bit pattern `0xFFFF`.
+	#[must_use]
+	pub fn left_side(&self) -> Option<u16> {
+		let s = u16::from_le(self.left);
+		(s != 0xFFFF).then_some(s)
+	}
+
+	pub fn set_start_vertex(&mut self, vertex: u16) {
+		self.v_start = vertex.to_le();
+	}
+
+	pub fn set_end_vertex(&mut self, vertex: u16) {
+		self.v_end = vertex.to_le();
+	}
+
+	/// The inverse of [`Self::flags`]. This format has room for only one
+	/// activation type, so only the first of player crossing, player use,
+	/// monster crossing, [impact](LineFlags::IMPACT), player pushing, and
+	/// projectile crossing is kept; if there is none, the line is
+	/// activated by player crossing. Other flags which this format
+	/// can not represent (e.g. [`LineFlags::PASS_USE`]) are discarded.
+	pub fn set_flags(&mut self, flags: LineFlags) {
+		let mut f = (flags.bits() & 0x01FF) as u16;
+
+		if flags.contains(LineFlags::REPEAT_SPECIAL) {
+			f |= 1 << 9;
+		}
+
+		let (activation, monsters) = if flags.contains(LineFlags::ALLOW_PLAYER_CROSS) {
+			(0, LineFlags::ALLOW_MONS_CROSS)
+		} else if flags.contains(LineFlags::ALLOW_PLAYER_USE) {
+			(1, LineFlags::ALLOW_MONS_USE)
+		} else if flags.contains(LineFlags::ALLOW_MONS_CROSS) {
+			(2, LineFlags::empty())
+		} else if flags.contains(LineFlags::IMPACT) {
+			(3, LineFlags::empty())
+		} else if flags.contains(LineFlags::ALLOW_PLAYER_PUSH) {
+			(4, LineFlags::ALLOW_MONS_PUSH)
+		} else if flags.contains(LineFlags::ALLOW_PROJ_CROSS) {
+			(5, LineFlags::empty())
+		} else {
+			(0, LineFlags::empty())
+		};
+
+		f |= activation << 10;
+
+		if !monsters.is_empty() && flags.contains(monsters) {
+			f |= 1 << 13;
+		}
+
+		self.flags = f.to_le();
+	}
+
+	pub fn set_special(&mut self, special: u8) {
+		self.special = special;
+	}
+
+	pub fn set_args(&mut self, args: [u8; 5]) {
+		self.args = args;
+	}
+
+	pub fn set_right_side(&mut self, side: u16) {
+		self.right = side.to_le();
+	}
+
+	/// `None` is written as `0xFFFF`.
+	pub fn set_left_side(&mut self, side: Option<u16>) {
+		self.left = side.unwrap_or(0xFFFF).to_le();
+	}
+}
+
+/// Casts a slice of raw bytes to extended line definitions (without allocating).
+/// Returns [`Error::MalformedFile`] if the length of `lump` is not divisible by 16.
+pub fn linedefs_ext(lump: &[u8]) -> Result<&[LineDefExtRaw], Error> {
+	if lump.is_empty() {
+		return Err(Error::EmptyFile("LINEDEFS (extended)"));
+	}
+
+	if (lump.len() % std::mem::size_of::<LineDefExtRaw>()) != 0 {
+		return Err(Error::MalformedFile("LINEDEFS (extended)"));
+	}
+
+	Ok(bytemuck::cast_slice(lump))
+}
+
+/// See [`linedefs_ext`].
+pub fn linedefs_ext_mut(lump: &mut [u8]) -> Result<&mut [LineDefExtRaw], Error> {
+	if lump.is_empty() {
+		return Err(Error::EmptyFile("LINEDEFS (extended)"));
+	}
+
+	if (lump.len() % std::mem::size_of::<LineDefExtRaw>()) != 0 {
+		return Err(Error::MalformedFile("LINEDEFS (extended)"));
+	}
+
+	Ok(bytemuck::cast_slice_mut(lump))
+}
+
 // NODES ///////////////////////////////////////////////////////////////////////
 
 /// See <https://doomwiki.org/wiki/Node>. Acquired via [`nodes`].
@@ -207,6 +455,27 @@ pub struct NodeRaw {
 }
 
 impl NodeRaw {
+	/// All arguments are in native endianness.
+	/// Bounding boxes are ordered top, bottom, left, right.
+	#[must_use]
+	pub fn new(
+		seg_start: [i16; 2],
+		seg_delta: [i16; 2],
+		aabb_r: [i16; 4],
+		aabb_l: [i16; 4],
+		child_r: BspNodeChild,
+		child_l: BspNodeChild,
+	) -> Self {
+		let mut ret = Self::zeroed();
+		ret.set_seg_start(seg_start);
+		ret.set_seg_delta(seg_delta);
+		ret.set_aabb_r(aabb_r);
+		ret.set_aabb_l(aabb_l);
+		ret.set_child_r(child_r);
+		ret.set_child_l(child_l);
+		ret
+	}
+
 	#[must_use]
 	pub fn seg_start(&self) -> [i16; 2] {
 		[i16::from_le(self.x), i16::from_le(self.y)]
@@ -246,6 +515,46 @@ impl NodeRaw {
 			BspNodeChild::SubNode(child as usize)
 		}
 	}
+
+	/// The bounding box of the right child; top, bottom, left, right.
+	#[must_use]
+	pub fn aabb_r(&self) -> [i16; 4] {
+		self.aabb_r.map(i16::from_le)
+	}
+
+	/// The bounding box of the left child; top, bottom, left, right.
+	#[must_use]
+	pub fn aabb_l(&self) -> [i16; 4] {
+		self.aabb_l.map(i16::from_le)
+	}
+
+	pub fn set_seg_start(&mut self, start: [i16; 2]) {
+		self.x = start[0].to_le();
+		self.y = start[1].to_le();
+	}
+
+	pub fn set_seg_delta(&mut self, delta: [i16; 2]) {
+		self.delta_x = delta[0].to_le();
+		self.delta_y = delta[1].to_le();
+	}
+
+	pub fn set_aabb_r(&mut self, aabb: [i16; 4]) {
+		self.aabb_r = aabb.map(i16::to_le);
+	}
+
+	pub fn set_aabb_l(&mut self, aabb: [i16; 4]) {
+		self.aabb_l = aabb.map(i16::to_le);
+	}
+
+	/// Indices are truncated to 15 bits.
+	pub fn set_child_r(&mut self, child: BspNodeChild) {
+		self.child_r = child.to_raw().to_le();
+	}
+
+	/// Indices are truncated to 15 bits.
+	pub fn set_child_l(&mut self, child: BspNodeChild) {
+		self.child_l = child.to_raw().to_le();
+	}
 }
 
 /// See [`NodeRaw`].
@@ -256,6 +565,16 @@ pub enum BspNodeChild {
 	SubNode(usize),
 }
 
+impl BspNodeChild {
+	#[must_use]
+	fn to_raw(self) -> i16 {
+		match self {
+			Self::SubSector(i) => ((i as u16 & 0x7FFF) | 0x8000) as i16,
+			Self::SubNode(i) => (i as u16 & 0x7FFF) as i16,
+		}
+	}
+}
+
 /// Casts a slice of raw bytes to line definitions (without allocating).
 /// Returns [`Error::MalformedFile`] if the length of `lump` is not divisible by 28.
 /// or [`Error::EmptyFile`] if the length of `lump` is zero.
@@ -330,6 +649,27 @@ pub struct SectorRaw {
 }
 
 impl SectorRaw {
+	/// All arguments are in native endianness. See [`Self::set_floor_texture`].
+	#[must_use]
+	pub fn new(
+		heights: [i16; 2],
+		floor_texture: Option<&str>,
+		ceiling_texture: Option<&str>,
+		light_level: u16,
+		special: u16,
+		trigger: u16,
+	) -> Self {
+		let mut ret = Self::zeroed();
+		ret.set_floor_height(heights[0]);
+		ret.set_ceiling_height(heights[1]);
+		ret.set_floor_texture(floor_texture);
+		ret.set_ceiling_texture(ceiling_texture);
+		ret.set_light_level(light_level);
+		ret.set_special(special);
+		ret.set_trigger(trigger);
+		ret
+	}
+
 	#[must_use]
 	pub fn floor_height(&self) -> i16 {
 		i16::from_le(self.height_floor)
@@ -367,6 +707,37 @@ impl SectorRaw {
 	pub fn trigger(&self) -> u16 {
 		u16::from_le(self.trigger)
 	}
+
+	pub fn set_floor_height(&mut self, height: i16) {
+		self.height_floor = height.to_le();
+	}
+
+	pub fn set_ceiling_height(&mut self, height: i16) {
+		self.height_ceil = height.to_le();
+	}
+
+	/// `None` is written as all NUL bytes.
+	/// Names longer than 8 bytes are truncated.
+	pub fn set_floor_texture(&mut self, texture: Option<&str>) {
+		self.tex_floor = id8_bytes(texture);
+	}
+
+	/// See [`Self::set_floor_texture`].
+	pub fn set_ceiling_texture(&mut self, texture: Option<&str>) {
+		self.tex_ceil = id8_bytes(texture);
+	}
+
+	pub fn set_light_level(&mut self, light_level: u16) {
+		self.light_level = light_level.to_le();
+	}
+
+	pub fn set_special(&mut self, special: u16) {
+		self.special = special.to_le();
+	}
+
+	pub fn set_trigger(&mut self, trigger: u16) {
+		self.trigger = trigger.to_le();
+	}
 }
 
 /// Casts a slice of raw bytes to line definitions (without allocating).
@@ -442,6 +813,25 @@ pub struct SegRaw {
 }
 
 impl SegRaw {
+	/// All arguments are in native endianness.
+	#[must_use]
+	pub fn new(
+		vertices: [u16; 2],
+		angle: i16,
+		linedef: u16,
+		direction: SegDirection,
+		offset: i16,
+	) -> Self {
+		let mut ret = Self::zeroed();
+		ret.set_start_vertex(vertices[0]);
+		ret.set_end_vertex(vertices[1]);
+		ret.set_angle(angle);
+		ret.set_linedef(linedef);
+		ret.set_direction(direction);
+		ret.set_offset(offset);
+		ret
+	}
+
 	/// To be used as an index into a slice of [`VertexRaw`].
 	#[must_use]
 	pub fn start_vertex(&self) -> u16 {
@@ -480,6 +870,35 @@ impl SegRaw {
 	pub fn offset(&self) -> i16 {
 		i16::from_le(self.offset)
 	}
+
+	pub fn set_start_vertex(&mut self, vertex: u16) {
+		self.v_start = vertex.to_le();
+	}
+
+	pub fn set_end_vertex(&mut self, vertex: u16) {
+		self.v_end = vertex.to_le();
+	}
+
+	/// See [`Self::angle`].
+	pub fn set_angle(&mut self, angle: i16) {
+		self.angle = angle.to_le();
+	}
+
+	pub fn set_linedef(&mut self, linedef: u16) {
+		self.linedef = linedef.to_le();
+	}
+
+	pub fn set_direction(&mut self, direction: SegDirection) {
+		self.direction = match direction {
+			SegDirection::Front => 0_i16,
+			SegDirection::Back => 1_i16,
+		}
+		.to_le();
+	}
+
+	pub fn set_offset(&mut self, offset: i16) {
+		self.offset = offset.to_le();
+	}
 }
 
 /// See [`SegRaw::direction`].
@@ -574,6 +993,24 @@ pub struct SideDefRaw {
 }
 
 impl SideDefRaw {
+	/// All arguments are in native endianness. See [`SectorRaw::set_floor_texture`].
+	#[must_use]
+	pub fn new(
+		offset: [i16; 2],
+		top_texture: Option<&str>,
+		bottom_texture: Option<&str>,
+		mid_texture: Option<&str>,
+		sector: u16,
+	) -> Self {
+		let mut ret = Self::zeroed();
+		ret.set_offset(offset);
+		ret.set_top_texture(top_texture);
+		ret.set_bottom_texture(bottom_texture);
+		ret.set_mid_texture(mid_texture);
+		ret.set_sector(sector);
+		ret
+	}
+
 	#[must_use]
 	pub fn offset(&self) -> [i16; 2] {
 		[i16::from_le(self.offs_x), i16::from_le(self.offs_y)]
@@ -602,6 +1039,30 @@ impl SideDefRaw {
 	pub fn bottom_texture(&self) -> Option<Id8> {
 		read_id8(self.tex_bottom)
 	}
+
+	pub fn set_offset(&mut self, offset: [i16; 2]) {
+		self.offs_x = offset[0].to_le();
+		self.offs_y = offset[1].to_le();
+	}
+
+	pub fn set_sector(&mut self, sector: u16) {
+		self.sector = sector.to_le();
+	}
+
+	/// See [`SectorRaw::set_floor_texture`].
+	pub fn set_top_texture(&mut self, texture: Option<&str>) {
+		self.tex_top = id8_bytes(texture);
+	}
+
+	/// See [`SectorRaw::set_floor_texture`].
+	pub fn set_mid_texture(&mut self, texture: Option<&str>) {
+		self.tex_mid = id8_bytes(texture);
+	}
+
+	/// See [`SectorRaw::set_floor_texture`].
+	pub fn set_bottom_texture(&mut self, texture: Option<&str>) {
+		self.tex_bottom = id8_bytes(texture);
+	}
 }
 
 /// Casts a slice of raw bytes to line definitions (without allocating).
@@ -673,6 +1134,15 @@ pub struct SSectorRaw {
 }
 
 impl SSectorRaw {
+	/// All arguments are in native endianness.
+	#[must_use]
+	pub fn new(seg_count: u16, first_seg: u16) -> Self {
+		let mut ret = Self::zeroed();
+		ret.set_seg_count(seg_count);
+		ret.set_first_seg(first_seg);
+		ret
+	}
+
 	#[must_use]
 	pub fn seg_count(self) -> u16 {
 		u16::from_le(self.seg_count)
@@ -690,6 +1160,14 @@ impl SSectorRaw {
 		let seg0 = self.first_seg() as usize;
 		seg0..(seg0 + self.seg_count() as usize)
 	}
+
+	pub fn set_seg_count(&mut self, count: u16) {
+		self.seg_count = count.to_le();
+	}
+
+	pub fn set_first_seg(&mut self, seg: u16) {
+		self.seg = seg.to_le();
+	}
 }
 
 /// Casts a slice of raw bytes to line definitions (without allocating).
@@ -764,6 +1242,17 @@ pub struct ThingRaw {
 }
 
 impl ThingRaw {
+	/// All arguments are in native endianness. See [`Self::set_flags`].
+	#[must_use]
+	pub fn new(position: [i16; 2], angle: u16, editor_num: u16, flags: ThingFlags) -> Self {
+		let mut ret = Self::zeroed();
+		ret.set_position(position);
+		ret.set_angle(angle);
+		ret.set_editor_num(editor_num);
+		ret.set_flags(flags);
+		ret
+	}
+
 	#[must_use]
 	pub fn position(&self) -> [i16; 2] {
 		[i16::from_le(self.x), i16::from_le(self.y)]
@@ -780,6 +1269,9 @@ impl ThingRaw {
 		u16::from_le(self.angle)
 	}
 
+	/// Bit 4 is "not in single-player", and bits 5 and 6 are Boom's "not in
+	/// deathmatch" and "not in co-op", so a thing with none of them set
+	/// is present in every game mode.
 	#[must_use]
 	pub fn flags(&self) -> ThingFlags {
 		let f = i16::from_le(self.flags);
@@ -803,18 +1295,16 @@ impl ThingRaw {
 			flags.insert(ThingFlags::AMBUSH);
 		}
 
-		if (f & (1 << 4)) != 0 {
-			flags.insert(ThingFlags::COOP);
-		} else {
+		if (f & (1 << 4)) == 0 {
 			flags.insert(ThingFlags::SINGLEPLAY);
 		}
 
-		if (f & (1 << 5)) != 0 {
-			flags.remove(ThingFlags::DEATHMATCH);
+		if (f & (1 << 5)) == 0 {
+			flags.insert(ThingFlags::DEATHMATCH);
 		}
 
-		if (f & (1 << 6)) != 0 {
-			flags.remove(ThingFlags::COOP);
+		if (f & (1 << 6)) == 0 {
+			flags.insert(ThingFlags::COOP);
 		}
 
 		if (f & (1 << 7)) != 0 {
@@ -823,6 +1313,45 @@ impl ThingRaw {
 
 		flags
 	}
+
+	pub fn set_position(&mut self, position: [i16; 2]) {
+		self.x = position[0].to_le();
+		self.y = position[1].to_le();
+	}
+
+	/// See [`Self::angle`].
+	pub fn set_angle(&mut self, angle: u16) {
+		self.angle = angle.to_le();
+	}
+
+	pub fn set_editor_num(&mut self, editor_num: u16) {
+		self.ednum = editor_num.to_le();
+	}
+
+	/// The inverse of [`Self::flags`]. Flags which this format can not
+	/// represent (e.g. [`ThingFlags::DORMANT`]) are discarded.
+	pub fn set_flags(&mut self, flags: ThingFlags) {
+		let mut f = encode_skill_flags(flags);
+
+		for (i, flag) in [
+			ThingFlags::SINGLEPLAY,
+			ThingFlags::DEATHMATCH,
+			ThingFlags::COOP,
+		]
+		.into_iter()
+		.enumerate()
+		{
+			if !flags.contains(flag) {
+				f |= 1 << (i + 4);
+			}
+		}
+
+		if flags.contains(ThingFlags::FRIEND) {
+			f |= 1 << 7;
+		}
+
+		self.flags = f.to_le();
+	}
 }
 
 bitflags::bitflags! {
@@ -911,10 +1440,8 @@ pub fn things_lossy_mut(lump: &mut [u8]) -> Result<&mut [ThingRaw], Error> {
 /// See <https://doomwiki.org/wiki/Thing#Hexen_format>. Acquired via [`things`].
 /// These are cast directly from the bytes of a WAD's lump;
 /// attached methods automatically convert from Little Endian.
-///
-/// Please note that due to padding, this type cannot be cast to mutable slices.
 #[repr(C)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, bytemuck::AnyBitPattern)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, bytemuck::Zeroable, bytemuck::Pod)]
 pub struct ThingExtRaw {
 	tid: i16,
 	x: i16,
@@ -923,10 +1450,38 @@ pub struct ThingExtRaw {
 	angle: u16,
 	ednum: u16,
 	flags: i16,
+	special: u8,
 	args: [u8; 5],
 }
 
 impl ThingExtRaw {
+	/// All arguments are in native endianness. See [`Self::set_flags`].
+	#[must_use]
+	pub fn new(
+		tid: i16,
+		position: [i16; 3],
+		angle: u16,
+		editor_num: u16,
+		flags: ThingFlags,
+		special: u8,
+		args: [u8; 5],
+	) -> Self {
+		let mut ret = Self::zeroed();
+		ret.set_tid(tid);
+		ret.set_position(position);
+		ret.set_angle(angle);
+		ret.set_editor_num(editor_num);
+		ret.set_flags(flags);
+		ret.set_special(special);
+		ret.set_args(args);
+		ret
+	}
+
+	#[must_use]
+	pub fn tid(&self) -> i16 {
+		i16::from_le(self.tid)
+	}
+
 	/// Returns, in order, X, Y, and Z coordinates.
 	#[must_use]
 	pub fn position(&self) -> [i16; 3] {
@@ -1000,14 +1555,72 @@ impl ThingExtRaw {
 		flags
 	}
 
+	#[must_use]
+	pub fn special(&self) -> u8 {
+		self.special
+	}
+
 	#[must_use]
 	pub fn args(&self) -> [u8; 5] {
 		self.args
 	}
+
+	pub fn set_tid(&mut self, tid: i16) {
+		self.tid = tid.to_le();
+	}
+
+	/// In order, X, Y, and Z coordinates.
+	pub fn set_position(&mut self, position: [i16; 3]) {
+		self.x = position[0].to_le();
+		self.y = position[1].to_le();
+		self.z = position[2].to_le();
+	}
+
+	/// See [`Self::angle`].
+	pub fn set_angle(&mut self, angle: u16) {
+		self.angle = angle.to_le();
+	}
+
+	pub fn set_editor_num(&mut self, editor_num: u16) {
+		self.ednum = editor_num.to_le();
+	}
+
+	/// The inverse of [`Self::flags`]. Flags which this format can not
+	/// represent (e.g. [`ThingFlags::FRIEND`]) are discarded.
+	pub fn set_flags(&mut self, flags: ThingFlags) {
+		let mut f = encode_skill_flags(flags);
+
+		for (i, flag) in [
+			ThingFlags::DORMANT,
+			ThingFlags::CLASS_1,
+			ThingFlags::CLASS_2,
+			ThingFlags::CLASS_3,
+			ThingFlags::SINGLEPLAY,
+			ThingFlags::COOP,
+			ThingFlags::DEATHMATCH,
+		]
+		.into_iter()
+		.enumerate()
+		{
+			if flags.contains(flag) {
+				f |= 1 << (i + 4);
+			}
+		}
+
+		self.flags = f.to_le();
+	}
+
+	pub fn set_special(&mut self, special: u8) {
+		self.special = special;
+	}
+
+	pub fn set_args(&mut self, args: [u8; 5]) {
+		self.args = args;
+	}
 }
 
 /// Casts a slice of raw bytes to extended thing definitions (without allocating).
-/// Returns [`Error::MalformedFile`] if the length of `lump` is not divisible by 160.
+/// Returns [`Error::MalformedFile`] if the length of `lump` is not divisible by 20.
 pub fn things_ext(lump: &[u8]) -> Result<&[ThingExtRaw], Error> {
 	if lump.is_empty() {
 		return Err(Error::EmptyFile("THINGS (extended)"));
@@ -1020,11 +1633,24 @@ pub fn things_ext(lump: &[u8]) -> Result<&[ThingExtRaw], Error> {
 	Ok(bytemuck::cast_slice(lump))
 }
 
+/// See [`things_ext`].
+pub fn things_ext_mut(lump: &mut [u8]) -> Result<&mut [ThingExtRaw], Error> {
+	if lump.is_empty() {
+		return Err(Error::EmptyFile("THINGS (extended)"));
+	}
+
+	if (lump.len() % std::mem::size_of::<ThingExtRaw>()) != 0 {
+		return Err(Error::MalformedFile("THINGS (extended)"));
+	}
+
+	Ok(bytemuck::cast_slice_mut(lump))
+}
+
 /// Like [`things_ext`], but any bytes at the end of slice which do not fit into
 /// another [`ThingExtRaw`] are truncated.
 pub fn things_ext_lossy(lump: &[u8]) -> Result<&[ThingExtRaw], Error> {
 	if lump.is_empty() {
-		return Err(Error::EmptyFile("THINGS"));
+		return Err(Error::EmptyFile("THINGS (extended)"));
 	}
 
 	let sz = std::mem::size_of::<ThingExtRaw>();
@@ -1033,6 +1659,19 @@ pub fn things_ext_lossy(lump: &[u8]) -> Result<&[ThingExtRaw], Error> {
 	Ok(bytemuck::cast_slice(subslice))
 }
 
+/// Like [`things_ext_mut`], but any bytes at the end of slice which do not fit into
+/// another [`ThingExtRaw`] are truncated.
+pub fn things_ext_lossy_mut(lump: &mut [u8]) -> Result<&mut [ThingExtRaw], Error> {
+	if lump.is_empty() {
+		return Err(Error::EmptyFile("THINGS (extended)"));
+	}
+
+	let sz = std::mem::size_of::<ThingExtRaw>();
+	let count = lump.len() / sz;
+	let subslice = &mut lump[..(count * sz)];
+	Ok(bytemuck::cast_slice_mut(subslice))
+}
+
 // VERTEXES ////////////////////////////////////////////////////////////////////
 
 /// See <https://doomwiki.org/wiki/Vertex>. Acquired via [`vertexes`].
@@ -1046,6 +1685,14 @@ pub struct VertexRaw {
 }
 
 impl VertexRaw {
+	/// `position` is in native endianness.
+	#[must_use]
+	pub fn new(position: [i16; 2]) -> Self {
+		let mut ret = Self::zeroed();
+		ret.set_position(position);
+		ret
+	}
+
 	/// Returns a "minimum" and "maximum" corner, respectively.
 	#[must_use]
 	pub fn bounds(verts: &[Self]) -> ([i16; 2], [i16; 2]) {
@@ -1073,6 +1720,11 @@ impl VertexRaw {
 	pub fn position(&self) -> [i16; 2] {
 		[i16::from_le(self.x), i16::from_le(self.y)]
 	}
+
+	pub fn set_position(&mut self, position: [i16; 2]) {
+		self.x = position[0].to_le();
+		self.y = position[1].to_le();
+	}
 }
 
 /// Casts a slice of raw bytes to vertex definitions (without allocating).
@@ -1111,3 +1763,42 @@ pub fn vertexes_lossy_mut(lump: &mut [u8]) -> &mut [VertexRaw] {
 	let subslice = &mut lump[..(count * sz)];
 	bytemuck::cast_slice_mut(subslice)
 }
+
+// Details /////////////////////////////////////////////////////////////////////
+
+/// The inverse of [`read_id8`].
+#[must_use]
+fn id8_bytes(id8: Option<&str>) -> [u8; 8] {
+	let mut ret = [0; 8];
+
+	if let Some(id8) = id8 {
+		let len = id8.len().min(8);
+		ret[..len].copy_from_slice(&id8.as_bytes()[..len]);
+	}
+
+	ret
+}
+
+/// The bits shared by the Doom and Hexen formats, in native endianness.
+#[must_use]
+fn encode_skill_flags(flags: ThingFlags) -> i16 {
+	let mut f = 0;
+
+	if flags.intersects(ThingFlags::SKILL_1 | ThingFlags::SKILL_2) {
+		f |= 1 << 0;
+	}
+
+	if flags.contains(ThingFlags::SKILL_3) {
+		f |= 1 << 1;
+	}
+
+	if flags.intersects(ThingFlags::SKILL_4 | ThingFlags::SKILL_5) {
+		f |= 1 << 2;
+	}
+
+	if flags.contains(ThingFlags::AMBUSH) {
+		f |= 1 << 3;
+	}
+
+	f
+}
@@ -400,8 +400,9 @@ impl<'i, S: Sink> Parser<'i, S> {
 					token,
 					Token::Ident
 						| Token::KwSector | Token::KwLineDef
-						| Token::KwNamespace | Token::KwSideDef
-						| Token::KwThing | Token::KwVertex
+						| Token::KwNamespace
+						| Token::KwSideDef | Token::KwThing
+						| Token::KwVertex
 				)
 			}) {
 				break;
@@ -0,0 +1,203 @@
+//! Functions for serializing vanilla ["map lumps"] from raw records.
+//!
+//! Each is the inverse of its counterpart in [`super::read`]. Records store
+//! their fields in Little Endian already (see e.g. [`LineDefRaw::new`]), so
+//! the returned bytes can be written into a WAD as-is.
+//!
+//! ["map lumps"]: https://doomwiki.org/wiki/Lump#Standard_lumps
+
+use super::read::{
+	LineDefExtRaw, LineDefRaw, NodeRaw, SSectorRaw, SectorRaw, SegRaw, SideDefRaw, ThingExtRaw,
+	ThingRaw, VertexRaw,
+};
+
+/// Casts line definitions to the bytes of a `LINEDEFS` lump (without allocating).
+#[must_use]
+pub fn linedefs(linedefs: &[LineDefRaw]) -> &[u8] {
+	bytemuck::cast_slice(linedefs)
+}
+
+/// Casts line definitions to the bytes of a Hexen-format `LINEDEFS` lump
+/// (without allocating).
+#[must_use]
+pub fn linedefs_ext(linedefs: &[LineDefExtRaw]) -> &[u8] {
+	bytemuck::cast_slice(linedefs)
+}
+
+/// Casts BSP nodes to the bytes of a `NODES` lump (without allocating).
+#[must_use]
+pub fn nodes(nodes: &[NodeRaw]) -> &[u8] {
+	bytemuck::cast_slice(nodes)
+}
+
+/// Casts sectors to the bytes of a `SECTORS` lump (without allocating).
+#[must_use]
+pub fn sectors(sectors: &[SectorRaw]) -> &[u8] {
+	bytemuck::cast_slice(sectors)
+}
+
+/// Casts segs to the bytes of a `SEGS` lump (without allocating).
+#[must_use]
+pub fn segs(segs: &[SegRaw]) -> &[u8] {
+	bytemuck::cast_slice(segs)
+}
+
+/// Casts side definitions to the bytes of a `SIDEDEFS` lump (without allocating).
+#[must_use]
+pub fn sidedefs(sidedefs: &[SideDefRaw]) -> &[u8] {
+	bytemuck::cast_slice(sidedefs)
+}
+
+/// Casts subsectors to the bytes of a `SSECTORS` lump (without allocating).
+#[must_use]
+pub fn ssectors(ssectors: &[SSectorRaw]) -> &[u8] {
+	bytemuck::cast_slice(ssectors)
+}
+
+/// Casts things to the bytes of a Doom-format `THINGS` lump (without allocating).
+#[must_use]
+pub fn things(things: &[ThingRaw]) -> &[u8] {
+	bytemuck::cast_slice(things)
+}
+
+/// Casts things to the bytes of a Hexen-format `THINGS` lump (without allocating).
+#[must_use]
+pub fn things_ext(things: &[ThingExtRaw]) -> &[u8] {
+	bytemuck::cast_slice(things)
+}
+
+/// Casts vertices to the bytes of a `VERTEXES` lump (without allocating).
+#[must_use]
+pub fn vertexes(vertexes: &[VertexRaw]) -> &[u8] {
+	bytemuck::cast_slice(vertexes)
+}
+
+#[cfg(test)]
+mod test {
+	use crate::level::read::{self, BspNodeChild, SegDirection, ThingFlags};
+
+	use super::*;
+
+	#[test]
+	fn round_trip() {
+		// Doom's format has no room for activation flags.
+		let linedefs = [LineDefRaw::new(
+			[0, 1],
+			read::LineFlags::TWO_SIDED | read::LineFlags::ALLOW_PLAYER_USE,
+			11,
+			2,
+			0,
+			None,
+		)];
+		let bytes = super::linedefs(&linedefs);
+		assert_eq!(bytes.len(), 14);
+		let back = read::linedefs(bytes).unwrap();
+		assert_eq!(back[0].end_vertex(), 1);
+		assert_eq!(back[0].flags(), read::LineFlags::TWO_SIDED);
+		assert_eq!(back[0].left_side(), None);
+
+		let flags = read::LineFlags::TWO_SIDED
+			| read::LineFlags::REPEAT_SPECIAL
+			| read::LineFlags::ALLOW_PLAYER_USE
+			| read::LineFlags::ALLOW_MONS_USE;
+		let linedefs = [LineDefExtRaw::new(
+			[2, 3],
+			flags,
+			80,
+			[1, 2, 3, 4, 5],
+			0,
+			Some(1),
+		)];
+		let bytes = super::linedefs_ext(&linedefs);
+		assert_eq!(bytes.len(), 16);
+		assert_eq!(bytes[6], 80);
+		let back = read::linedefs_ext(bytes).unwrap();
+		assert_eq!(back[0].flags(), flags);
+		assert_eq!(back[0].special(), 80);
+		assert_eq!(back[0].args(), [1, 2, 3, 4, 5]);
+		assert_eq!(back[0].left_side(), Some(1));
+
+		let nodes = [NodeRaw::new(
+			[64, -32],
+			[0, 128],
+			[1, 2, 3, 4],
+			[5, 6, 7, 8],
+			BspNodeChild::SubSector(3),
+			BspNodeChild::SubNode(0),
+		)];
+		let back = read::nodes(super::nodes(&nodes)).unwrap();
+		assert_eq!(back[0].seg_end(), [64, 96]);
+		assert_eq!(back[0].aabb_l(), [5, 6, 7, 8]);
+		assert_eq!(back[0].child_r(), BspNodeChild::SubSector(3));
+		assert_eq!(back[0].child_l(), BspNodeChild::SubNode(0));
+
+		let sectors = [SectorRaw::new(
+			[0, 128],
+			Some("FLOOR4_8"),
+			Some("CEIL3_5_LONGER"),
+			160,
+			9,
+			2,
+		)];
+		let back = read::sectors(super::sectors(&sectors)).unwrap();
+		assert_eq!(back[0].floor_texture().unwrap().as_str(), "FLOOR4_8");
+		assert_eq!(back[0].ceiling_texture().unwrap().as_str(), "CEIL3_5_");
+		assert_eq!(back[0].light_level(), 160);
+
+		let segs = [SegRaw::new([2, 3], 16384, 7, SegDirection::Back, 12)];
+		let back = read::segs(super::segs(&segs)).unwrap();
+		assert_eq!(back[0].direction(), SegDirection::Back);
+		assert_eq!(back[0].offset(), 12);
+
+		let sidedefs = [SideDefRaw::new([8, -8], None, None, Some("STARTAN2"), 4)];
+		let back = read::sidedefs(super::sidedefs(&sidedefs)).unwrap();
+		assert_eq!(back[0].top_texture(), None);
+		assert_eq!(back[0].mid_texture().unwrap().as_str(), "STARTAN2");
+
+		let ssectors = [SSectorRaw::new(4, 10)];
+		let back = read::ssectors(super::ssectors(&ssectors)).unwrap();
+		assert_eq!(back[0].segs(), 10..14);
+
+		let flags =
+			ThingFlags::SKILL_1 | ThingFlags::SKILL_2 | ThingFlags::AMBUSH | ThingFlags::SINGLEPLAY;
+		let things = [ThingRaw::new([32, 32], 90, 1, flags)];
+		let back = read::things(super::things(&things)).unwrap();
+		assert_eq!(back[0].flags(), flags);
+
+		for flags in [
+			ThingFlags::SKILL_3
+				| ThingFlags::SINGLEPLAY
+				| ThingFlags::DEATHMATCH
+				| ThingFlags::COOP,
+			ThingFlags::SKILL_3 | ThingFlags::DEATHMATCH,
+			ThingFlags::SKILL_3 | ThingFlags::COOP,
+		] {
+			let things = [ThingRaw::new([0, 0], 0, 1, flags)];
+			let back = read::things(super::things(&things)).unwrap();
+			assert_eq!(back[0].flags(), flags);
+		}
+
+		let flags =
+			ThingFlags::SKILL_3 | ThingFlags::DORMANT | ThingFlags::CLASS_2 | ThingFlags::COOP;
+		let things = [ThingExtRaw::new(
+			5,
+			[0, 0, 16],
+			180,
+			3001,
+			flags,
+			80,
+			[1, 2, 3, 4, 5],
+		)];
+		let bytes = super::things_ext(&things);
+		assert_eq!(bytes.len(), 20);
+		assert_eq!(bytes[14], 80);
+		let back = read::things_ext(bytes).unwrap();
+		assert_eq!(back[0].flags(), flags);
+		assert_eq!(back[0].special(), 80);
+		assert_eq!(back[0].args(), [1, 2, 3, 4, 5]);
+
+		let vertexes = [VertexRaw::new([-1, 1])];
+		let back = read::vertexes(super::vertexes(&vertexes)).unwrap();
+		assert_eq!(back[0].position(), [-1, 1]);
+	}
+}
@@ -1,5 +1,751 @@
 //! VileTech's node builder; a wrapper around ZNBX (a fork of [ZDBSP]).
 //!
+//! [`NodeBuilder`] is a safe interface over [`sys`]; it owns the underlying
+//! processor and destroys it when dropped, and all of its output is copied into
+//! owned vectors, so nothing it returns borrows from ZNBX's allocations.
+//!
 //! [ZDBSP]: https://github.com/ZDoom/zdbsp
 
 pub extern crate znbx_sys as sys;
+
+use std::{ffi::c_void, marker::PhantomData, ptr::NonNull};
+
+use super::{
+	read::{NodeRaw, SSectorRaw, SegRaw},
+	Error, RawLevel, RawLineDefs, RawThings,
+};
+
+/// Options given to ZNBX before it builds nodes. See [`NodeBuilder::run`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+	/// Also build GL nodes. See [`BuiltNodes::nodes_gl`] et al.
+	pub gl_nodes: bool,
+	/// Only meaningful alongside `gl_nodes`. Makes the non-GL nodes
+	/// match the GL nodes instead of being built independently.
+	pub conform_nodes: bool,
+	/// Only meaningful alongside `gl_nodes`. Build version 5 GL nodes.
+	/// See [`BuiltNodes::nodes_gl_v5`] et al.
+	pub gl_v5: bool,
+	/// How strongly axis-aligned splitters are favored over diagonal ones.
+	pub aa_preference: i32,
+	/// How many segs to consider when choosing each splitter.
+	pub max_segs: i32,
+	/// How costly it is considered to split a seg.
+	pub split_cost: i32,
+	/// How ZNBX treats the level's `REJECT` lump.
+	/// [`BuiltNodes::reject`] is unaffected by this.
+	pub reject_mode: sys::znbx_RejectMode,
+	/// How ZNBX produces the level's `BLOCKMAP` lump. See [`BuiltNodes::blockmap`].
+	pub blockmap_mode: sys::znbx_BlockmapMode,
+}
+
+impl Options {
+	/// ZDBSP's default.
+	pub const AA_PREFERENCE_DEFAULT: i32 = 16;
+	/// ZDBSP's default.
+	pub const MAX_SEGS_DEFAULT: i32 = 64;
+	/// ZDBSP's default.
+	pub const SPLIT_COST_DEFAULT: i32 = 8;
+}
+
+impl Default for Options {
+	fn default() -> Self {
+		// SAFETY: pure functions with no preconditions.
+		let (flags, reject_mode, blockmap_mode) = unsafe {
+			(
+				sys::znbx_processflags_default(),
+				sys::znbx_rejectmode_default(),
+				sys::znbx_blockmapmode_default(),
+			)
+		};
+
+		Self {
+			gl_nodes: (flags & sys::znbx_ProcessFlags_ZNBX_PROCF_BUILDGLNODES) != 0,
+			conform_nodes: (flags & sys::znbx_ProcessFlags_ZNBX_PROCF_CONFORMNODES) != 0,
+			gl_v5: (flags & sys::znbx_ProcessFlags_ZNBX_PROCF_V5GL) != 0,
+			aa_preference: Self::AA_PREFERENCE_DEFAULT,
+			max_segs: Self::MAX_SEGS_DEFAULT,
+			split_cost: Self::SPLIT_COST_DEFAULT,
+			reject_mode,
+			blockmap_mode,
+		}
+	}
+}
+
+/// A level which has been handed to ZNBX but not yet processed.
+///
+/// ZNBX may keep pointers into the level data it was given,
+/// so that data must outlive the builder (and its [`BuiltNodes`]).
+#[derive(Debug)]
+pub struct NodeBuilder<'r> {
+	processor: Processor,
+	/// `None` for UDMF levels.
+	sectors: Option<usize>,
+	_input: PhantomData<&'r [u8]>,
+}
+
+impl<'r> NodeBuilder<'r> {
+	/// `name` must be ASCII and no longer than 8 bytes (e.g. `MAP01`).
+	/// Levels with [`RawThings::Ext`] and [`RawLineDefs::Ext`] are processed as
+	/// being in Hexen's format; mixing the two formats raises [`Error::NodeBuild`].
+	/// The level's existing nodes, segs, and subsectors are ignored.
+	pub fn new(name: &str, level: RawLevel<'r>) -> Result<Self, Error> {
+		let (things, linedefs, ext) = match (level.things, level.linedefs) {
+			(RawThings::Doom(things), RawLineDefs::Doom(linedefs)) => {
+				(slice_u8(things), slice_u8(linedefs), false)
+			}
+			(RawThings::Ext(things), RawLineDefs::Ext(linedefs)) => {
+				(slice_u8(things), slice_u8(linedefs), true)
+			}
+			_ => {
+				return Err(Error::NodeBuild(
+					"things and linedefs are not in the same format",
+				))
+			}
+		};
+
+		let raw = sys::znbx_Level {
+			name: level_name(name)?,
+			things,
+			vertices: slice_u8(level.vertices),
+			linedefs,
+			sidedefs: slice_u8(level.sidedefs),
+			sectors: slice_u8(level.sectors),
+		};
+
+		// SAFETY: every slice in `raw` is borrowed for `'r`.
+		let ptr = unsafe {
+			if ext {
+				sys::znbx_processor_new_extended(raw)
+			} else {
+				sys::znbx_processor_new_vanilla(raw)
+			}
+		};
+
+		Ok(Self {
+			processor: Processor::new(ptr)?,
+			sectors: Some(level.sectors.len()),
+			_input: PhantomData,
+		})
+	}
+
+	/// `name` must be ASCII and no longer than 8 bytes (e.g. `MAP01`).
+	pub fn new_udmf(name: &str, textmap: &'r [u8]) -> Result<Self, Error> {
+		let raw = sys::znbx_LevelUdmf {
+			name: level_name(name)?,
+			textmap: slice_u8(textmap),
+		};
+
+		// SAFETY: `textmap` is borrowed for `'r`.
+		let ptr = unsafe { sys::znbx_processor_new_udmf(raw) };
+
+		Ok(Self {
+			processor: Processor::new(ptr)?,
+			sectors: None,
+			_input: PhantomData,
+		})
+	}
+
+	/// Builds nodes, a blockmap, and (if requested) GL nodes.
+	#[must_use]
+	pub fn run(self, options: &Options) -> BuiltNodes<'r> {
+		// SAFETY: a pure function with no preconditions.
+		let mut flags = unsafe { sys::znbx_processflags_default() };
+
+		for (enabled, flag) in [
+			(
+				options.gl_nodes,
+				sys::znbx_ProcessFlags_ZNBX_PROCF_BUILDGLNODES,
+			),
+			(
+				options.conform_nodes,
+				sys::znbx_ProcessFlags_ZNBX_PROCF_CONFORMNODES,
+			),
+			(options.gl_v5, sys::znbx_ProcessFlags_ZNBX_PROCF_V5GL),
+		] {
+			if enabled {
+				flags |= flag;
+			} else {
+				flags &= !flag;
+			}
+		}
+
+		let node_cfg = sys::znbx_NodeConfig {
+			aa_preference: options.aa_preference,
+			max_segs: options.max_segs,
+			split_cost: options.split_cost,
+		};
+
+		// SAFETY: the processor is valid, and has not been run yet.
+		unsafe {
+			let proc_cfg = sys::znbx_ProcessConfig {
+				flags,
+				reject_mode: options.reject_mode,
+				blockmap_mode: options.blockmap_mode,
+			};
+
+			sys::znbx_processor_configure(self.processor.0.as_ptr(), std::ptr::addr_of!(proc_cfg));
+			sys::znbx_processor_run(self.processor.0.as_ptr(), std::ptr::addr_of!(node_cfg));
+		}
+
+		BuiltNodes {
+			processor: self.processor,
+			sectors: self.sectors,
+			_input: PhantomData,
+		}
+	}
+}
+
+/// The output of [`NodeBuilder::run`]. Every method copies its records out of
+/// ZNBX into a new vector, so each should only be called as often as needed.
+///
+/// Vanilla records are returned as the types from [`super::read`], so they can
+/// be written back out as lumps as-is. Everything else is returned as [`sys`] types.
+#[derive(Debug)]
+pub struct BuiltNodes<'r> {
+	processor: Processor,
+	/// `None` for UDMF levels.
+	sectors: Option<usize>,
+	_input: PhantomData<&'r [u8]>,
+}
+
+impl BuiltNodes<'_> {
+	/// For a vanilla `NODES` lump.
+	#[must_use]
+	pub fn nodes(&self) -> Vec<NodeRaw> {
+		// SAFETY: `znbx_NodeRaw` and `NodeRaw` have identical layouts.
+		unsafe { self.collect::<sys::znbx_NodeRaw, _>(sys::znbx_processor_nodes_foreach) }
+	}
+
+	/// For a vanilla `SEGS` lump.
+	#[must_use]
+	pub fn segs(&self) -> Vec<SegRaw> {
+		// SAFETY: `znbx_SegRaw` and `SegRaw` have identical layouts.
+		unsafe { self.collect::<sys::znbx_SegRaw, _>(sys::znbx_processor_segs_foreach) }
+	}
+
+	/// For a vanilla `SSECTORS` lump.
+	#[must_use]
+	pub fn subsectors(&self) -> Vec<SSectorRaw> {
+		// SAFETY: `znbx_SubsectorRaw` and `SSectorRaw` have identical layouts.
+		unsafe { self.collect::<sys::znbx_SubsectorRaw, _>(sys::znbx_processor_ssectors_foreach) }
+	}
+
+	/// For extended (`XNOD`/`ZNOD`) nodes.
+	#[must_use]
+	pub fn nodes_ext(&self) -> Vec<sys::znbx_NodeEx> {
+		// SAFETY: the record type is unchanged.
+		unsafe { self.collect(sys::znbx_processor_nodesx_foreach) }
+	}
+
+	/// For extended (`XNOD`/`ZNOD`) nodes.
+	#[must_use]
+	pub fn segs_ext(&self) -> Vec<sys::znbx_SegEx> {
+		// SAFETY: the record type is unchanged.
+		unsafe { self.collect(sys::znbx_processor_segsx_foreach) }
+	}
+
+	/// For extended (`XNOD`/`ZNOD`) nodes.
+	#[must_use]
+	pub fn subsectors_ext(&self) -> Vec<sys::znbx_SubsectorEx> {
+		// SAFETY: the record type is unchanged.
+		unsafe { self.collect(sys::znbx_processor_ssectorsx_foreach) }
+	}
+
+	/// For extended (`XNOD`/`ZNOD`) nodes. Only includes vertices created by
+	/// the node builder; see [`Self::orig_vertex_count`].
+	#[must_use]
+	pub fn vertices_ext(&self) -> Vec<sys::znbx_VertexEx> {
+		// SAFETY: the record type is unchanged.
+		unsafe { self.collect(sys::znbx_processor_vertsx_foreach) }
+	}
+
+	/// For a `GL_NODES` lump. Empty unless [`Options::gl_nodes`] was set.
+	#[must_use]
+	pub fn nodes_gl(&self) -> Vec<NodeRaw> {
+		// SAFETY: `znbx_NodeRaw` and `NodeRaw` have identical layouts.
+		unsafe { self.collect::<sys::znbx_NodeRaw, _>(sys::znbx_processor_nodesgl_foreach) }
+	}
+
+	/// For a `GL_SEGS` lump. Empty unless [`Options::gl_nodes`] was set.
+	#[must_use]
+	pub fn segs_gl(&self) -> Vec<sys::znbx_SegGl> {
+		// SAFETY: the record type is unchanged.
+		unsafe { self.collect(sys::znbx_processor_segsgl_foreach) }
+	}
+
+	/// For a `GL_SSECT` lump. Empty unless [`Options::gl_nodes`] was set.
+	#[must_use]
+	pub fn subsectors_gl(&self) -> Vec<SSectorRaw> {
+		// SAFETY: `znbx_SubsectorRaw` and `SSectorRaw` have identical layouts.
+		unsafe { self.collect::<sys::znbx_SubsectorRaw, _>(sys::znbx_processor_ssectorsgl_foreach) }
+	}
+
+	/// Only includes vertices created by the node builder;
+	/// see [`Self::orig_vertex_count`]. Empty unless [`Options::gl_nodes`] was
+	/// set or the level is UDMF.
+	#[must_use]
+	pub fn vertices_gl(&self) -> Vec<sys::znbx_VertexEx> {
+		// SAFETY: the record type is unchanged.
+		unsafe { self.collect(sys::znbx_processor_vertsgl_foreach) }
+	}
+
+	/// For extended GL (`XGLN`/`ZGLN`) nodes, which is what UDMF levels get.
+	#[must_use]
+	pub fn nodes_glx(&self) -> Vec<sys::znbx_NodeEx> {
+		// SAFETY: the record type is unchanged.
+		unsafe { self.collect(sys::znbx_processor_nodesglx_foreach) }
+	}
+
+	/// For extended GL (`XGLN`/`ZGLN`) nodes, which is what UDMF levels get.
+	#[must_use]
+	pub fn segs_glx(&self) -> Vec<sys::znbx_SegGlEx> {
+		// SAFETY: the record type is unchanged.
+		unsafe { self.collect(sys::znbx_processor_segsglx_foreach) }
+	}
+
+	/// For extended GL (`XGLN`/`ZGLN`) nodes, which is what UDMF levels get.
+	#[must_use]
+	pub fn subsectors_glx(&self) -> Vec<sys::znbx_SubsectorEx> {
+		// SAFETY: the record type is unchanged.
+		unsafe { self.collect(sys::znbx_processor_ssectorsglx_foreach) }
+	}
+
+	/// Empty unless [`Options::gl_v5`] was set.
+	#[must_use]
+	pub fn nodes_gl_v5(&self) -> Vec<sys::znbx_NodeExO> {
+		// SAFETY: the record type is unchanged.
+		unsafe { self.collect(sys::znbx_processor_nodesx_v5_foreach) }
+	}
+
+	/// Empty unless [`Options::gl_v5`] was set.
+	#[must_use]
+	pub fn segs_gl_v5(&self) -> Vec<sys::znbx_SegGlEx> {
+		// SAFETY: the record type is unchanged.
+		unsafe { self.collect(sys::znbx_processor_segsglx_v5_foreach) }
+	}
+
+	/// Empty unless [`Options::gl_v5`] was set.
+	#[must_use]
+	pub fn subsectors_gl_v5(&self) -> Vec<sys::znbx_SubsectorEx> {
+		// SAFETY: the record type is unchanged.
+		unsafe { self.collect(sys::znbx_processor_ssectorsx_v5_foreach) }
+	}
+
+	/// How many vertices the level had before nodes were built.
+	/// Indices into [`Self::vertices_ext`] and [`Self::vertices_gl`] are offset by this.
+	#[must_use]
+	pub fn orig_vertex_count(&self) -> usize {
+		// SAFETY: the processor is valid, and has been run.
+		unsafe { sys::znbx_processor_vertsorig_count(self.processor.0.as_ptr()) as usize }
+	}
+
+	/// The 4-byte header which starts an extended nodes lump
+	/// (e.g. `XNOD`, or `ZNOD` if `compressed` is true).
+	#[must_use]
+	pub fn magic_number(&self, compressed: bool) -> [u8; 4] {
+		// SAFETY: the processor is valid, and ZNBX always returns
+		// a pointer to a static string of at least 4 characters.
+		unsafe {
+			let ptr = sys::znbx_processor_magicnumber(self.processor.0.as_ptr(), compressed as u8);
+			std::ptr::read::<[u8; 4]>(ptr.cast())
+		}
+	}
+
+	/// For a `BLOCKMAP` lump, as native-endian words.
+	#[must_use]
+	pub fn blockmap(&self) -> Vec<u16> {
+		// SAFETY: the processor is valid, and has been run.
+		unsafe {
+			let blockmap = sys::znbx_processor_blockmap(self.processor.0.as_ptr());
+
+			if blockmap.ptr.is_null() || blockmap.len == 0 {
+				return vec![];
+			}
+
+			std::slice::from_raw_parts(blockmap.ptr, blockmap.len).to_vec()
+		}
+	}
+
+	/// For a `REJECT` lump. ZNBX never computes sector visibility (ZDBSP's
+	/// "rebuild" mode only zero-fills the table) and does not expose its reject
+	/// data, so this is a zero-filled table sized for the level's sectors, which
+	/// is what ZNBX would have written.
+	///
+	/// Returns `None` for UDMF levels, which do not need a `REJECT` lump.
+	#[must_use]
+	pub fn reject(&self) -> Option<Vec<u8>> {
+		self.sectors
+			.map(|sectors| vec![0; (sectors * sectors).div_ceil(8)])
+	}
+
+	/// # Safety
+	///
+	/// `T` must have the same size and layout as `S`.
+	#[must_use]
+	unsafe fn collect<S, T>(&self, foreach: ForEach<S>) -> Vec<T> {
+		unsafe extern "C" fn callback<S, T>(ctx: *mut c_void, ptr: *const S) {
+			let ret = &mut *ctx.cast::<Vec<T>>();
+			ret.push(std::ptr::read_unaligned(ptr.cast::<T>()));
+		}
+
+		debug_assert_eq!(std::mem::size_of::<S>(), std::mem::size_of::<T>());
+
+		let mut ret = Vec::<T>::new();
+
+		foreach(
+			self.processor.0.as_ptr(),
+			std::ptr::addr_of_mut!(ret).cast(),
+			Some(callback::<S, T>),
+		);
+
+		ret
+	}
+}
+
+// Details /////////////////////////////////////////////////////////////////////
+
+type ForEach<S> = unsafe extern "C" fn(
+	*mut sys::znbx_Processor,
+	*mut c_void,
+	Option<unsafe extern "C" fn(*mut c_void, *const S)>,
+);
+
+/// Destroys the ZNBX processor it owns when dropped.
+#[derive(Debug)]
+struct Processor(NonNull<sys::znbx_Processor>);
+
+impl Processor {
+	fn new(ptr: *mut sys::znbx_Processor) -> Result<Self, Error> {
+		NonNull::new(ptr)
+			.map(Self)
+			.ok_or(Error::NodeBuild("ZNBX failed to read the level"))
+	}
+}
+
+impl Drop for Processor {
+	fn drop(&mut self) {
+		// SAFETY: the pointer came from a `znbx_processor_new_*` function
+		// and this is the only place it gets destroyed.
+		unsafe { sys::znbx_processor_destroy(self.0.as_ptr()) }
+	}
+}
+
+const _: () = {
+	assert!(std::mem::size_of::<sys::znbx_NodeRaw>() == std::mem::size_of::<NodeRaw>());
+	assert!(std::mem::size_of::<sys::znbx_SegRaw>() == std::mem::size_of::<SegRaw>());
+	assert!(std::mem::size_of::<sys::znbx_SubsectorRaw>() == std::mem::size_of::<SSectorRaw>());
+};
+
+#[must_use]
+fn slice_u8<T>(slice: &[T]) -> sys::znbx_SliceU8 {
+	sys::znbx_SliceU8 {
+		ptr: slice.as_ptr().cast(),
+		len: std::mem::size_of_val(slice),
+	}
+}
+
+fn level_name(name: &str) -> Result<[i8; 9], Error> {
+	if name.len() > 8 || !name.is_ascii() {
+		return Err(Error::NodeBuild("level name is not ASCII or is too long"));
+	}
+
+	let mut ret = [0; 9];
+
+	for (i, b) in name.bytes().enumerate() {
+		ret[i] = b as i8;
+	}
+
+	Ok(ret)
+}
+
+/// Mirrors the smoke tests of [`sys`], to check that this wrapper passes
+/// ZNBX the same level and options, and copies out the same records.
+#[cfg(test)]
+mod test {
+	use std::{io::Cursor, path::Path};
+
+	use crate::level::{ednums, read, write};
+
+	use super::*;
+
+	#[test]
+	fn vanilla_smoke() {
+		let lumps = load_level();
+		let level = raw_level(&lumps);
+		let built = NodeBuilder::new("MAP01", level)
+			.unwrap()
+			.run(&Options::default());
+
+		assert_eq!(built.magic_number(true), *b"ZNOD");
+
+		assert_eq!(
+			checksum(write::nodes(&built.nodes())),
+			"375e670aef63eddb364b41b40f19ee02"
+		);
+
+		assert_eq!(
+			checksum(write::segs(&built.segs())),
+			"9bc66ebed4271c73bb938b76b20f204c"
+		);
+
+		assert_eq!(
+			checksum(write::ssectors(&built.subsectors())),
+			"41496992928328ea481f60f1cbb13dc5"
+		);
+
+		assert_eq!(
+			checksum(bytemuck::cast_slice(&built.blockmap())),
+			"ca8320b3126bf740d558220f802a3f71"
+		);
+	}
+
+	#[test]
+	fn extended_smoke() {
+		let lumps = load_level();
+		let level = raw_level(&lumps);
+		let built = NodeBuilder::new("MAP01", level)
+			.unwrap()
+			.run(&Options::default());
+
+		let mut bytes = b"XNOD".to_vec();
+		let verts = built.vertices_ext();
+		bytes.extend((built.orig_vertex_count() as u32).to_le_bytes());
+		bytes.extend((verts.len() as u32).to_le_bytes());
+
+		for vert in verts {
+			bytes.extend(vert.x.to_le_bytes());
+			bytes.extend(vert.y.to_le_bytes());
+		}
+
+		let subsectors = built.subsectors_ext();
+		bytes.extend((subsectors.len() as u32).to_le_bytes());
+
+		for subsector in subsectors {
+			bytes.extend(subsector.num_lines.to_le_bytes());
+		}
+
+		let segs = built.segs_ext();
+		bytes.extend((segs.len() as u32).to_le_bytes());
+
+		// Line and side numbers are truncated the same way
+		// as when ZNBX writes an extended nodes lump.
+		for seg in segs {
+			bytes.extend(seg.v1.to_le_bytes());
+			bytes.extend(seg.v2.to_le_bytes());
+			bytes.extend(&seg.linedef.to_le_bytes()[..2]);
+			bytes.push(seg.side.to_le_bytes()[0]);
+		}
+
+		push_nodes_ext(&mut bytes, &built.nodes_ext());
+		assert_eq!(checksum(&bytes), "30025de1f1cf2a091cd7e2c92ea0af88");
+	}
+
+	/// ZNBX only considers the geometry of a level (besides polyobjects),
+	/// so it should build the same nodes for the level in Hexen's format.
+	#[test]
+	fn hexen_smoke() {
+		let lumps = load_level();
+		let vanilla = NodeBuilder::new("MAP01", raw_level(&lumps))
+			.unwrap()
+			.run(&Options::default());
+
+		// In Hexen, these editor numbers are polyobject anchors and spawn spots.
+		let polyobj = ednums::HEXEN_ANCHOR..=ednums::HEXEN_SPAWNCRUSH;
+
+		let things = read::things(&lumps[0])
+			.unwrap()
+			.iter()
+			.filter(|thing| !polyobj.contains(&thing.editor_num()))
+			.map(|thing| {
+				let [x, y] = thing.position();
+
+				read::ThingExtRaw::new(
+					0,
+					[x, y, 0],
+					thing.angle(),
+					thing.editor_num(),
+					thing.flags(),
+					0,
+					[0; 5],
+				)
+			})
+			.collect::<Vec<_>>();
+
+		let linedefs = read::linedefs(&lumps[1])
+			.unwrap()
+			.iter()
+			.map(|line| {
+				read::LineDefExtRaw::new(
+					[line.start_vertex(), line.end_vertex()],
+					line.flags(),
+					0,
+					[0; 5],
+					line.right_side(),
+					line.left_side(),
+				)
+			})
+			.collect::<Vec<_>>();
+
+		let level = RawLevel {
+			things: RawThings::Ext(&things),
+			linedefs: RawLineDefs::Ext(&linedefs),
+			..raw_level(&lumps)
+		};
+
+		let mixed = RawLevel {
+			things: RawThings::Ext(&things),
+			..raw_level(&lumps)
+		};
+
+		assert!(matches!(
+			NodeBuilder::new("MAP01", mixed),
+			Err(Error::NodeBuild(_))
+		));
+
+		let built = NodeBuilder::new("MAP01", level)
+			.unwrap()
+			.run(&Options::default());
+
+		assert_eq!(built.nodes(), vanilla.nodes());
+		assert_eq!(built.segs(), vanilla.segs());
+		assert_eq!(built.subsectors(), vanilla.subsectors());
+		assert_eq!(built.blockmap(), vanilla.blockmap());
+	}
+
+	#[test]
+	fn glnodes_smoke() {
+		let lumps = load_level();
+		let level = raw_level(&lumps);
+
+		let built = NodeBuilder::new("MAP01", level).unwrap().run(&Options {
+			gl_nodes: true,
+			..Default::default()
+		});
+
+		assert_eq!(
+			checksum(write::nodes(&built.nodes_gl())),
+			"f1d971b1b0188c4cdbd32b7b4d1123f1"
+		);
+
+		let segs = built.segs_gl();
+
+		// SAFETY: `znbx_SegGl` is plain old data without padding.
+		let segs = unsafe {
+			std::slice::from_raw_parts(segs.as_ptr().cast::<u8>(), std::mem::size_of_val(&*segs))
+		};
+
+		assert_eq!(checksum(segs), "dfed7b623c2136bc727562d958a4c9b3");
+
+		assert_eq!(
+			checksum(write::ssectors(&built.subsectors_gl())),
+			"8aa841c49b27f02232bede64205c8790"
+		);
+	}
+
+	#[test]
+	fn udmf_smoke() {
+		let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../sample/udmf.wad");
+		let textmap = read_lumps(&path, 1).pop().unwrap();
+		let built = NodeBuilder::new_udmf("MAP01", &textmap)
+			.unwrap()
+			.run(&Options::default());
+
+		assert_eq!(built.magic_number(false), *b"XGLN");
+
+		let mut bytes = b"XGLN".to_vec();
+		let verts = built.vertices_gl();
+		bytes.extend((built.orig_vertex_count() as u32).to_le_bytes());
+		bytes.extend((verts.len() as u32).to_le_bytes());
+
+		for vert in verts {
+			bytes.extend(vert.x.to_le_bytes());
+			bytes.extend(vert.y.to_le_bytes());
+		}
+
+		let subsectors = built.subsectors_glx();
+		bytes.extend((subsectors.len() as u32).to_le_bytes());
+
+		for subsector in subsectors {
+			bytes.extend(subsector.num_lines.to_le_bytes());
+		}
+
+		let segs = built.segs_glx();
+		bytes.extend((segs.len() as u32).to_le_bytes());
+
+		for seg in segs {
+			bytes.extend(seg.v1.to_le_bytes());
+			bytes.extend(seg.partner.to_le_bytes());
+			bytes.extend(&seg.linedef.to_le_bytes()[..2]);
+			bytes.push(seg.side.to_le_bytes()[0]);
+		}
+
+		push_nodes_ext(&mut bytes, &built.nodes_glx());
+		assert_eq!(checksum(&bytes), "39ed77ca24155506b2455a887243c3ef");
+	}
+
+	// Details and helpers /////////////////////////////////////////////////////
+
+	#[must_use]
+	fn checksum(bytes: &[u8]) -> String {
+		format!("{:#?}", md5::compute(bytes))
+	}
+
+	/// Node count, then each node as it would be written to an extended nodes lump.
+	fn push_nodes_ext(bytes: &mut Vec<u8>, nodes: &[sys::znbx_NodeEx]) {
+		bytes.extend((nodes.len() as u32).to_le_bytes());
+
+		for node in nodes {
+			for coord in [node.x, node.y, node.dx, node.dy] {
+				bytes.extend(((coord >> 16) as i16).to_le_bytes());
+			}
+
+			for bbox in node.bbox {
+				for coord in bbox {
+					bytes.extend(&coord.to_le_bytes()[..2]);
+				}
+			}
+
+			for child in node.children {
+				bytes.extend(child.to_le_bytes());
+			}
+		}
+	}
+
+	/// Freedoom 2's MAP01, from `THINGS` to `SECTORS`.
+	#[must_use]
+	fn load_level() -> Vec<Vec<u8>> {
+		let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../sample/freedoom2/map01.wad");
+		read_lumps(&path, 8)
+	}
+
+	/// The `count` lumps after the marker of the level `MAP01`.
+	#[must_use]
+	fn read_lumps(path: &Path, count: usize) -> Vec<Vec<u8>> {
+		let wad_bytes = std::fs::read(path).unwrap();
+		let mut reader = wadload::Reader::new(Cursor::new(wad_bytes)).unwrap();
+
+		while reader
+			.next()
+			.is_some_and(|result| result.is_ok_and(|(d, _)| !d.name.eq_ignore_ascii_case("MAP01")))
+		{}
+
+		(0..count)
+			.map(|_| reader.next().unwrap().unwrap().1)
+			.collect()
+	}
+
+	#[must_use]
+	fn raw_level(lumps: &[Vec<u8>]) -> RawLevel<'_> {
+		RawLevel {
+			things: RawThings::Doom(read::things(&lumps[0]).unwrap()),
+			linedefs: RawLineDefs::Doom(read::linedefs(&lumps[1]).unwrap()),
+			sidedefs: read::sidedefs(&lumps[2]).unwrap(),
+			vertices: read::vertexes(&lumps[3]).unwrap(),
+			segs: read::segs(&lumps[4]).unwrap(),
+			subsectors: read::ssectors(&lumps[5]).unwrap(),
+			nodes: read::nodes(&lumps[6]).unwrap(),
+			sectors: read::sectors(&lumps[7]).unwrap(),
+		}
+	}
+}
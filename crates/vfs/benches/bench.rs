@@ -2,7 +2,7 @@ use std::path::{Path, PathBuf};
 
 use viletech_fs::{VPath, VirtualFs};
 
-criterion::criterion_group!(benches, operations);
+criterion::criterion_group!(benches, operations, large_archive);
 criterion::criterion_main!(benches);
 
 fn operations(crit: &mut criterion::Criterion) {
@@ -47,6 +47,100 @@ fn operations(crit: &mut criterion::Criterion) {
 	grp.finish();
 }
 
+/// Lookups in a synthetic PK3 with many entries, which do not require any
+/// sample data to be present.
+fn large_archive(crit: &mut criterion::Criterion) {
+	const DIRS: usize = 64;
+	const FILES_PER_DIR: usize = 512;
+
+	let path = std::env::temp_dir().join(format!("viletech-fs-bench-{}.pk3", std::process::id()));
+	std::fs::write(&path, large_zip(DIRS, FILES_PER_DIR)).unwrap();
+
+	let mut grp = crit.benchmark_group("Mount");
+
+	grp.bench_function("PK3, Large", |bencher| {
+		bencher.iter_batched_ref(
+			VirtualFs::default,
+			|vfs| {
+				vfs.mount(&path, VPath::new("large")).unwrap();
+			},
+			criterion::BatchSize::SmallInput,
+		);
+	});
+
+	grp.finish();
+
+	let mut vfs = VirtualFs::default();
+	vfs.mount(&path, VPath::new("large")).unwrap();
+	std::fs::remove_file(&path).unwrap();
+
+	let mut grp = crit.benchmark_group("Lookup");
+
+	grp.bench_function("PK3, First", |bencher| {
+		bencher.iter(|| {
+			let _ = std::hint::black_box(vfs.lookup(VPath::new("/large/dir0/file0.txt")).unwrap());
+		});
+	});
+
+	let last = format!("/LARGE/DIR{}/FILE{}.TXT", DIRS - 1, FILES_PER_DIR - 1);
+
+	grp.bench_function("PK3, Last", |bencher| {
+		bencher.iter(|| {
+			let _ = std::hint::black_box(vfs.lookup(VPath::new(&last)).unwrap());
+		});
+	});
+
+	grp.bench_function("PK3, Missing", |bencher| {
+		bencher.iter(|| {
+			let _ = std::hint::black_box(vfs.lookup(VPath::new("/large/dir0/nonexistent")));
+		});
+	});
+
+	grp.finish();
+}
+
+/// A zip archive of empty, uncompressed files.
+#[must_use]
+fn large_zip(dirs: usize, files_per_dir: usize) -> Vec<u8> {
+	let mut zip = vec![];
+	let mut cdir = vec![];
+	let count = dirs * files_per_dir;
+
+	for d in 0..dirs {
+		for f in 0..files_per_dir {
+			let name = format!("dir{d}/file{f}.txt");
+			let offs = zip.len() as u32;
+			let mut fields = vec![];
+			fields.extend(20_u16.to_le_bytes()); // Version needed to extract.
+			fields.extend([0; 20]); // Flags through uncompressed size.
+			fields.extend((name.len() as u16).to_le_bytes());
+			fields.extend(0_u16.to_le_bytes()); // Extra field length.
+
+			zip.extend(0x04034B50_u32.to_le_bytes());
+			zip.extend(&fields);
+			zip.extend(name.as_bytes());
+
+			cdir.extend(0x02014B50_u32.to_le_bytes());
+			cdir.extend(20_u16.to_le_bytes()); // Version made by.
+			cdir.extend(&fields);
+			cdir.extend([0; 10]); // Comment length through external attributes.
+			cdir.extend(offs.to_le_bytes());
+			cdir.extend(name.as_bytes());
+		}
+	}
+
+	let cdir_offs = zip.len() as u32;
+	zip.extend(&cdir);
+	zip.extend(0x06054B50_u32.to_le_bytes());
+	zip.extend([0; 4]); // Disk numbers.
+	zip.extend((count as u16).to_le_bytes());
+	zip.extend((count as u16).to_le_bytes());
+	zip.extend((cdir.len() as u32).to_le_bytes());
+	zip.extend(cdir_offs.to_le_bytes());
+	zip.extend(0_u16.to_le_bytes()); // Comment length.
+	zip
+}
+
 #[must_use]
 fn freedoom2_path() -> Option<PathBuf> {
 	let Ok(evar) = std::env::var("VILETECHFS_SAMPLE_DIR") else {
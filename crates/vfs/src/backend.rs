@@ -0,0 +1,216 @@
+//! [`ArchiveBackend`] and its related symbols.
+
+use std::{
+	collections::HashMap,
+	io::{Read, Seek},
+	ops::Range,
+	sync::Arc,
+};
+
+use parking_lot::Mutex;
+use util::SmallString;
+
+use crate::{
+	detail::{Compression, Reader},
+	Error, FileSlot, FolderKind, FolderSlot, MountFormat, MountWarning, Slot, VFile, VFolder,
+	VirtualFs,
+};
+
+/// How many bytes from the start of a file are passed to [`ArchiveBackend::detect`].
+pub const MAGIC_LEN: usize = 16;
+
+/// A container format which [`VirtualFs::mount`] can expand into a subtree.
+///
+/// WAD, zip, 7z, Quake PAK, and Build GRP support are built in; implement this
+/// trait and pass it to [`VirtualFs::register_backend`] to add more.
+pub trait ArchiveBackend: std::fmt::Debug + Send + Sync {
+	/// Should be short, and unique among all backends registered to one VFS
+	/// (e.g. `"rff"` for Blood's RFF format).
+	#[must_use]
+	fn name(&self) -> &'static str;
+
+	/// `magic` is the start of the file, which is [`MAGIC_LEN`] bytes long
+	/// unless the file is shorter than that. `file_name` is the last component
+	/// of the physical path being mounted.
+	#[must_use]
+	fn detect(&self, magic: &[u8], file_name: &str) -> bool;
+
+	/// Enumerates the archive in `source` into [`MountContext::root`].
+	/// `source` is positioned at its start upon being passed.
+	///
+	/// If this returns an error, everything added to `ctx` is removed again.
+	fn mount(&self, ctx: &mut MountContext, source: Box<dyn Source>) -> Result<(), Error>;
+
+	/// Reported via [`crate::MountInfo::format`].
+	#[must_use]
+	fn format(&self) -> MountFormat {
+		MountFormat::Other(self.name())
+	}
+
+	/// The kind of the folder at the root of each mount.
+	#[must_use]
+	fn folder_kind(&self) -> FolderKind {
+		FolderKind::Other(self.name())
+	}
+
+	/// The kind of folders created via [`MountContext::folder`].
+	#[must_use]
+	fn dir_kind(&self) -> FolderKind {
+		FolderKind::OtherDir(self.name())
+	}
+}
+
+/// Reads the content of the entries in one mounted archive, as created by an
+/// [`ArchiveBackend`]. See [`SharedReader::new`].
+pub trait ArchiveReader: std::fmt::Debug + Send {
+	/// `span` is exactly what was passed to [`MountContext::file`];
+	/// an archive reader is free to interpret it however it wishes.
+	fn read(&mut self, span: Range<usize>) -> Result<Vec<u8>, Error>;
+}
+
+/// A seekable byte stream out of which an archive can be mounted.
+/// Blanket-implemented for anything that fits the bounds.
+pub trait Source: Read + Seek + Send + std::fmt::Debug {}
+
+impl<T: Read + Seek + Send + std::fmt::Debug> Source for T {}
+
+/// A handle to an [`ArchiveReader`] which can be shared between many virtual
+/// files; reading from one locks the reader for all of them.
+#[derive(Debug, Clone)]
+pub struct SharedReader(pub(crate) Arc<Mutex<Reader>>);
+
+impl SharedReader {
+	#[must_use]
+	pub fn new<R: ArchiveReader + 'static>(reader: R) -> Self {
+		Self::from_inner(Reader::Custom(Box::new(reader)))
+	}
+
+	#[must_use]
+	pub(crate) fn from_inner(reader: Reader) -> Self {
+		Self(Arc::new(Mutex::new(reader)))
+	}
+}
+
+/// Passed to [`ArchiveBackend::mount`] to populate the subtree of a new mount.
+#[derive(Debug)]
+pub struct MountContext<'vfs> {
+	pub(crate) vfs: &'vfs mut VirtualFs,
+	pub(crate) root: FolderSlot,
+	pub(crate) dir_kind: FolderKind,
+	pub(crate) warnings: Vec<MountWarning>,
+}
+
+impl MountContext<'_> {
+	/// The folder representing the archive itself.
+	#[must_use]
+	pub fn root(&self) -> FolderSlot {
+		self.root
+	}
+
+	#[must_use]
+	pub fn vfs(&self) -> &VirtualFs {
+		self.vfs
+	}
+
+	/// Records something about the archive which users should be told about;
+	/// see [`crate::MountInfo::warnings`]. Discarded if mounting fails.
+	pub fn warn(&mut self, warning: MountWarning) {
+		self.warnings.push(warning);
+	}
+
+	/// Splits an entry's path on forward and back slashes, leaving out empty
+	/// components, and leaving out `.` and `..` with a warning.
+	#[must_use]
+	pub fn components<'p>(&mut self, path: &'p str) -> Vec<&'p str> {
+		let mut dropped = false;
+
+		let ret = path
+			.split(['/', '\\'])
+			.filter(|pcomp| match *pcomp {
+				"" => false,
+				"." | ".." => {
+					dropped = true;
+					false
+				}
+				_ => true,
+			})
+			.collect();
+
+		if dropped {
+			self.warn(MountWarning::ComponentsDropped {
+				path: path.to_owned(),
+			});
+		}
+
+		ret
+	}
+
+	/// Returns the slot of the subfolder of `parent` named `name` (ASCII
+	/// case-insensitively, as with lookups), creating it first if it does
+	/// not exist yet.
+	pub fn folder(&mut self, parent: FolderSlot, name: &str) -> FolderSlot {
+		let existing = self.vfs.folders[parent]
+			.children_named(name)
+			.iter()
+			.find_map(|slot| match slot {
+				Slot::Folder(sfslot) => Some(*sfslot),
+				Slot::File(_) => None,
+			});
+
+		if let Some(sfslot) = existing {
+			return sfslot;
+		}
+
+		let sfslot = self.vfs.folders.insert(VFolder {
+			name: SmallString::from(name),
+			parent: Some(parent),
+			files: indexmap::indexset![],
+			subfolders: indexmap::indexset![],
+			index: HashMap::default(),
+			kind: self.dir_kind,
+		});
+
+		self.vfs.folders[parent].link_subfolder(sfslot, name);
+		sfslot
+	}
+
+	/// Like [`Self::folder`], but descends from [`Self::root`] through
+	/// every one of `components` in turn, returning the last folder.
+	pub fn folder_path<'c>(&mut self, components: impl IntoIterator<Item = &'c str>) -> FolderSlot {
+		components
+			.into_iter()
+			.fold(self.root, |parent, comp| self.folder(parent, comp))
+	}
+
+	/// Adds a virtual file named `name` to `parent`.
+	/// Reading it passes `span` to `reader`.
+	pub fn file(
+		&mut self,
+		parent: FolderSlot,
+		name: &str,
+		reader: &SharedReader,
+		span: Range<u32>,
+	) -> FileSlot {
+		self.file_compressed(parent, name, reader, span, Compression::None)
+	}
+
+	pub(crate) fn file_compressed(
+		&mut self,
+		parent: FolderSlot,
+		name: &str,
+		reader: &SharedReader,
+		span: Range<u32>,
+		compression: Compression,
+	) -> FileSlot {
+		let islot = self.vfs.files.insert(VFile {
+			name: SmallString::from(name),
+			parent,
+			reader: reader.0.clone(),
+			span,
+			compression,
+		});
+
+		self.vfs.folders[parent].link_file(islot, name);
+		islot
+	}
+}
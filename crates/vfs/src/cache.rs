@@ -0,0 +1,179 @@
+//! [`ContentCache`] and its related symbols.
+
+use std::{
+	collections::{BTreeMap, HashMap},
+	sync::Arc,
+};
+
+use parking_lot::Mutex;
+
+use crate::{Error, FileRef, FileSlot, VirtualFs};
+
+/// A least-recently-used cache of the decompressed content of virtual files,
+/// bounded by a budget of bytes. Safe to use from many threads at once.
+///
+/// Every [`VirtualFs`] has one, which starts with a budget of zero (i.e. disabled).
+/// It is read through by [`FileRef::read_cached`], and kept up-to-date by
+/// every operation which changes or removes a file.
+#[derive(Debug, Default)]
+pub struct ContentCache {
+	inner: Mutex<CacheInner>,
+}
+
+#[derive(Debug, Default)]
+struct CacheInner {
+	budget: usize,
+	/// The total length of every entry's content.
+	used: usize,
+	/// The number is the entry's key in `order`.
+	entries: HashMap<FileSlot, (Arc<[u8]>, u64)>,
+	/// Lower keys were used less recently.
+	order: BTreeMap<u64, FileSlot>,
+	/// Incremented upon every use of an entry.
+	tick: u64,
+	stats: CacheStats,
+}
+
+/// See [`ContentCache::stats`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+	pub hits: u64,
+	pub misses: u64,
+	/// How many entries have been removed to stay within the budget.
+	pub evictions: u64,
+	/// How many entries are currently in the cache.
+	pub entries: usize,
+	/// The total length of the content currently in the cache.
+	pub bytes: usize,
+}
+
+impl ContentCache {
+	/// In bytes.
+	#[must_use]
+	pub fn budget(&self) -> usize {
+		self.inner.lock().budget
+	}
+
+	/// Evicts least-recently-used entries until the cache fits within `bytes`.
+	/// Setting this to zero disables the cache.
+	pub fn set_budget(&self, bytes: usize) {
+		let mut inner = self.inner.lock();
+		inner.budget = bytes;
+		inner.evict_to(bytes);
+	}
+
+	/// Hit, miss, and eviction counts are cumulative since
+	/// creation or the last call to [`Self::reset_stats`].
+	#[must_use]
+	pub fn stats(&self) -> CacheStats {
+		let inner = self.inner.lock();
+
+		CacheStats {
+			entries: inner.entries.len(),
+			bytes: inner.used,
+			..inner.stats
+		}
+	}
+
+	pub fn reset_stats(&self) {
+		self.inner.lock().stats = CacheStats::default();
+	}
+
+	#[must_use]
+	pub fn contains(&self, slot: FileSlot) -> bool {
+		self.inner.lock().entries.contains_key(&slot)
+	}
+
+	/// Removes every entry; the budget and statistics are unaffected.
+	pub fn clear(&self) {
+		let mut inner = self.inner.lock();
+		inner.entries.clear();
+		inner.order.clear();
+		inner.used = 0;
+	}
+
+	/// Counts a hit or miss, and marks the entry as most recently used upon a hit.
+	#[must_use]
+	fn get(&self, slot: FileSlot) -> Option<Arc<[u8]>> {
+		let mut inner = self.inner.lock();
+		let inner = &mut *inner;
+
+		let Some((bytes, used)) = inner.entries.get_mut(&slot) else {
+			inner.stats.misses += 1;
+			return None;
+		};
+
+		inner.stats.hits += 1;
+		inner.tick += 1;
+		inner.order.remove(used);
+		inner.order.insert(inner.tick, slot);
+		*used = inner.tick;
+		Some(bytes.clone())
+	}
+
+	/// Does nothing if `bytes` is longer than the budget.
+	fn insert(&self, slot: FileSlot, bytes: Arc<[u8]>) {
+		let mut inner = self.inner.lock();
+
+		if bytes.len() > inner.budget {
+			return;
+		}
+
+		let room = inner.budget - bytes.len();
+		inner.remove(slot);
+		inner.evict_to(room);
+		inner.tick += 1;
+		let tick = inner.tick;
+		inner.used += bytes.len();
+		inner.order.insert(tick, slot);
+		inner.entries.insert(slot, (bytes, tick));
+	}
+
+	/// See [`VirtualFs::forget_content`].
+	pub(crate) fn remove(&self, slot: FileSlot) {
+		self.inner.lock().remove(slot);
+	}
+}
+
+impl CacheInner {
+	fn remove(&mut self, slot: FileSlot) {
+		if let Some((bytes, tick)) = self.entries.remove(&slot) {
+			self.order.remove(&tick);
+			self.used -= bytes.len();
+		}
+	}
+
+	fn evict_to(&mut self, bytes: usize) {
+		while self.used > bytes {
+			let Some((_, slot)) = self.order.pop_first() else {
+				break;
+			};
+
+			if let Some((content, _)) = self.entries.remove(&slot) {
+				self.used -= content.len();
+				self.stats.evictions += 1;
+			}
+		}
+	}
+}
+
+impl VirtualFs {
+	#[must_use]
+	pub fn cache(&self) -> &ContentCache {
+		&self.cache
+	}
+}
+
+impl FileRef<'_> {
+	/// Like reading through [`Self::lock`], but the decompressed content is
+	/// taken from, or stored in, the VFS' [`ContentCache`] (if it fits the budget).
+	pub fn read_cached(&self) -> Result<Arc<[u8]>, Error> {
+		if let Some(bytes) = self.vfs.cache.get(self.slot) {
+			return Ok(bytes);
+		}
+
+		let bytes = Arc::<[u8]>::from(self.lock().read()?.as_ref());
+		self.vfs.cache.insert(self.slot, bytes.clone());
+		Ok(bytes)
+	}
+}
@@ -8,10 +8,12 @@ use std::{
 	sync::Arc,
 };
 
+use bzip2::read::BzDecoder;
 use flate2::read::DeflateDecoder;
-use parking_lot::RwLock;
+use parking_lot::Mutex;
+use zstd::stream::read::Decoder as ZstdDecoder;
 
-use super::{Error, FolderSlot, VirtualFs};
+use super::{ArchiveReader, Error, FileStream, FolderSlot, Source, VirtualFs};
 
 pub(super) fn path_append(vfs: &VirtualFs, buf: &mut String, slot: FolderSlot) {
 	let folder = &vfs.folders[slot];
@@ -28,21 +30,60 @@ pub(super) fn path_append(vfs: &VirtualFs, buf: &mut String, slot: FolderSlot) {
 }
 
 pub(super) fn decompress(bytes: Cow<[u8]>, compression: Compression) -> Result<Cow<[u8]>, Error> {
+	let mut decompressed = vec![];
+
 	match compression {
-		Compression::None => Ok(bytes),
+		Compression::None => return Ok(bytes),
+		Compression::Bzip2 => {
+			BzDecoder::new(&bytes[..])
+				.read_to_end(&mut decompressed)
+				.map_err(Error::Decompress)?;
+		}
 		Compression::Deflate => {
-			let mut deflater = DeflateDecoder::new(&bytes[..]);
-			let mut decompressed = vec![];
-
-			deflater
+			DeflateDecoder::new(&bytes[..])
 				.read_to_end(&mut decompressed)
 				.map_err(Error::Decompress)?;
+		}
+		Compression::Lzma { unpacked_size } => {
+			// Zip archives precede the LZMA properties with a 2-byte
+			// version number and a 2-byte length of the properties.
+			let Some(stream) = bytes.get(4..) else {
+				return Err(Error::Decompress(std::io::ErrorKind::UnexpectedEof.into()));
+			};
+
+			let options = lzma_rs::decompress::Options {
+				unpacked_size: lzma_rs::decompress::UnpackedSize::UseProvided(Some(
+					unpacked_size as u64,
+				)),
+				..Default::default()
+			};
 
-			Ok(Cow::Owned(decompressed))
+			// The size comes from the archive, so it can not be trusted to be
+			// anywhere near accurate; beyond this, let the buffer grow as needed.
+			decompressed.reserve_exact((unpacked_size as usize).min(bytes.len().saturating_mul(8)));
+
+			lzma_rs::lzma_decompress_with_options(&mut &stream[..], &mut decompressed, &options)
+				.map_err(lzma_error)?;
+		}
+		Compression::Xz => {
+			lzma_rs::xz_decompress(&mut &bytes[..], &mut decompressed).map_err(lzma_error)?;
 		}
-		Compression::Bzip2 | Compression::Lzma | Compression::Xz | Compression::Zstd => {
-			unimplemented!()
+		Compression::Zstd => {
+			ZstdDecoder::new(&bytes[..])
+				.and_then(|mut decoder| decoder.read_to_end(&mut decompressed))
+				.map_err(Error::Decompress)?;
 		}
+		Compression::Unsupported(method) => return Err(Error::UnsupportedCompression(method)),
+	}
+
+	Ok(Cow::Owned(decompressed))
+}
+
+#[must_use]
+fn lzma_error(err: lzma_rs::error::Error) -> Error {
+	match err {
+		lzma_rs::error::Error::IoError(err) => Error::Decompress(err),
+		other => Error::Decompress(std::io::Error::new(std::io::ErrorKind::InvalidData, other)),
 	}
 }
 
@@ -51,8 +92,12 @@ pub(crate) enum Reader {
 	/// e.g. lump in a WAD, or entry in a zip archive.
 	File(File),
 	Memory(Vec<u8>),
-	/// e.g. entry in a zip archive nested within another zip archive.
-	_Super(ReaderLayer),
+	/// e.g. lump in a WAD mounted by an [`crate::ArchiveBackend`].
+	Stream(Box<dyn Source>),
+	/// Supplied by an [`crate::ArchiveBackend`] via [`crate::SharedReader::new`].
+	Custom(Box<dyn ArchiveReader>),
+	/// e.g. lump in a WAD nested within a zip archive.
+	Super(ReaderLayer),
 }
 
 impl Reader {
@@ -63,18 +108,51 @@ impl Reader {
 	) -> Result<Cow<[u8]>, Error> {
 		let bytes = match self {
 			Self::File(ref mut fh) => Cow::Owned(Self::read_from_file(fh, span)?),
-			Self::Memory(bytes) => Cow::Borrowed(bytes.as_slice()),
-			Self::_Super(layer) => {
-				let mut guard = layer.parent.write();
-				let cow = guard.read(layer.span.clone(), layer.compression)?;
-				Cow::Owned(cow.into_owned())
-			}
+			Self::Memory(bytes) => Cow::Borrowed(bytes.get(span).ok_or_else(out_of_bounds)?),
+			Self::Stream(src) => Cow::Owned(Self::read_from_file(src, span)?),
+			Self::Custom(reader) => Cow::Owned(reader.read(span)?),
+			Self::Super(layer) => Cow::Owned(layer.read(span)?),
 		};
 
 		decompress(bytes, compression)
 	}
 
-	pub(super) fn read_from_file(fh: &mut File, span: Range<usize>) -> Result<Vec<u8>, Error> {
+	/// Fills `buf` with the bytes starting at `offset`, without decompressing anything.
+	pub(crate) fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), Error> {
+		let span = offset..(offset + buf.len());
+
+		match self {
+			Self::File(ref mut fh) => Self::read_into(fh, offset, buf),
+			Self::Memory(bytes) => {
+				buf.copy_from_slice(bytes.get(span).ok_or_else(out_of_bounds)?);
+				Ok(())
+			}
+			Self::Stream(src) => Self::read_into(src, offset, buf),
+			Self::Custom(reader) => {
+				buf.copy_from_slice(&reader.read(span)?);
+				Ok(())
+			}
+			Self::Super(layer) => {
+				buf.copy_from_slice(&layer.read(span)?);
+				Ok(())
+			}
+		}
+	}
+
+	fn read_into<R: Read + Seek + ?Sized>(
+		fh: &mut R,
+		offset: usize,
+		buf: &mut [u8],
+	) -> Result<(), Error> {
+		fh.seek(SeekFrom::Start(offset as u64))
+			.map_err(Error::Seek)?;
+		fh.read_exact(buf).map_err(Error::FileRead)
+	}
+
+	pub(super) fn read_from_file<R: Read + Seek + ?Sized>(
+		fh: &mut R,
+		span: Range<usize>,
+	) -> Result<Vec<u8>, Error> {
 		fh.seek(SeekFrom::Start(span.start as u64))
 			.map_err(Error::Seek)?;
 		let mut bytes = vec![0; span.len()];
@@ -83,20 +161,108 @@ impl Reader {
 	}
 }
 
+/// One entry of a parent reader, treated as a file in its own right
+/// (e.g. a WAD nested within a zip archive). Spans read from a layer are
+/// relative to the start of the entry's decompressed content.
 #[derive(Debug)]
 pub(crate) struct ReaderLayer {
-	pub(crate) parent: Arc<RwLock<Reader>>,
+	pub(crate) parent: Arc<Mutex<Reader>>,
+	/// Of the entry, within `parent`.
 	pub(crate) span: Range<usize>,
 	pub(crate) compression: Compression,
+	/// Compressed entries get read through an incremental decoder, which is kept
+	/// between reads so that reading spans in order only decompresses the entry
+	/// once, without ever holding all of it. LZMA and XZ entries have no such
+	/// decoder, so those get decompressed in full by every read instead.
+	/// Always `None` for uncompressed entries, which read through to `parent`.
+	pub(crate) stream: Option<FileStream>,
+}
+
+impl ReaderLayer {
+	#[must_use]
+	pub(crate) fn new(
+		parent: Arc<Mutex<Reader>>,
+		span: Range<usize>,
+		compression: Compression,
+	) -> Self {
+		Self {
+			parent,
+			span,
+			compression,
+			stream: None,
+		}
+	}
+
+	pub(crate) fn read(&mut self, span: Range<usize>) -> Result<Vec<u8>, Error> {
+		if self.compression == Compression::None {
+			let start = self.span.start + span.start;
+			let end = self.span.start + span.end;
+
+			if end > self.span.end {
+				return Err(out_of_bounds());
+			}
+
+			let mut guard = self.parent.lock();
+			return Ok(guard.read(start..end, Compression::None)?.into_owned());
+		}
+
+		let stream = self.stream.get_or_insert_with(|| {
+			FileStream::new(
+				self.parent.clone(),
+				self.span.clone(),
+				self.compression,
+				None,
+			)
+		});
+
+		let mut ret = vec![0; span.len()];
+
+		let result = stream
+			.seek(SeekFrom::Start(span.start as u64))
+			.and_then(|_| stream.read_exact(&mut ret));
+
+		// A decoder which has failed can not be trusted to continue.
+		if result.is_err() || !stream.is_incremental() {
+			self.stream = None;
+		}
+
+		match result {
+			Ok(()) => Ok(ret),
+			Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => Err(out_of_bounds()),
+			Err(err) => Err(Error::Decompress(err)),
+		}
+	}
+}
+
+/// For implementing [`Read`] and [`Seek`] on top of this crate's readers.
+#[must_use]
+pub(crate) fn io_error(err: Error) -> std::io::Error {
+	match err {
+		Error::FileRead(err) | Error::Seek(err) | Error::Decompress(err) => err,
+		other => std::io::Error::other(other.to_string()),
+	}
+}
+
+#[must_use]
+fn out_of_bounds() -> Error {
+	Error::FileRead(std::io::ErrorKind::UnexpectedEof.into())
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) enum Compression {
 	/// Always the case for WAD lumps.
 	None,
 	Bzip2,
 	Deflate,
-	Lzma,
+	/// Decoding requires knowing the decompressed size up-front.
+	Lzma {
+		unpacked_size: u32,
+	},
 	Xz,
 	Zstd,
+	/// Holds the method number from a zip archive's central directory.
+	/// Attempting to read an entry with this compression raises
+	/// [`Error::UnsupportedCompression`].
+	Unsupported(u16),
 }
@@ -0,0 +1,97 @@
+//! [`ContentHash`] and its related symbols.
+
+use indexmap::IndexMap;
+use rayon::prelude::*;
+
+use crate::{Error, FileRef, FileSlot, VirtualFs};
+
+/// The SHA-1 digest of a virtual file's decompressed content.
+///
+/// This does not depend on the file's path or on how its archive stores it, and
+/// is stable between runs, so it is suitable for keying caches of data derived
+/// from a file's content (e.g. compiled scripts, converted textures).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ContentHash(pub [u8; 20]);
+
+impl ContentHash {
+	/// The hash of zero bytes.
+	pub const EMPTY: Self = Self([
+		0xda, 0x39, 0xa3, 0xee, 0x5e, 0x6b, 0x4b, 0x0d, 0x32, 0x55, 0xbf, 0xef, 0x95, 0x60, 0x18,
+		0x90, 0xaf, 0xd8, 0x07, 0x09,
+	]);
+}
+
+impl std::fmt::Display for ContentHash {
+	/// As lowercase hexadecimal.
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		for byte in self.0 {
+			write!(f, "{byte:02x}")?;
+		}
+
+		Ok(())
+	}
+}
+
+/// A group of files with identical content. See [`VirtualFs::duplicates`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplicates {
+	pub hash: ContentHash,
+	/// Always has at least two elements.
+	pub files: Vec<FileSlot>,
+}
+
+impl VirtualFs {
+	/// Hashes every file which has not been hashed yet (in parallel), and then
+	/// returns every group of two or more files with identical content, across
+	/// all mounts. Empty files (e.g. WAD markers) are never reported.
+	///
+	/// Groups are ordered by their first file, and files are ordered as per
+	/// [`Self::files`].
+	pub fn duplicates(&self) -> Result<Vec<Duplicates>, Error> {
+		let slots = self
+			.files
+			.iter()
+			.filter_map(|(islot, vfile)| (vfile.size() > 0).then_some(islot))
+			.collect::<Vec<_>>();
+
+		let hashes = slots
+			.par_iter()
+			.map(|islot| self.get_file(*islot).unwrap().hash())
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let mut groups = IndexMap::<ContentHash, Vec<FileSlot>>::new();
+
+		for (islot, hash) in slots.into_iter().zip(hashes) {
+			groups.entry(hash).or_default().push(islot);
+		}
+
+		Ok(groups
+			.into_iter()
+			.filter(|(hash, files)| files.len() > 1 && *hash != ContentHash::EMPTY)
+			.map(|(hash, files)| Duplicates { hash, files })
+			.collect())
+	}
+
+	/// Should be called whenever a file's content changes or it gets removed.
+	pub(crate) fn forget_content(&self, slot: FileSlot) {
+		self.cache.remove(slot);
+		self.hashes.lock().remove(&slot);
+	}
+}
+
+impl FileRef<'_> {
+	/// Computed upon the first call (without going through the [`crate::ContentCache`]),
+	/// and then kept until this file's content changes or it gets removed.
+	pub fn hash(&self) -> Result<ContentHash, Error> {
+		if let Some(hash) = self.vfs.hashes.lock().get(&self.slot) {
+			return Ok(*hash);
+		}
+
+		let mut sha1 = sha1_smol::Sha1::new();
+		sha1.update(&self.lock().read()?);
+		let hash = ContentHash(sha1.digest().bytes());
+		self.vfs.hashes.lock().insert(self.slot, hash);
+		Ok(hash)
+	}
+}
@@ -5,16 +5,27 @@
 //! into one tree so that reading from them is more convenient at all other levels
 //! of the engine, without exposing any details of the user's underlying machine.
 
+mod backend;
+mod cache;
 mod detail;
+mod hash;
+mod lumps;
+#[cfg(feature = "serde")]
+mod manifest;
 mod mount;
+mod overlay;
 mod path;
+mod query;
 mod refs;
+mod stream;
+mod watch;
 
 #[cfg(test)]
 mod test;
 
 use std::{
 	borrow::Cow,
+	collections::HashMap,
 	ops::Range,
 	path::{Path, PathBuf},
 	string::FromUtf8Error,
@@ -30,7 +41,13 @@ use zip_structs::zip_error::ZipReadError;
 
 use self::detail::{Compression, Reader};
 
-pub use self::{path::*, refs::*};
+#[cfg(feature = "serde")]
+pub use self::manifest::*;
+pub use self::{
+	backend::*, cache::*, hash::*, lumps::*, overlay::*, path::*, query::*, refs::*, stream::*,
+	watch::*,
+};
+pub use wadload::Namespace;
 
 #[derive(Debug)]
 pub struct VirtualFs {
@@ -38,6 +55,15 @@ pub struct VirtualFs {
 	pub(crate) mounts: Vec<MountInfo>,
 	pub(crate) files: HopSlotMap<FileSlot, VFile>,
 	pub(crate) folders: HopSlotMap<FolderSlot, VFolder>,
+	pub(crate) backends: Vec<Arc<dyn ArchiveBackend>>,
+	/// `None` if the VFS is read-only. See [`Self::mount_overlay`].
+	pub(crate) overlay: Option<Overlay>,
+	/// The state of each real file (or archive's root folder) as of when it was
+	/// last read. See [`Self::refresh`].
+	pub(crate) stamps: HashMap<Slot, watch::Stamp>,
+	pub(crate) cache: ContentCache,
+	/// See [`FileRef::hash`].
+	pub(crate) hashes: Mutex<HashMap<FileSlot, ContentHash>>,
 }
 
 impl VirtualFs {
@@ -74,31 +100,138 @@ impl VirtualFs {
 			return Err(Error::MountSymlink);
 		}
 
-		match mount::mount(self, &canon, mount_point.as_str()) {
-			Ok(mntinfo) => {
-				self.mounts.push(mntinfo);
-				Ok(())
+		let mntinfo = self.mount_canon(&canon, mount_point.as_str(), None)?;
+		self.mounts.push(mntinfo);
+		Ok(())
+	}
+
+	/// Removes the mount at `mount_point` (with or without a leading `/`,
+	/// compared ASCII case-insensitively) and everything under it.
+	/// Returns `None` if nothing is mounted there.
+	pub fn unmount(&mut self, mount_point: &VPath) -> Option<MountInfo> {
+		let i = self.mount_index(mount_point)?;
+		let mntinfo = self.mounts.remove(i);
+
+		match mntinfo.root {
+			Slot::File(islot) => {
+				let removed = self.remove_file(islot);
+				debug_assert!(removed);
 			}
-			Err(err) => {
-				let to_clean = match self.lookup(mount_point) {
-					Some(Ref::File(iref)) => Some(Slot::File(iref.slot)),
-					Some(Ref::Folder(oref)) => Some(Slot::Folder(oref.slot)),
-					None => None,
-				};
-
-				match to_clean {
-					Some(Slot::File(islot)) => {
-						self.remove_file_by_slot(islot);
-					}
-					Some(Slot::Folder(oslot)) => {
-						self.remove_folder_by_slot(oslot);
-					}
-					None => {}
-				}
+			Slot::Folder(oslot) => self.remove_folder_recur(oslot),
+		}
 
-				Err(err)
+		Some(mntinfo)
+	}
+
+	/// Reads the mount at `mount_point` (see [`Self::unmount`]) from its real
+	/// path again. The new mount takes the old one's place in [`Self::mounts`]
+	/// and among the root folder's children, so load order is unchanged.
+	/// Slots under other mounts remain valid, but those under the old mount do not.
+	/// Any changes the [overlay](Self::mount_overlay) made under the old mount
+	/// get applied to the new one.
+	///
+	/// If this returns an error, the old mount is left in place.
+	pub fn remount(&mut self, mount_point: &VPath) -> Result<(), Error> {
+		let i = self.mount_index(mount_point).ok_or(Error::NotFound)?;
+		let real_path = self.mounts[i].real_path.clone();
+		let mpoint = self.mounts[i]
+			.mount_point
+			.as_str()
+			.trim_start_matches('/')
+			.to_owned();
+		let mntinfo = self.mount_canon(&real_path, &mpoint, Some(self.mounts[i].root))?;
+		let old_root = std::mem::replace(&mut self.mounts[i], mntinfo).root;
+		let new_root = self.mounts[i].root;
+
+		let name = match new_root {
+			Slot::File(islot) => self.files[islot].name.clone(),
+			Slot::Folder(oslot) => self.folders[oslot].name.clone(),
+		};
+
+		self.folders[self.root].move_before(new_root, old_root, name.as_str());
+
+		match old_root {
+			Slot::File(islot) => {
+				let removed = self.remove_file(islot);
+				debug_assert!(removed);
 			}
+			Slot::Folder(oslot) => self.remove_folder_recur(oslot),
+		}
+
+		self.reapply_overlay(name.as_str());
+		Ok(())
+	}
+
+	#[must_use]
+	fn mount_index(&self, mount_point: &VPath) -> Option<usize> {
+		let mpoint = mount_point.as_str().trim_start_matches('/');
+
+		self.mounts.iter().position(|mntinfo| {
+			mntinfo
+				.mount_point
+				.as_str()
+				.trim_start_matches('/')
+				.eq_ignore_ascii_case(mpoint)
+		})
+	}
+
+	/// If mounting fails, everything it added to the root folder gets removed.
+	/// See [`mount::mount`] regarding `replacing`.
+	fn mount_canon(
+		&mut self,
+		canon: &Path,
+		mpoint: &str,
+		replacing: Option<Slot>,
+	) -> Result<MountInfo, Error> {
+		let file_count = self.folders[self.root].files.len();
+		let subfolder_count = self.folders[self.root].subfolders.len();
+
+		let err = match mount::mount(self, canon, mpoint, replacing) {
+			Ok(mntinfo) => return Ok(mntinfo),
+			Err(err) => err,
+		};
+
+		let root = &self.folders[self.root];
+		let files = root
+			.files
+			.iter()
+			.skip(file_count)
+			.copied()
+			.collect::<Vec<_>>();
+		let subfolders = root
+			.subfolders
+			.iter()
+			.skip(subfolder_count)
+			.copied()
+			.collect::<Vec<_>>();
+
+		for islot in files {
+			self.remove_file(islot);
 		}
+
+		for sfslot in subfolders {
+			self.remove_folder_recur(sfslot);
+		}
+
+		Err(err)
+	}
+
+	/// Makes [`Self::mount`] able to expand another archive format.
+	///
+	/// When mounting a file (or a directory, for each file in it),
+	/// backends are tried in reverse order of registration,
+	/// so a backend registered later can take over files claimed by an earlier one
+	/// (including the built-in WAD, zip, 7z, PAK, and GRP backends). If a backend
+	/// with the same [name](ArchiveBackend::name) is already registered, it gets replaced.
+	pub fn register_backend<B: ArchiveBackend + 'static>(&mut self, backend: B) {
+		self.backends.retain(|b| b.name() != backend.name());
+		self.backends.push(Arc::new(backend));
+	}
+
+	/// Yields every registered [`ArchiveBackend`] in order of registration,
+	/// starting with the built-in ones.
+	pub fn backends(&self) -> impl Iterator<Item = &dyn ArchiveBackend> {
+		self.backends.iter().map(|b| b.as_ref())
 	}
 
 	#[must_use]
@@ -117,42 +250,72 @@ impl VirtualFs {
 	}
 
 	/// Returns `true` if a file was removed.
+	/// Any mount with this file as its root gets removed too.
 	pub fn remove_file_by_slot(&mut self, slot: FileSlot) -> bool {
-		let ret = self.files.remove(slot).is_some();
-
-		if let Some(p) = self.mounts.iter().position(|mntinfo| mntinfo.root == slot) {
-			self.mounts.remove(p);
-		}
-
+		let ret = self.remove_file(slot);
+		self.forget_removed_mounts();
 		ret
 	}
 
+	/// Any mount with its root at or under this folder gets removed too.
 	pub fn remove_folder_by_slot(&mut self, slot: FolderSlot) {
 		assert_ne!(slot, self.root, "root folder cannot be removed");
 		self.remove_folder_recur(slot);
+		self.forget_removed_mounts();
+	}
+
+	/// Drops every [`MountInfo`] whose root no longer exists.
+	fn forget_removed_mounts(&mut self) {
+		self.mounts.retain(|mntinfo| match mntinfo.root {
+			Slot::File(islot) => self.files.contains_key(islot),
+			Slot::Folder(oslot) => self.folders.contains_key(oslot),
+		});
+	}
+
+	/// Also removes the file from its parent folder.
+	fn remove_file(&mut self, islot: FileSlot) -> bool {
+		let Some(vfile) = self.files.remove(islot) else {
+			return false;
+		};
+
+		self.stamps.remove(&Slot::File(islot));
+		self.forget_content(islot);
 
-		if let Some(p) = self.mounts.iter().position(|mntinfo| mntinfo.root == slot) {
-			self.mounts.remove(p);
+		if let Some(parent) = self.folders.get_mut(vfile.parent) {
+			let did_remove = parent.unlink(Slot::File(islot), vfile.name.as_str());
+			debug_assert!(did_remove);
 		}
+
+		true
 	}
 
+	/// Removes the folder from its parent folder,
+	/// and then removes it and everything under it.
 	fn remove_folder_recur(&mut self, oslot: FolderSlot) {
-		let parent_slot = self.folders[oslot].parent.unwrap();
-		let parent = &mut self.folders[parent_slot];
-		let did_remove = parent.subfolders.remove(&oslot);
+		let vfolder = &self.folders[oslot];
+		let parent_slot = vfolder.parent.unwrap();
+		let name = vfolder.name.clone();
+		let did_remove = self.folders[parent_slot].unlink(Slot::Folder(oslot), name.as_str());
 		debug_assert!(did_remove);
+		self.remove_folder_tree(oslot);
+	}
+
+	fn remove_folder_tree(&mut self, oslot: FolderSlot) {
+		let Some(vfolder) = self.folders.remove(oslot) else {
+			return;
+		};
 
-		let subfolders = std::mem::take(&mut self.folders[oslot].subfolders);
+		self.stamps.remove(&Slot::Folder(oslot));
 
-		for slot in subfolders {
-			self.remove_folder_recur(slot);
-			let removed = self.folders.remove(slot);
-			debug_assert!(removed.is_some());
+		for sfslot in vfolder.subfolders {
+			self.remove_folder_tree(sfslot);
 		}
 
-		for islot in self.folders[oslot].files.iter().copied() {
+		for islot in vfolder.files {
 			let removed = self.files.remove(islot);
 			debug_assert!(removed.is_some());
+			self.stamps.remove(&Slot::File(islot));
+			self.forget_content(islot);
 		}
 	}
 
@@ -174,8 +337,8 @@ impl VirtualFs {
 		for root in to_unmount {
 			match root {
 				Slot::File(islot) => {
-					let removed = self.files.remove(islot);
-					debug_assert!(removed.is_some());
+					let removed = self.remove_file(islot);
+					debug_assert!(removed);
 				}
 				Slot::Folder(oslot) => {
 					self.remove_folder_recur(oslot);
@@ -222,43 +385,14 @@ impl VirtualFs {
 			}));
 		};
 
-		if let Some((sfslot, subfold)) = folder.subfolders.iter().copied().find_map(|s| {
-			let fold = &self.folders[s];
-
-			fold.name
-				.eq_ignore_ascii_case(pcomp.as_str())
-				.then_some((s, fold))
-		}) {
-			return self.lookup_recur(sfslot, subfold, components);
+		match folder.find_child(pcomp.as_str())? {
+			Slot::Folder(sfslot) => self.lookup_recur(sfslot, &self.folders[sfslot], components),
+			Slot::File(islot) => Some(Ref::File(FileRef {
+				vfs: self,
+				slot: islot,
+				vfile: &self.files[islot],
+			})),
 		}
-
-		let option = match folder.files.len() {
-			// TODO: tweak the parallel search threshold to determine an optima.
-			0..=4096 => folder.files.iter().copied().find_map(|slot| {
-				let file = &self.files[slot];
-
-				file.name
-					.eq_ignore_ascii_case(pcomp.as_str())
-					.then_some((slot, file))
-			}),
-			_ => folder.files.par_iter().copied().find_map_any(|slot| {
-				let file = &self.files[slot];
-
-				file.name
-					.eq_ignore_ascii_case(pcomp.as_str())
-					.then_some((slot, file))
-			}),
-		};
-
-		let Some((slot, file)) = option else {
-			return None;
-		};
-
-		Some(Ref::File(FileRef {
-			vfs: self,
-			slot,
-			vfile: file,
-		}))
 	}
 
 	/// Each virtual file backed by a physical file reads its slice into a buffer
@@ -273,7 +407,9 @@ impl VirtualFs {
 			let result = match reader {
 				Reader::File(fh) => Reader::read_from_file(fh, orig_span),
 				Reader::Memory(_) => return None,
-				Reader::_Super(_) => unimplemented!(),
+				Reader::Stream(src) => Reader::read_from_file(src, orig_span),
+				Reader::Custom(reader) => reader.read(orig_span),
+				Reader::Super(layer) => layer.read(orig_span),
 			};
 
 			result
@@ -317,6 +453,9 @@ impl VirtualFs {
 	}
 
 	/// Changes the names of all files and folders to be ASCII lowercase.
+	///
+	/// Folders' child indices are already keyed by ASCII lowercase names,
+	/// so they remain valid as-is.
 	pub fn normalize_names(&mut self) {
 		self.files.values_mut().par_bridge().for_each(|vfile| {
 			vfile.name.make_ascii_lowercase();
@@ -377,9 +516,16 @@ impl VirtualFs {
 	}
 
 	pub fn clear(&mut self) {
-		let root = self.folders.remove(self.root).unwrap();
+		let mut root = self.folders.remove(self.root).unwrap();
+		root.files.clear();
+		root.subfolders.clear();
+		root.index.clear();
 		self.folders.clear();
 		self.files.clear();
+		self.mounts.clear();
+		self.stamps.clear();
+		self.cache.clear();
+		self.hashes.lock().clear();
 		self.root = self.folders.insert(root);
 	}
 }
@@ -393,6 +539,7 @@ impl Default for VirtualFs {
 			parent: None,
 			files: indexmap::indexset![],
 			subfolders: indexmap::indexset![],
+			index: HashMap::default(),
 			kind: FolderKind::Root,
 		});
 
@@ -401,6 +548,17 @@ impl Default for VirtualFs {
 			mounts: vec![],
 			files: HopSlotMap::default(),
 			folders,
+			backends: vec![
+				Arc::new(mount::WadBackend),
+				Arc::new(mount::ZipBackend),
+				Arc::new(mount::SevenZipBackend),
+				Arc::new(mount::PakBackend),
+				Arc::new(mount::GrpBackend),
+			],
+			overlay: None,
+			stamps: HashMap::default(),
+			cache: ContentCache::default(),
+			hashes: Mutex::default(),
 		}
 	}
 }
@@ -412,6 +570,52 @@ pub struct MountInfo {
 	pub mount_point: VPathBuf,
 	pub root: Slot,
 	pub format: MountFormat,
+	/// Everything noteworthy which happened while mounting, in the order it happened.
+	pub warnings: Vec<MountWarning>,
+}
+
+/// Something which did not stop a mount from succeeding, but which may explain
+/// why the tree is not as expected. See [`MountInfo::warnings`].
+///
+/// Each `path` is that of an entry within an archive, or of a real file or directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MountWarning {
+	/// Components of the path which can not be represented in the VFS
+	/// (`.` and `..`) were left out of it.
+	ComponentsDropped { path: String },
+	/// More than one child of the folder at `folder` has a name ASCII
+	/// case-insensitively equal to `name`, so lookups only find one of them
+	/// (a folder, if there is one). Never reported for WADs, wherein repeated
+	/// lump names are expected.
+	DuplicateName { folder: VPathBuf, name: String },
+	/// The name was not valid UTF-8, so every invalid sequence in it was replaced
+	/// with U+FFFD. Lookups must use the replaced name.
+	LossyName { path: String },
+	/// The entry was left out of the tree, or added as a plain file rather
+	/// than mounted as an archive, for the given reason.
+	Skipped { path: String, reason: String },
+	/// The entry is in the tree, but reading it will fail with
+	/// [`Error::UnsupportedCompression`] holding `method`.
+	UnsupportedCompression { path: String, method: u16 },
+}
+
+impl std::fmt::Display for MountWarning {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::ComponentsDropped { path } => {
+				write!(f, "`.` or `..` left out of path: {path}")
+			}
+			Self::DuplicateName { folder, name } => {
+				write!(f, "folder `{folder}` has more than one child named: {name}")
+			}
+			Self::LossyName { path } => write!(f, "name is not valid UTF-8: {path}"),
+			Self::Skipped { path, reason } => write!(f, "skipped `{path}`: {reason}"),
+			Self::UnsupportedCompression { path, method } => {
+				write!(f, "unsupported compression method {method} used by: {path}")
+			}
+		}
+	}
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -420,6 +624,13 @@ pub enum MountFormat {
 	Directory,
 	Wad,
 	Zip,
+	SevenZip,
+	/// Quake's archive format.
+	Pak,
+	/// The Build engine's archive format (e.g. `DUKE3D.GRP`).
+	Grp,
+	/// Mounted by a registered [`ArchiveBackend`] with the given name.
+	Other(&'static str),
 }
 
 /// Short for "virtual file".
@@ -466,6 +677,9 @@ pub struct VFolder {
 	pub(crate) parent: Option<FolderSlot>,
 	pub(crate) files: IndexSet<FileSlot>,
 	pub(crate) subfolders: IndexSet<FolderSlot>,
+	/// Keys are the ASCII lowercase names of every file and subfolder.
+	/// Children sharing a name are kept in the order they were added.
+	pub(crate) index: HashMap<SmallString, Vec<Slot>>,
 	pub(crate) kind: FolderKind,
 }
 
@@ -476,6 +690,19 @@ pub enum FolderKind {
 	Wad,
 	Zip,
 	ZipDir,
+	SevenZip,
+	SevenZipDir,
+	Pak,
+	PakDir,
+	Grp,
+	/// Created by [`VirtualFs::write`] or [`VirtualFs::create_folder`].
+	Overlay,
+	/// The root of an archive mounted by a registered [`ArchiveBackend`]
+	/// with the given name.
+	Other(&'static str),
+	/// A folder within an archive mounted by a registered [`ArchiveBackend`]
+	/// with the given name.
+	OtherDir(&'static str),
 }
 
 impl VFolder {
@@ -512,6 +739,110 @@ impl VFolder {
 	pub fn child_count(&self) -> usize {
 		self.files.len() + self.subfolders.len()
 	}
+
+	/// Finds the child with a name ASCII case-insensitively equal to `name`
+	/// in `O(1)` time. Subfolders take priority over files; beyond that,
+	/// the child added earliest wins.
+	#[must_use]
+	pub(crate) fn find_child(&self, name: &str) -> Option<Slot> {
+		let slots = self.children_named(name);
+
+		slots
+			.iter()
+			.find(|slot| matches!(slot, Slot::Folder(_)))
+			.or_else(|| slots.first())
+			.copied()
+	}
+
+	/// Every child with a name ASCII case-insensitively equal to `name`,
+	/// in the order they were added.
+	#[must_use]
+	pub(crate) fn children_named(&self, name: &str) -> &[Slot] {
+		self.index
+			.get(fold_name(name).as_str())
+			.map_or(&[], |slots| slots.as_slice())
+	}
+
+	pub(crate) fn link_file(&mut self, islot: FileSlot, name: &str) {
+		if self.files.insert(islot) {
+			self.index
+				.entry(fold_name(name))
+				.or_default()
+				.push(islot.into());
+		}
+	}
+
+	pub(crate) fn link_subfolder(&mut self, sfslot: FolderSlot, name: &str) {
+		if self.subfolders.insert(sfslot) {
+			self.index
+				.entry(fold_name(name))
+				.or_default()
+				.push(sfslot.into());
+		}
+	}
+
+	/// Moves child `new` to directly before child `old`, both in iteration order
+	/// and among children with the same name, if they are the same kind of child.
+	/// `name` must be the name of `new`.
+	pub(crate) fn move_before(&mut self, new: Slot, old: Slot, name: &str) {
+		match (new, old) {
+			(Slot::File(new), Slot::File(old)) => set_move_before(&mut self.files, &new, &old),
+			(Slot::Folder(new), Slot::Folder(old)) => {
+				set_move_before(&mut self.subfolders, &new, &old)
+			}
+			_ => {}
+		}
+
+		let Some(slots) = self.index.get_mut(fold_name(name).as_str()) else {
+			return;
+		};
+
+		let from = slots.iter().position(|s| *s == new);
+		let to = slots.iter().position(|s| *s == old);
+
+		if let (Some(from), Some(to)) = (from, to) {
+			let slot = slots.remove(from);
+			slots.insert(if from < to { to - 1 } else { to }, slot);
+		}
+	}
+
+	/// Returns `true` if `slot` was a child of this folder.
+	/// `name` must be the name of the child.
+	pub(crate) fn unlink(&mut self, slot: Slot, name: &str) -> bool {
+		let removed = match slot {
+			Slot::File(islot) => self.files.shift_remove(&islot),
+			Slot::Folder(oslot) => self.subfolders.shift_remove(&oslot),
+		};
+
+		if !removed {
+			return false;
+		}
+
+		let key = fold_name(name);
+
+		if let Some(slots) = self.index.get_mut(key.as_str()) {
+			slots.retain(|s| *s != slot);
+
+			if slots.is_empty() {
+				self.index.remove(key.as_str());
+			}
+		}
+
+		true
+	}
+}
+
+fn set_move_before<T: std::hash::Hash + Eq>(set: &mut IndexSet<T>, new: &T, old: &T) {
+	if let (Some(from), Some(to)) = (set.get_index_of(new), set.get_index_of(old)) {
+		set.move_index(from, if from < to { to - 1 } else { to });
+	}
+}
+
+#[must_use]
+fn fold_name(name: &str) -> SmallString {
+	let mut ret = SmallString::from(name);
+	ret.make_ascii_lowercase();
+	ret
 }
 
 new_key_type! {
@@ -573,6 +904,8 @@ impl From<FolderSlot> for Slot {
 
 #[derive(Debug)]
 pub enum Error {
+	/// Raised by an [`ArchiveBackend`] or [`ArchiveReader`] from outside this crate.
+	Archive(Box<dyn std::error::Error + Send + Sync>),
 	Canonicalize(std::io::Error),
 	Decompress(std::io::Error),
 	DirRead(std::io::Error),
@@ -580,16 +913,37 @@ pub enum Error {
 	FileHandleClone(std::io::Error),
 	FileOpen(std::io::Error),
 	FileRead(std::io::Error),
+	Glob(globset::Error),
+	/// A Build engine GRP archive is malformed in the described way.
+	Grp(&'static str),
 	Metadata(std::io::Error),
 	MountPointDuplicate,
 	MountPointEmpty,
 	MountPointInvalidChars,
 	MountSymlink,
+	/// Attempted to change the VFS without an overlay mounted.
+	NoOverlay,
 	NotFound,
+	/// A write, folder creation, or deletion was given a path which
+	/// is empty, contains `.` or `..`, has a component starting with `.wh.`,
+	/// conflicts with an existing entry, or is the root of a mount.
+	OverlayPath,
+	OverlayWrite(std::io::Error),
+	/// A Quake PAK archive is malformed in the described way.
+	Pak(&'static str),
 	Seek(std::io::Error),
+	SevenZip(sevenz_rust::Error),
+	/// An archive entry is compressed using a method which can not be decoded.
+	/// Holds the method number used by the archive format.
+	UnsupportedCompression(u16),
 	Utf8(FromUtf8Error),
 	VFolderRead,
 	Wad(wadload::Error),
+	#[cfg(feature = "watch")]
+	Watch(notify::Error),
+	/// Only directory and single-file mounts can be watched.
+	#[cfg(feature = "watch")]
+	WatchFormat(MountFormat),
 	Zip(ZipReadError),
 }
 
@@ -598,6 +952,7 @@ impl std::error::Error for Error {}
 impl std::fmt::Display for Error {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		match self {
+			Self::Archive(err) => write!(f, "archive read error: {err}"),
 			Self::Canonicalize(err) => write!(f, "failed to canonicalize a mount path: {err}"),
 			Self::Decompress(err) => write!(f, "failed to decompress an archive entry: {err}"),
 			Self::DirRead(err) => write!(
@@ -610,18 +965,35 @@ impl std::fmt::Display for Error {
 			}
 			Self::FileOpen(err) => write!(f, "failed to open a physical file handle: {err}"),
 			Self::FileRead(err) => write!(f, "failed to read a physical file: {err}"),
+			Self::Glob(err) => write!(f, "invalid glob pattern: {err}"),
+			Self::Grp(reason) => write!(f, "GRP archive read error: {reason}"),
 			Self::Metadata(err) => write!(f, "failed to retrieve physical file metadata: {err}"),
 			Self::MountPointDuplicate => {
 				write!(f, "attempt a mount using an already-present mount point")
 			}
 			Self::MountPointEmpty => write!(f, "given mount point is empty"),
 			Self::MountPointInvalidChars => write!(f, "given mount point has invalid characters"),
+			Self::NoOverlay => write!(f, "attempted to change a VFS with no writable overlay"),
 			Self::NotFound => write!(f, "no entry found by the given path"),
+			Self::OverlayPath => write!(f, "given path can not be written to the overlay"),
+			Self::OverlayWrite(err) => write!(f, "failed to write to the overlay directory: {err}"),
+			Self::Pak(reason) => write!(f, "PAK archive read error: {reason}"),
 			Self::Seek(err) => write!(f, "failed to seek a physical file handle: {err}"),
 			Self::MountSymlink => write!(f, "attempted to mount a symbolic link"),
+			Self::SevenZip(err) => write!(f, "7z archive read error: {err}"),
+			Self::UnsupportedCompression(method) => {
+				write!(
+					f,
+					"archive entry uses unsupported compression method: {method}"
+				)
+			}
 			Self::Utf8(err) => write!(f, "failed to read UTF-8 text from a virtual file: {err}"),
 			Self::VFolderRead => write!(f, "attempted to read byte content of a virtual folder"),
 			Self::Wad(err) => write!(f, "WAD read error: {err}"),
+			#[cfg(feature = "watch")]
+			Self::Watch(err) => write!(f, "file watcher error: {err}"),
+			#[cfg(feature = "watch")]
+			Self::WatchFormat(format) => write!(f, "mounts of format {format:?} can not be watched"),
 			Self::Zip(err) => write!(f, "zip archive read error: {err}"),
 		}
 	}
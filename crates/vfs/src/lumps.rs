@@ -0,0 +1,247 @@
+//! [`LumpIndex`] and its related symbols.
+
+use std::collections::HashMap;
+
+use util::Id8;
+use wadload::Namespace;
+
+use crate::{FileSlot, FolderKind, FolderSlot, Slot, VirtualFs};
+
+/// Resolves 8-character lump names across every mount the way a source port
+/// does, where the last-loaded lump with a given name takes precedence.
+///
+/// Built by [`VirtualFs::lump_index`]. This is a snapshot; mounting or
+/// unmounting anything afterwards requires building a new index.
+///
+/// Lumps are collected from:
+/// - single-file mounts, which are [`Namespace::Global`];
+/// - WADs, with namespaces determined by their marker lumps
+///   (see [`wadload::classify`]);
+/// - directories and non-WAD archives (e.g. PK3s), where files at the root are
+///   [`Namespace::Global`], files anywhere under a namespace directory like
+///   `sprites/` or `flats/` belong to that namespace, and files in any other
+///   directory can only be reached by path. The contents of each matching
+///   `filter/<game>/` directory are treated as though they were at the root,
+///   and WADs at the root are loaded after everything else in the archive.
+///
+/// Files in directories and archives are named per [`crate::VPath::lump_name`].
+/// All names are compared ASCII case-insensitively.
+#[derive(Debug, Default, Clone)]
+pub struct LumpIndex {
+	chains: HashMap<(Namespace, Id8), Vec<FileSlot>>,
+}
+
+/// All lumps in a [`LumpIndex`] with one namespace and name.
+#[derive(Debug, Clone, Copy)]
+pub struct LumpMatch<'i> {
+	/// The last-loaded lump, which is the one a source port would use.
+	pub winner: FileSlot,
+	/// Every lump with this namespace and name, in load order.
+	/// The last element is always [`Self::winner`].
+	pub chain: &'i [FileSlot],
+}
+
+impl LumpMatch<'_> {
+	/// Every lump overridden by [`Self::winner`], in load order.
+	#[must_use]
+	pub fn overridden(&self) -> &[FileSlot] {
+		&self.chain[..(self.chain.len() - 1)]
+	}
+}
+
+impl LumpIndex {
+	#[must_use]
+	pub fn get(&self, namespace: Namespace, name: &str) -> Option<LumpMatch<'_>> {
+		let chain = self.chains.get(&(namespace, lump_name(name)?))?;
+
+		chain.last().map(|winner| LumpMatch {
+			winner: *winner,
+			chain: chain.as_slice(),
+		})
+	}
+
+	/// How many distinct namespace/name pairs are indexed.
+	#[must_use]
+	pub fn len(&self) -> usize {
+		self.chains.len()
+	}
+
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.chains.is_empty()
+	}
+
+	/// Yields every namespace/name pair (in no particular order) alongside its lumps.
+	pub fn iter(&self) -> impl Iterator<Item = (Namespace, &str, LumpMatch<'_>)> {
+		self.chains.iter().filter_map(|((ns, name), chain)| {
+			chain.last().map(|winner| {
+				let lmatch = LumpMatch {
+					winner: *winner,
+					chain: chain.as_slice(),
+				};
+
+				(*ns, name.as_str(), lmatch)
+			})
+		})
+	}
+
+	fn push(&mut self, namespace: Namespace, name: Option<Id8>, slot: FileSlot) {
+		if let Some(name) = name.filter(|n| !n.is_empty()) {
+			self.chains.entry((namespace, name)).or_default().push(slot);
+		}
+	}
+
+	fn index_wad(&mut self, vfs: &VirtualFs, oslot: FolderSlot) {
+		let folder = &vfs.folders[oslot];
+		let names = folder
+			.files
+			.iter()
+			.map(|islot| vfs.files[*islot].name.as_str());
+		let classified = wadload::classify(names);
+
+		for (i, islot) in folder.files.iter().copied().enumerate() {
+			let name = vfs.files[islot].name.as_str();
+
+			if is_marker(name) && classified.namespaces[i] != Namespace::Global {
+				continue;
+			}
+
+			self.push(classified.namespaces[i], lump_name(name), islot);
+		}
+	}
+
+	/// `oslot` is a directory or non-WAD archive.
+	fn index_archive(&mut self, vfs: &VirtualFs, oslot: FolderSlot, filters: &[&str]) {
+		let mut wads = vec![];
+		self.index_archive_dir(vfs, oslot, None, &mut wads);
+
+		let filter_dir = vfs.folders[oslot]
+			.subfolders
+			.iter()
+			.copied()
+			.find(|sfslot| vfs.folders[*sfslot].name.eq_ignore_ascii_case("filter"));
+
+		if let Some(filter_dir) = filter_dir {
+			for filter in filters {
+				let sub = vfs.folders[filter_dir]
+					.subfolders
+					.iter()
+					.copied()
+					.find(|sfslot| vfs.folders[*sfslot].name.eq_ignore_ascii_case(filter));
+
+				if let Some(sub) = sub {
+					self.index_archive_dir(vfs, sub, None, &mut wads);
+				}
+			}
+		}
+
+		for wad in wads {
+			self.index_wad(vfs, wad);
+		}
+	}
+
+	/// `namespace` is `None` if `oslot` is the root of an archive or filter.
+	fn index_archive_dir(
+		&mut self,
+		vfs: &VirtualFs,
+		oslot: FolderSlot,
+		namespace: Option<Namespace>,
+		wads: &mut Vec<FolderSlot>,
+	) {
+		let folder = &vfs.folders[oslot];
+
+		for islot in folder.files.iter().copied() {
+			let name = vfs.files[islot].name().lump_name();
+			self.push(namespace.unwrap_or(Namespace::Global), name, islot);
+		}
+
+		for sfslot in folder.subfolders.iter().copied() {
+			let subfolder = &vfs.folders[sfslot];
+
+			match namespace {
+				Some(ns) => {
+					if subfolder.kind != FolderKind::Wad {
+						self.index_archive_dir(vfs, sfslot, Some(ns), wads);
+					}
+				}
+				None => {
+					if subfolder.kind == FolderKind::Wad {
+						wads.push(sfslot);
+					} else if let Some(ns) = dir_namespace(subfolder.name.as_str()) {
+						self.index_archive_dir(vfs, sfslot, Some(ns), wads);
+					}
+				}
+			}
+		}
+	}
+}
+
+impl VirtualFs {
+	/// Builds a [`LumpIndex`] over every mount, in the order they were mounted.
+	///
+	/// `filters` are the names of the `filter/` subdirectories in archives
+	/// whose contents should be loaded (e.g. `["doom", "doom.id", "doom.id.doom2"]`),
+	/// in increasing order of precedence.
+	#[must_use]
+	pub fn lump_index(&self, filters: &[&str]) -> LumpIndex {
+		let mut ret = LumpIndex::default();
+
+		for mntinfo in &self.mounts {
+			match mntinfo.root {
+				Slot::File(islot) => {
+					let name = self.files[islot].name().lump_name();
+					ret.push(Namespace::Global, name, islot);
+				}
+				Slot::Folder(oslot) => {
+					if self.folders[oslot].kind == FolderKind::Wad {
+						ret.index_wad(self, oslot);
+					} else {
+						ret.index_archive(self, oslot, filters);
+					}
+				}
+			}
+		}
+
+		ret
+	}
+}
+
+/// Returns `None` if `name` is empty.
+#[must_use]
+fn lump_name(name: &str) -> Option<Id8> {
+	if name.is_empty() {
+		return None;
+	}
+
+	let mut ret = Id8::new();
+
+	for c in name.chars().take(8) {
+		ret.push(c.to_ascii_uppercase());
+	}
+
+	Some(ret)
+}
+
+#[must_use]
+fn is_marker(name: &str) -> bool {
+	let upper = name.to_ascii_uppercase();
+	upper.ends_with("_START") || upper.ends_with("_END")
+}
+
+#[must_use]
+fn dir_namespace(name: &str) -> Option<Namespace> {
+	let ns = match name.to_ascii_lowercase().as_str() {
+		"acs" => Namespace::Acs,
+		"colormaps" => Namespace::Colormaps,
+		"flats" => Namespace::Flats,
+		"hires" => Namespace::HiRes,
+		"patches" => Namespace::Patches,
+		"sprites" => Namespace::Sprites,
+		"textures" => Namespace::Textures,
+		"voices" => Namespace::Voices,
+		"voxels" => Namespace::Voxels,
+		_ => return None,
+	};
+
+	Some(ns)
+}
@@ -0,0 +1,446 @@
+//! [`Manifest`] and its related symbols. Requires the `serde` feature.
+
+use std::{
+	collections::HashMap,
+	ops::Range,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use util::SmallString;
+
+use crate::{
+	detail::{Compression, Reader, ReaderLayer},
+	watch::Stamp,
+	Error, FolderKind, FolderSlot, MountFormat, MountInfo, MountWarning, Slot, VFile, VFolder,
+	VPath, VPathBuf, VirtualFs,
+};
+
+/// A record of every mount in a [`VirtualFs`], which can be serialized and later
+/// passed to [`VirtualFs::restore`] to mount everything again, skipping the
+/// parsing of any archive that has not changed in the meantime.
+///
+/// Created by [`VirtualFs::manifest`]. Uncompressed single-file, WAD, zip, PAK,
+/// and GRP mounts have their entire tree recorded, alongside the size and modification
+/// time of their real file. Every other kind of mount (directories, 7z archives,
+/// archives mounted by a registered [`crate::ArchiveBackend`]), and any mount
+/// with files that have been changed by an overlay or by [`VirtualFs::ingest_all`],
+/// only has its real path and mount point recorded, and gets mounted anew.
+/// The overlay is never recorded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+	version: u32,
+	mounts: Vec<MountRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct MountRecord {
+	real_path: PathBuf,
+	mount_point: String,
+	/// `None` if this mount has to be mounted anew.
+	tree: Option<TreeRecord>,
+	warnings: Vec<MountWarning>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TreeRecord {
+	format: FormatRecord,
+	/// Of the mount's real file as of when it was read.
+	stamp: Stamp,
+	/// Index 0 is always the mount's real file.
+	readers: Vec<ReaderRecord>,
+	/// Parents always precede their children.
+	/// Index 0 is the root of the mount, unless it is a single file.
+	folders: Vec<FolderRecord>,
+	files: Vec<FileRecord>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum FormatRecord {
+	Uncompressed,
+	Wad,
+	Zip,
+	Pak,
+	Grp,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum ReaderRecord {
+	Source,
+	/// See [`ReaderLayer`].
+	Layer {
+		parent: usize,
+		span: Range<usize>,
+		compression: Compression,
+	},
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct FolderRecord {
+	name: String,
+	/// `None` if this is the root of the mount.
+	parent: Option<usize>,
+	kind: KindRecord,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum KindRecord {
+	Wad,
+	Zip,
+	ZipDir,
+	Pak,
+	PakDir,
+	Grp,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct FileRecord {
+	name: String,
+	/// `None` if this is a single-file mount.
+	parent: Option<usize>,
+	reader: usize,
+	span: Range<u32>,
+	compression: Compression,
+}
+
+impl Manifest {
+	/// Incremented whenever the layout changes;
+	/// a manifest with any other version has every mount mounted anew.
+	const VERSION: u32 = 1;
+
+	/// How many mounts are recorded.
+	#[must_use]
+	pub fn len(&self) -> usize {
+		self.mounts.len()
+	}
+
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.mounts.is_empty()
+	}
+}
+
+impl VirtualFs {
+	#[must_use]
+	pub fn manifest(&self) -> Manifest {
+		Manifest {
+			version: Manifest::VERSION,
+			mounts: self
+				.mounts
+				.iter()
+				.map(|mntinfo| MountRecord {
+					real_path: mntinfo.real_path.clone(),
+					mount_point: mntinfo.mount_point.as_str().to_owned(),
+					tree: TreeRecord::new(self, mntinfo),
+					warnings: mntinfo.warnings.clone(),
+				})
+				.collect(),
+		}
+	}
+
+	/// Mounts everything recorded in `manifest`, in order; see [`Manifest`].
+	/// Returns how many mounts were restored without being read anew.
+	///
+	/// A recorded tree is only used if its real file's size and modification
+	/// time are unchanged; otherwise the mount is performed as per [`Self::mount`].
+	pub fn restore(&mut self, manifest: &Manifest) -> Result<usize, Error> {
+		let mut restored = 0;
+
+		for record in &manifest.mounts {
+			let mpoint = record.mount_point.trim_start_matches('/');
+
+			let tree = record
+				.tree
+				.as_ref()
+				.filter(|_| manifest.version == Manifest::VERSION)
+				.filter(|tree| tree.is_valid());
+
+			if let Some(tree) = tree {
+				if self.mount_index(VPath::new(mpoint)).is_some() {
+					return Err(Error::MountPointDuplicate);
+				}
+
+				if let Some(mut mntinfo) = tree.restore(self, &record.real_path, mpoint) {
+					mntinfo.warnings.clone_from(&record.warnings);
+					self.mounts.push(mntinfo);
+					restored += 1;
+					continue;
+				}
+			}
+
+			self.mount(&record.real_path, VPath::new(mpoint))?;
+		}
+
+		Ok(restored)
+	}
+}
+
+impl TreeRecord {
+	/// Returns `None` if the mount has to be mounted anew upon restoration.
+	#[must_use]
+	fn new(vfs: &VirtualFs, mntinfo: &MountInfo) -> Option<Self> {
+		// Recording the tree as-is would bake in whatever the overlay deleted.
+		if vfs.overlay_changed(mntinfo.root) {
+			return None;
+		}
+
+		let format = match mntinfo.format {
+			MountFormat::Uncompressed => FormatRecord::Uncompressed,
+			MountFormat::Wad => FormatRecord::Wad,
+			MountFormat::Zip => FormatRecord::Zip,
+			MountFormat::Pak => FormatRecord::Pak,
+			MountFormat::Grp => FormatRecord::Grp,
+			_ => return None,
+		};
+
+		let mut ret = Self {
+			format,
+			stamp: *vfs.stamps.get(&mntinfo.root)?,
+			readers: vec![ReaderRecord::Source],
+			folders: vec![],
+			files: vec![],
+		};
+
+		let mut readers = HashMap::new();
+
+		match mntinfo.root {
+			Slot::File(islot) => {
+				let vfile = &vfs.files[islot];
+
+				if !matches!(*vfile.reader.lock(), Reader::File(_)) {
+					return None;
+				}
+
+				ret.files.push(FileRecord {
+					name: vfile.name.to_string(),
+					parent: None,
+					reader: 0,
+					span: vfile.span.clone(),
+					compression: vfile.compression,
+				});
+			}
+			Slot::Folder(oslot) => {
+				ret.record_folder(vfs, oslot, None, &mut readers)?;
+			}
+		}
+
+		Some(ret)
+	}
+
+	/// `readers` maps the address of each reader to its index in `self.readers`.
+	#[must_use]
+	fn record_folder(
+		&mut self,
+		vfs: &VirtualFs,
+		oslot: FolderSlot,
+		parent: Option<usize>,
+		readers: &mut HashMap<usize, usize>,
+	) -> Option<()> {
+		let folder = &vfs.folders[oslot];
+
+		let kind = match folder.kind {
+			FolderKind::Wad => KindRecord::Wad,
+			FolderKind::Zip => KindRecord::Zip,
+			FolderKind::ZipDir => KindRecord::ZipDir,
+			FolderKind::Pak => KindRecord::Pak,
+			FolderKind::PakDir => KindRecord::PakDir,
+			FolderKind::Grp => KindRecord::Grp,
+			_ => return None,
+		};
+
+		let index = self.folders.len();
+
+		self.folders.push(FolderRecord {
+			name: folder.name.to_string(),
+			parent,
+			kind,
+		});
+
+		for islot in folder.files.iter().copied() {
+			let vfile = &vfs.files[islot];
+			let reader = self.record_reader(&vfile.reader, readers)?;
+
+			self.files.push(FileRecord {
+				name: vfile.name.to_string(),
+				parent: Some(index),
+				reader,
+				span: vfile.span.clone(),
+				compression: vfile.compression,
+			});
+		}
+
+		for sfslot in folder.subfolders.iter().copied() {
+			self.record_folder(vfs, sfslot, Some(index), readers)?;
+		}
+
+		Some(())
+	}
+
+	/// The mount's real file is the only stream reader in an archive mount.
+	#[must_use]
+	fn record_reader(
+		&mut self,
+		reader: &Arc<Mutex<Reader>>,
+		readers: &mut HashMap<usize, usize>,
+	) -> Option<usize> {
+		let addr = Arc::as_ptr(reader) as usize;
+
+		if let Some(index) = readers.get(&addr) {
+			return Some(*index);
+		}
+
+		let record = match &*reader.lock() {
+			Reader::Stream(_) => {
+				if readers.values().any(|i| *i == 0) {
+					return None;
+				}
+
+				readers.insert(addr, 0);
+				return Some(0);
+			}
+			Reader::Super(layer) => ReaderRecord::Layer {
+				parent: self.record_reader(&layer.parent, readers)?,
+				span: layer.span.clone(),
+				compression: layer.compression,
+			},
+			Reader::File(_) | Reader::Memory(_) | Reader::Custom(_) => return None,
+		};
+
+		let index = self.readers.len();
+		self.readers.push(record);
+		readers.insert(addr, index);
+		Some(index)
+	}
+
+	/// Checks that every index refers to something which precedes it,
+	/// so that a corrupt manifest can not cause a panic.
+	#[must_use]
+	fn is_valid(&self) -> bool {
+		let readers = self
+			.readers
+			.iter()
+			.enumerate()
+			.all(|(i, reader)| match reader {
+				ReaderRecord::Source => i == 0,
+				ReaderRecord::Layer { parent, .. } => *parent < i,
+			});
+
+		let folders = self
+			.folders
+			.iter()
+			.enumerate()
+			.all(|(i, folder)| folder.parent.map_or(i == 0, |p| p < i));
+
+		let files = self.files.iter().all(|file| {
+			file.reader < self.readers.len()
+				&& match file.parent {
+					Some(p) => p < self.folders.len(),
+					None => self.format == FormatRecord::Uncompressed && self.files.len() == 1,
+				}
+		});
+
+		let root = match self.format {
+			FormatRecord::Uncompressed => self.folders.is_empty() && self.files.len() == 1,
+			_ => !self.folders.is_empty(),
+		};
+
+		readers && folders && files && root
+	}
+
+	/// Returns `None` without changing `vfs` if the real file
+	/// has changed since this was recorded, or can not be opened.
+	#[must_use]
+	fn restore(&self, vfs: &mut VirtualFs, real_path: &Path, mpoint: &str) -> Option<MountInfo> {
+		let fh = std::fs::File::open(real_path).ok()?;
+
+		if Stamp::of(&fh.metadata().ok()?) != self.stamp {
+			return None;
+		}
+
+		let mut source = Some(match self.format {
+			FormatRecord::Uncompressed => Reader::File(fh),
+			_ => Reader::Stream(Box::new(fh)),
+		});
+
+		let mut readers: Vec<Arc<Mutex<Reader>>> = vec![];
+
+		for record in &self.readers {
+			let reader = match record {
+				ReaderRecord::Source => source.take()?,
+				ReaderRecord::Layer {
+					parent,
+					span,
+					compression,
+				} => Reader::Super(ReaderLayer::new(
+					readers[*parent].clone(),
+					span.clone(),
+					*compression,
+				)),
+			};
+
+			readers.push(Arc::new(Mutex::new(reader)));
+		}
+
+		let mut folders = vec![];
+
+		for record in &self.folders {
+			let parent = record.parent.map_or(vfs.root, |p| folders[p]);
+
+			let oslot = vfs.folders.insert(VFolder {
+				name: SmallString::from(record.name.as_str()),
+				parent: Some(parent),
+				files: indexmap::indexset![],
+				subfolders: indexmap::indexset![],
+				index: HashMap::default(),
+				kind: match record.kind {
+					KindRecord::Wad => FolderKind::Wad,
+					KindRecord::Zip => FolderKind::Zip,
+					KindRecord::ZipDir => FolderKind::ZipDir,
+					KindRecord::Pak => FolderKind::Pak,
+					KindRecord::PakDir => FolderKind::PakDir,
+					KindRecord::Grp => FolderKind::Grp,
+				},
+			});
+
+			vfs.folders[parent].link_subfolder(oslot, &record.name);
+			folders.push(oslot);
+		}
+
+		let mut files = vec![];
+
+		for record in &self.files {
+			let parent = record.parent.map_or(vfs.root, |p| folders[p]);
+
+			let islot = vfs.files.insert(VFile {
+				name: SmallString::from(record.name.as_str()),
+				parent,
+				reader: readers[record.reader].clone(),
+				span: record.span.clone(),
+				compression: record.compression,
+			});
+
+			vfs.folders[parent].link_file(islot, &record.name);
+			files.push(islot);
+		}
+
+		let (root, format) = match self.format {
+			FormatRecord::Uncompressed => (Slot::File(files[0]), MountFormat::Uncompressed),
+			FormatRecord::Wad => (Slot::Folder(folders[0]), MountFormat::Wad),
+			FormatRecord::Zip => (Slot::Folder(folders[0]), MountFormat::Zip),
+			FormatRecord::Pak => (Slot::Folder(folders[0]), MountFormat::Pak),
+			FormatRecord::Grp => (Slot::Folder(folders[0]), MountFormat::Grp),
+		};
+
+		vfs.stamps.insert(root, self.stamp);
+
+		Some(MountInfo {
+			real_path: real_path.to_path_buf(),
+			mount_point: VPathBuf::new(format!("/{mpoint}")),
+			root,
+			format,
+			warnings: vec![],
+		})
+	}
+}
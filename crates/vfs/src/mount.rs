@@ -2,8 +2,10 @@
 
 use std::{
 	borrow::Cow,
+	collections::HashMap,
 	fs::File,
-	io::{Cursor, Read, Seek, SeekFrom},
+	io::{Read, Seek, SeekFrom},
+	ops::Range,
 	path::Path,
 	sync::Arc,
 };
@@ -12,193 +14,335 @@ use parking_lot::Mutex;
 use util::SmallString;
 use zip_structs::{zip_central_directory::ZipCDEntry, zip_eocd::ZipEOCD};
 
-use crate::FolderKind;
-
-use super::{
-	detail, Compression, Error, FolderSlot, MountFormat, MountInfo, Reader, Slot, VFile, VFolder,
-	VPath, VPathBuf, VirtualFs,
+use crate::{
+	detail::ReaderLayer, watch::Stamp, ArchiveBackend, ArchiveReader, Compression, Error,
+	FileStream, FolderKind, FolderSlot, MountContext, MountFormat, MountInfo, MountWarning, Reader,
+	SharedReader, Slot, Source, VFile, VFolder, VPath, VPathBuf, VirtualFs, MAGIC_LEN,
 };
 
-pub(super) fn mount(vfs: &mut VirtualFs, real: &Path, mpoint: &str) -> Result<MountInfo, Error> {
-	if real.is_dir() {
-		let oslot = mount_dir(vfs, real, vfs.root)?;
-
-		return Ok(MountInfo {
-			real_path: real.to_path_buf(),
-			mount_point: VPathBuf::new(format!("/{mpoint}")),
-			root: Slot::Folder(oslot),
-			format: MountFormat::Directory,
-		});
-	}
+/// `replacing` is the root of the mount being [remounted](VirtualFs::remount), if any,
+/// which is about to be removed and so does not count as sharing the new root's name.
+pub(super) fn mount(
+	vfs: &mut VirtualFs,
+	real: &Path,
+	mpoint: &str,
+	replacing: Option<Slot>,
+) -> Result<MountInfo, Error> {
+	let mut warnings = vec![];
+	let (root, format) = mount_real(vfs, real, mpoint, &mut warnings)?;
+	warn_duplicates(vfs, root, replacing, &mut warnings);
 
-	let mut fh = std::fs::File::open(real).map_err(Error::FileOpen)?;
+	Ok(MountInfo {
+		real_path: real.to_path_buf(),
+		mount_point: VPathBuf::new(format!("/{mpoint}")),
+		root,
+		format,
+		warnings,
+	})
+}
 
-	if let Ok(w_reader) = wadload::DirReader::new(&mut fh) {
-		let oslot = mount_wad_file(vfs, mpoint, vfs.root, w_reader)?;
+fn mount_real(
+	vfs: &mut VirtualFs,
+	real: &Path,
+	mpoint: &str,
+	warnings: &mut Vec<MountWarning>,
+) -> Result<(Slot, MountFormat), Error> {
+	if real.is_dir() {
+		// A filesystem root has no name of its own.
+		let name = match real.file_name() {
+			Some(_) => real_name(real, warnings),
+			None => SmallString::from(mpoint),
+		};
 
-		return Ok(MountInfo {
-			real_path: real.to_path_buf(),
-			mount_point: VPathBuf::new(format!("/{mpoint}")),
-			root: Slot::Folder(oslot),
-			format: MountFormat::Wad,
-		});
+		let oslot = mount_dir(vfs, real, &name, vfs.root, warnings)?;
+		return Ok((Slot::Folder(oslot), MountFormat::Directory));
 	}
 
-	fh.seek(SeekFrom::Start(0)).map_err(Error::Seek)?;
+	let mut fh = std::fs::File::open(real).map_err(Error::FileOpen)?;
 	let (magic, len) = magic_and_length(&mut fh)?;
-
-	if util::io::is_zip(&magic) {
-		let oslot = mount_zip_file(vfs, fh, mpoint, vfs.root)?;
-
-		return Ok(MountInfo {
-			real_path: real.to_path_buf(),
-			mount_point: VPathBuf::new(format!("/{mpoint}")),
-			root: Slot::Folder(oslot),
-			format: MountFormat::Zip,
-		});
+	let stamp = Stamp::of(&fh.metadata().map_err(Error::Metadata)?);
+	fh.seek(SeekFrom::Start(0)).map_err(Error::Seek)?;
+	let file_name = real_name(real, warnings);
+	let magic = &magic[..(len.min(MAGIC_LEN as u64) as usize)];
+
+	if let Some(backend) = detect_backend(vfs, magic, &file_name) {
+		let oslot = mount_archive(
+			vfs,
+			backend.as_ref(),
+			Box::new(fh),
+			mpoint,
+			vfs.root,
+			warnings,
+		)?;
+
+		vfs.stamps.insert(Slot::Folder(oslot), stamp);
+		return Ok((Slot::Folder(oslot), backend.format()));
 	}
 
 	let islot = vfs.files.insert(VFile {
-		name: real.file_name().unwrap().to_string_lossy().into(),
+		name: file_name.clone(),
 		parent: vfs.root,
 		reader: Arc::new(Mutex::new(Reader::File(fh))),
 		span: 0..(len as u32),
 		compression: Compression::None,
 	});
 
-	vfs.folders[vfs.root].files.insert(islot);
+	vfs.folders[vfs.root].link_file(islot, &file_name);
+	vfs.stamps.insert(Slot::File(islot), stamp);
+	Ok((Slot::File(islot), MountFormat::Uncompressed))
+}
 
-	Ok(MountInfo {
-		real_path: real.to_path_buf(),
-		mount_point: VPathBuf::new(format!("/{mpoint}")),
-		root: Slot::File(islot),
-		format: MountFormat::Uncompressed,
-	})
+/// Creates a folder named `name` under `parent_slot` and has `backend` fill it.
+/// If `backend` fails, the folder and everything it added get removed.
+fn mount_archive(
+	vfs: &mut VirtualFs,
+	backend: &dyn ArchiveBackend,
+	source: Box<dyn Source>,
+	name: &str,
+	parent_slot: FolderSlot,
+	warnings: &mut Vec<MountWarning>,
+) -> Result<FolderSlot, Error> {
+	let oslot = vfs.folders.insert(VFolder {
+		name: SmallString::from(name),
+		parent: Some(parent_slot),
+		files: indexmap::indexset![],
+		subfolders: indexmap::indexset![],
+		index: HashMap::default(),
+		kind: backend.folder_kind(),
+	});
+
+	vfs.folders[parent_slot].link_subfolder(oslot, name);
+
+	let mut ctx = MountContext {
+		vfs,
+		root: oslot,
+		dir_kind: backend.dir_kind(),
+		warnings: vec![],
+	};
+
+	let result = backend.mount(&mut ctx, source);
+	let mut ctx_warnings = ctx.warnings;
+
+	if let Err(err) = result {
+		vfs.remove_folder_recur(oslot);
+		return Err(err);
+	}
+
+	warnings.append(&mut ctx_warnings);
+	Ok(oslot)
 }
 
-fn mount_dir(
+/// Mounts the real directory at `real` as a folder named `name`.
+/// If this fails, nothing gets added to `parent_slot`.
+pub(super) fn mount_dir(
 	vfs: &mut VirtualFs,
 	real: &Path,
+	name: &str,
 	parent_slot: FolderSlot,
+	warnings: &mut Vec<MountWarning>,
 ) -> Result<FolderSlot, Error> {
 	let d_reader = std::fs::read_dir(real).map_err(Error::DirRead)?;
 
 	let oslot = vfs.folders.insert(VFolder {
-		name: real.file_name().unwrap().to_string_lossy().into(),
+		name: SmallString::from(name),
 		parent: Some(parent_slot),
 		files: indexmap::indexset![],
 		subfolders: indexmap::indexset![],
+		index: HashMap::default(),
 		kind: FolderKind::Directory,
 	});
 
+	if let Err(err) = mount_dir_entries(vfs, d_reader, oslot, warnings) {
+		vfs.remove_folder_tree(oslot);
+		return Err(err);
+	}
+
+	vfs.folders[parent_slot].link_subfolder(oslot, name);
+
+	Ok(oslot)
+}
+
+fn mount_dir_entries(
+	vfs: &mut VirtualFs,
+	d_reader: std::fs::ReadDir,
+	oslot: FolderSlot,
+	warnings: &mut Vec<MountWarning>,
+) -> Result<(), Error> {
 	for result in d_reader {
 		let d_ent = result.map_err(Error::DirRead)?;
 		let path = d_ent.path();
 
 		if path.is_dir() {
-			let _ = mount_dir(vfs, &path, oslot)?;
+			let name = real_name(&path, warnings);
+			let _ = mount_dir(vfs, &path, &name, oslot, warnings)?;
 			continue;
 		}
 
-		let mut fh = std::fs::File::open(&path).map_err(Error::FileOpen)?;
-		let (magic, len) = magic_and_length(&mut fh)?;
-
-		let name = SmallString::from(path.file_name().unwrap().to_string_lossy());
+		if !path.is_file() {
+			warnings.push(MountWarning::Skipped {
+				path: path.to_string_lossy().into_owned(),
+				reason: "not a regular file, or a broken symbolic link".to_owned(),
+			});
 
-		if wad_extension(name.as_str()) && wad_magic(&magic) {
-			let mut bytes = vec![];
-			fh.read_to_end(&mut bytes).map_err(Error::FileRead)?;
-			let _ = mount_wad_blob(vfs, name.as_str(), oslot, bytes)?;
 			continue;
 		}
 
-		let islot = vfs.files.insert(VFile {
-			name,
-			parent: oslot,
-			reader: Arc::new(Mutex::new(Reader::File(fh))),
-			span: 0..(len as u32),
-			compression: Compression::None,
-		});
-
-		vfs.folders[oslot].files.insert(islot);
+		let _ = mount_dir_file(vfs, &path, oslot, warnings)?;
 	}
 
-	vfs.folders[parent_slot].subfolders.insert(oslot);
-
-	Ok(oslot)
+	Ok(())
 }
 
-fn mount_wad_file(
+/// Adds the real file at `real` to `parent_slot`, expanding it into
+/// a folder if any registered [`ArchiveBackend`] recognizes it and can mount it.
+pub(super) fn mount_dir_file(
 	vfs: &mut VirtualFs,
-	mpoint: &str,
+	real: &Path,
 	parent_slot: FolderSlot,
-	w_reader: wadload::DirReader<&mut File>,
-) -> Result<FolderSlot, Error> {
-	let rfh = w_reader
-		.get_ref()
-		.try_clone()
-		.map_err(Error::FileHandleClone)?;
+	warnings: &mut Vec<MountWarning>,
+) -> Result<Slot, Error> {
+	let mut fh = std::fs::File::open(real).map_err(Error::FileOpen)?;
+	let (magic, len) = magic_and_length(&mut fh)?;
+	let stamp = Stamp::of(&fh.metadata().map_err(Error::Metadata)?);
+	let magic = &magic[..(len.min(MAGIC_LEN as u64) as usize)];
+
+	let name = real_name(real, warnings);
+
+	// A magic number match does not guarantee an archive (e.g. a text file
+	// starting with "PACK"), so if the backend can not make sense of the file,
+	// it gets added as-is instead of failing the rest of the directory.
+	if let Some(backend) = detect_backend(vfs, magic, &name) {
+		fh.seek(SeekFrom::Start(0)).map_err(Error::Seek)?;
+
+		match mount_archive(
+			vfs,
+			backend.as_ref(),
+			Box::new(fh),
+			name.as_str(),
+			parent_slot,
+			warnings,
+		) {
+			Ok(oslot) => {
+				vfs.stamps.insert(Slot::Folder(oslot), stamp);
+				return Ok(Slot::Folder(oslot));
+			}
+			Err(err) => {
+				warnings.push(MountWarning::Skipped {
+					path: real.to_string_lossy().into_owned(),
+					reason: format!("not mountable as a {} archive: {err}", backend.name()),
+				});
 
-	let arc = Arc::new(Mutex::new(Reader::File(rfh)));
+				fh = std::fs::File::open(real).map_err(Error::FileOpen)?;
+			}
+		}
+	}
 
-	let oslot = vfs.folders.insert(VFolder {
-		name: SmallString::from(mpoint),
-		parent: Some(parent_slot),
-		files: indexmap::indexset![],
-		subfolders: indexmap::indexset![],
-		kind: FolderKind::Wad,
+	let islot = vfs.files.insert(VFile {
+		name: name.clone(),
+		parent: parent_slot,
+		reader: Arc::new(Mutex::new(Reader::File(fh))),
+		span: 0..(len as u32),
+		compression: Compression::None,
 	});
 
-	let folder = &mut vfs.folders[oslot];
+	vfs.folders[parent_slot].link_file(islot, &name);
+	vfs.stamps.insert(Slot::File(islot), stamp);
+	Ok(Slot::File(islot))
+}
 
-	for result in w_reader {
-		let w_ent = result.map_err(Error::Wad)?;
+/// The built-in [`ArchiveBackend`] for WADs.
+#[derive(Debug)]
+pub(crate) struct WadBackend;
 
-		let islot = vfs.files.insert(VFile {
-			name: SmallString::from(w_ent.name.as_str()),
-			parent: oslot,
-			reader: arc.clone(),
-			span: (w_ent.span.start as u32)..(w_ent.span.end as u32),
-			compression: Compression::None,
-		});
+impl ArchiveBackend for WadBackend {
+	fn name(&self) -> &'static str {
+		"wad"
+	}
 
-		folder.files.insert(islot);
+	fn detect(&self, magic: &[u8], _: &str) -> bool {
+		wad_magic(magic)
 	}
 
-	vfs.folders[parent_slot].subfolders.insert(oslot);
+	fn mount(&self, ctx: &mut MountContext, mut source: Box<dyn Source>) -> Result<(), Error> {
+		let entries = wadload::DirReader::new(&mut source)
+			.map_err(Error::Wad)?
+			.collect::<Result<Vec<_>, _>>()
+			.map_err(Error::Wad)?;
 
-	Ok(oslot)
+		let reader = SharedReader::from_inner(Reader::Stream(source));
+
+		for w_ent in entries {
+			let span = (w_ent.span.start as u32)..(w_ent.span.end as u32);
+			ctx.file(ctx.root, w_ent.name.as_str(), &reader, span);
+		}
+
+		Ok(())
+	}
+
+	fn format(&self) -> MountFormat {
+		MountFormat::Wad
+	}
+
+	fn folder_kind(&self) -> FolderKind {
+		FolderKind::Wad
+	}
+
+	fn dir_kind(&self) -> FolderKind {
+		FolderKind::Wad
+	}
 }
 
-fn mount_wad_blob(
+/// Mounts the WAD in `layer` under `parent_slot`, unless `layer` does not
+/// actually hold a WAD, in which case `None` gets returned. The header and
+/// directory get read through a [`FileStream`], so a compressed WAD only gets
+/// decompressed as far as its directory. Lumps are read through `layer`
+/// on demand; `len` is the size of its decompressed content.
+fn mount_wad_layer(
 	vfs: &mut VirtualFs,
 	mpoint: &str,
 	parent_slot: FolderSlot,
-	bytes: Vec<u8>,
-) -> Result<FolderSlot, Error> {
-	let arc = Arc::new(Mutex::new(Reader::Memory(bytes)));
-	let guard = arc.lock();
+	layer: ReaderLayer,
+	len: usize,
+) -> Result<Option<FolderSlot>, Error> {
+	if len < 4 {
+		return Ok(None);
+	}
 
-	let Reader::Memory(blob) = std::ops::Deref::deref(&guard) else {
-		unreachable!()
-	};
+	let mut stream = FileStream::new(
+		layer.parent.clone(),
+		layer.span.clone(),
+		layer.compression,
+		Some(len as u64),
+	);
+
+	let mut magic = [0; 4];
+	stream.read_exact(&mut magic).map_err(Error::Decompress)?;
+
+	if !wad_magic(&magic) {
+		return Ok(None);
+	}
+
+	stream.rewind().map_err(Error::Seek)?;
 
-	let cursor = Cursor::new(blob.as_slice());
-	let w_reader = wadload::DirReader::new(cursor).map_err(Error::Wad)?;
+	let entries = wadload::DirReader::new(stream)
+		.map_err(Error::Wad)?
+		.collect::<Result<Vec<_>, _>>()
+		.map_err(Error::Wad)?;
+
+	let arc = Arc::new(Mutex::new(Reader::Super(layer)));
 
 	let oslot = vfs.folders.insert(VFolder {
 		name: SmallString::from(mpoint),
 		parent: Some(parent_slot),
 		files: indexmap::indexset![],
 		subfolders: indexmap::indexset![],
+		index: HashMap::default(),
 		kind: FolderKind::Wad,
 	});
 
 	let folder = &mut vfs.folders[oslot];
 
-	for result in w_reader {
-		let w_ent = result.map_err(Error::Wad)?;
-
+	for w_ent in entries {
 		let islot = vfs.files.insert(VFile {
 			name: SmallString::from(w_ent.name.as_str()),
 			parent: oslot,
@@ -207,131 +351,540 @@ fn mount_wad_blob(
 			compression: Compression::None,
 		});
 
-		folder.files.insert(islot);
+		folder.link_file(islot, w_ent.name.as_str());
 	}
 
-	vfs.folders[parent_slot].subfolders.insert(oslot);
+	vfs.folders[parent_slot].link_subfolder(oslot, mpoint);
 
-	Ok(oslot)
+	Ok(Some(oslot))
 }
 
-fn mount_zip_file(
-	vfs: &mut VirtualFs,
-	mut fh: File,
-	mpoint: &str,
-	parent_slot: FolderSlot,
-) -> Result<FolderSlot, Error> {
-	let rfh = fh.try_clone().map_err(Error::FileHandleClone)?;
+/// The built-in [`ArchiveBackend`] for zip archives (e.g. PK3s).
+#[derive(Debug)]
+pub(crate) struct ZipBackend;
 
-	let arc = Arc::new(Mutex::new(Reader::File(rfh)));
+impl ArchiveBackend for ZipBackend {
+	fn name(&self) -> &'static str {
+		"zip"
+	}
 
-	let eocd = ZipEOCD::from_reader(&mut fh).map_err(Error::Zip)?;
-	let entries = ZipCDEntry::all_from_eocd(&mut fh, &eocd).map_err(Error::Zip)?;
+	fn detect(&self, magic: &[u8], _: &str) -> bool {
+		util::io::is_zip(magic)
+	}
 
-	let oslot = vfs.folders.insert(VFolder {
-		name: SmallString::from(mpoint),
-		parent: Some(vfs.root),
-		files: indexmap::indexset![],
-		subfolders: indexmap::indexset![],
-		kind: FolderKind::Zip,
-	});
+	fn mount(&self, ctx: &mut MountContext, mut source: Box<dyn Source>) -> Result<(), Error> {
+		let eocd = ZipEOCD::from_reader(&mut source).map_err(Error::Zip)?;
+		let entries = ZipCDEntry::all_from_eocd(&mut source, &eocd).map_err(Error::Zip)?;
+		let reader = SharedReader::from_inner(Reader::Stream(source));
+
+		for entry in entries {
+			let compression = match entry.compression_method {
+				0 => Compression::None,
+				8 => Compression::Deflate,
+				12 => Compression::Bzip2,
+				14 => Compression::Lzma {
+					unpacked_size: entry.uncompressed_size,
+				},
+				93 => Compression::Zstd,
+				95 => Compression::Xz,
+				other => Compression::Unsupported(other),
+			};
+
+			let epath = String::from_utf8_lossy(&entry.file_name_raw);
+
+			if let Cow::Owned(path) = &epath {
+				ctx.warn(MountWarning::LossyName { path: path.clone() });
+			}
 
-	for entry in entries {
-		let compression = match entry.compression_method {
-			0 => Compression::None,
-			8 => Compression::Deflate,
-			12 => Compression::Bzip2,
-			14 => Compression::Lzma,
-			93 => Compression::Zstd,
-			95 => Compression::Xz,
-			_ => unimplemented!(),
-		};
+			let mut components = ctx.components(&epath);
 
-		let epath = String::from_utf8_lossy(&entry.file_name_raw);
+			if epath.ends_with('/') {
+				let _ = ctx.folder_path(components);
+				continue;
+			}
 
-		let components = epath
-			.split('/')
-			.filter(|pcomp| pcomp.contains(|c| char::is_ascii_alphanumeric(&c)));
+			let Some(name) = components.pop() else {
+				ctx.warn(MountWarning::Skipped {
+					path: epath.into_owned(),
+					reason: "entry has no name".to_owned(),
+				});
 
-		let Some(name) = components.clone().last() else {
-			continue;
-		};
+				continue;
+			};
 
-		let eparent = build_zip_dir_structure(vfs, oslot, components, name);
+			let eparent = ctx.folder_path(components);
 
-		let start = entry.local_header_position
-			+ 4 + 22 + 2 + 2
-			+ (entry.file_name_length as u32)
-			+ (entry.extra_field_length as u32);
+			if let Compression::Unsupported(method) = compression {
+				ctx.warn(MountWarning::UnsupportedCompression {
+					path: epath.to_string(),
+					method,
+				});
+			}
 
-		let span = start..(start + entry.compressed_size);
+			let start = entry.local_header_position
+				+ 4 + 22 + 2 + 2
+				+ (entry.file_name_length as u32)
+				+ (entry.extra_field_length as u32);
 
-		if wad_extension(name) {
-			let mut compressed = vec![0; span.len()];
-			fh.seek(SeekFrom::Start(start as u64))
-				.map_err(Error::Seek)?;
-			fh.read_exact(&mut compressed).map_err(Error::FileRead)?;
-			let bytes = detail::decompress(Cow::Owned(compressed), compression)?;
+			let span = start..(start + entry.compressed_size);
 
-			if wad_magic(&bytes[0..8]) {
-				let s = mount_wad_blob(vfs, name, eparent, bytes.into_owned())?;
-				vfs.folders[eparent].subfolders.insert(s);
-				continue;
+			if wad_extension(name) && !matches!(compression, Compression::Unsupported(_)) {
+				let layer = ReaderLayer::new(
+					reader.0.clone(),
+					span.start as usize..span.end as usize,
+					compression,
+				);
+				let len = entry.uncompressed_size as usize;
+
+				if mount_wad_layer(ctx.vfs, name, eparent, layer, len)?.is_some() {
+					continue;
+				}
 			}
+
+			ctx.file_compressed(eparent, name, &reader, span, compression);
 		}
 
-		let islot = vfs.files.insert(VFile {
-			name: SmallString::from(name),
-			parent: eparent,
-			reader: arc.clone(),
-			span,
-			compression,
+		Ok(())
+	}
+
+	fn format(&self) -> MountFormat {
+		MountFormat::Zip
+	}
+
+	fn folder_kind(&self) -> FolderKind {
+		FolderKind::Zip
+	}
+
+	fn dir_kind(&self) -> FolderKind {
+		FolderKind::ZipDir
+	}
+}
+
+/// The built-in [`ArchiveBackend`] for 7z archives (e.g. PK7s).
+///
+/// Every block (a "folder" in 7z terms) gets its own reader, which decompresses
+/// the entire block upon the first read of any file in it and keeps the result,
+/// since files in a solid block can only be decompressed in order.
+#[derive(Debug)]
+pub(crate) struct SevenZipBackend;
+
+impl ArchiveBackend for SevenZipBackend {
+	fn name(&self) -> &'static str {
+		"7z"
+	}
+
+	fn detect(&self, magic: &[u8], _: &str) -> bool {
+		util::io::is_7z(magic)
+	}
+
+	fn mount(&self, ctx: &mut MountContext, mut source: Box<dyn Source>) -> Result<(), Error> {
+		let len = source.seek(SeekFrom::End(0)).map_err(Error::Seek)?;
+		source.seek(SeekFrom::Start(0)).map_err(Error::Seek)?;
+		let archive = sevenz_rust::Archive::read(&mut source, len, &[]).map_err(Error::SevenZip)?;
+
+		let archive = Arc::new(SevenZipArchive {
+			source: Mutex::new(source),
+			archive,
 		});
 
-		vfs.folders[eparent].files.insert(islot);
+		let blocks = (0..archive.archive.folders.len())
+			.map(|index| {
+				SharedReader::new(SevenZipBlock {
+					archive: archive.clone(),
+					index,
+					unpacked: None,
+				})
+			})
+			.collect::<Vec<_>>();
+
+		let empty = SharedReader::from_inner(Reader::Memory(vec![]));
+		let mut offsets = vec![0_u64; blocks.len()];
+
+		for (i, entry) in archive.archive.files.iter().enumerate() {
+			let mut components = ctx.components(entry.name());
+
+			if entry.is_directory {
+				let _ = ctx.folder_path(components);
+				continue;
+			}
+
+			let Some(name) = components.pop() else {
+				ctx.warn(MountWarning::Skipped {
+					path: entry.name().to_owned(),
+					reason: "entry has no name".to_owned(),
+				});
+
+				continue;
+			};
+
+			let eparent = ctx.folder_path(components);
+
+			match archive.archive.stream_map.file_folder_index[i] {
+				Some(block) if entry.has_stream => {
+					let start = offsets[block];
+					offsets[block] = start.saturating_add(entry.size);
+
+					let (Ok(start), Ok(end)) =
+						(u32::try_from(start), u32::try_from(offsets[block]))
+					else {
+						ctx.warn(MountWarning::Skipped {
+							path: entry.name().to_owned(),
+							reason: "entry ends past 4 GiB into its block".to_owned(),
+						});
+
+						continue;
+					};
+
+					if wad_extension(name) {
+						let layer = ReaderLayer::new(
+							blocks[block].0.clone(),
+							(start as usize)..(end as usize),
+							Compression::None,
+						);
+						let len = (end - start) as usize;
+
+						if mount_wad_layer(ctx.vfs, name, eparent, layer, len)?.is_some() {
+							continue;
+						}
+					}
+
+					ctx.file(eparent, name, &blocks[block], start..end);
+				}
+				_ => {
+					ctx.file(eparent, name, &empty, 0..0);
+				}
+			}
+		}
+
+		Ok(())
 	}
 
-	vfs.folders[parent_slot].subfolders.insert(oslot);
+	fn format(&self) -> MountFormat {
+		MountFormat::SevenZip
+	}
 
-	Ok(oslot)
+	fn folder_kind(&self) -> FolderKind {
+		FolderKind::SevenZip
+	}
+
+	fn dir_kind(&self) -> FolderKind {
+		FolderKind::SevenZipDir
+	}
 }
 
-#[must_use]
-fn build_zip_dir_structure<'s>(
-	vfs: &mut VirtualFs,
-	zip_slot: FolderSlot,
-	components: impl Iterator<Item = &'s str>,
-	entry_name: &str,
-) -> FolderSlot {
-	let mut eparent = zip_slot;
-
-	for comp in components {
-		if std::ptr::eq(comp, entry_name) {
-			return eparent;
+#[derive(Debug)]
+struct SevenZipArchive {
+	source: Mutex<Box<dyn Source>>,
+	archive: sevenz_rust::Archive,
+}
+
+#[derive(Debug)]
+struct SevenZipBlock {
+	archive: Arc<SevenZipArchive>,
+	index: usize,
+	/// The contents of every file in the block, back-to-back.
+	unpacked: Option<Vec<u8>>,
+}
+
+impl ArchiveReader for SevenZipBlock {
+	fn read(&mut self, span: Range<usize>) -> Result<Vec<u8>, Error> {
+		if let Some(unpacked) = &self.unpacked {
+			return block_slice(unpacked, span);
+		}
+
+		let mut unpacked = vec![];
+		let mut source = self.archive.source.lock();
+		let decoder =
+			sevenz_rust::BlockDecoder::new(self.index, &self.archive.archive, &[], &mut *source);
+
+		decoder
+			.for_each_entries(&mut |_, reader| {
+				reader
+					.read_to_end(&mut unpacked)
+					.map_err(sevenz_rust::Error::io)?;
+
+				Ok(true)
+			})
+			.map_err(Error::SevenZip)?;
+
+		let ret = block_slice(&unpacked, span);
+		self.unpacked = Some(unpacked);
+		ret
+	}
+}
+
+/// A corrupt archive can declare entries larger than what its block decompresses to.
+fn block_slice(unpacked: &[u8], span: Range<usize>) -> Result<Vec<u8>, Error> {
+	unpacked
+		.get(span)
+		.map(|bytes| bytes.to_vec())
+		.ok_or(Error::SevenZip(sevenz_rust::Error::other(
+			"block is shorter than the entries in it",
+		)))
+}
+
+/// The built-in [`ArchiveBackend`] for Quake's PAK format.
+///
+/// Entry names are paths of up to 55 bytes, so PAKs can have folders.
+#[derive(Debug)]
+pub(crate) struct PakBackend;
+
+impl ArchiveBackend for PakBackend {
+	fn name(&self) -> &'static str {
+		"pak"
+	}
+
+	fn detect(&self, magic: &[u8], _: &str) -> bool {
+		magic.starts_with(b"PACK")
+	}
+
+	fn mount(&self, ctx: &mut MountContext, mut source: Box<dyn Source>) -> Result<(), Error> {
+		const ENTRY_LEN: usize = 64;
+
+		let len = source.seek(SeekFrom::End(0)).map_err(Error::Seek)?;
+		source.seek(SeekFrom::Start(0)).map_err(Error::Seek)?;
+
+		let mut header = [0; 12];
+		source.read_exact(&mut header).map_err(Error::FileRead)?;
+		let dir_offs = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+		let dir_len = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+
+		if !(dir_len as usize).is_multiple_of(ENTRY_LEN) || (dir_offs as u64 + dir_len as u64) > len
+		{
+			return Err(Error::Pak("directory is out of bounds or truncated"));
+		}
+
+		let mut dir = vec![0; dir_len as usize];
+		source
+			.seek(SeekFrom::Start(dir_offs as u64))
+			.map_err(Error::Seek)?;
+		source.read_exact(&mut dir).map_err(Error::FileRead)?;
+		let reader = SharedReader::from_inner(Reader::Stream(source));
+
+		for entry in dir.chunks_exact(ENTRY_LEN) {
+			let epath = legacy_name(ctx, &entry[..56]);
+			let offs = u32::from_le_bytes([entry[56], entry[57], entry[58], entry[59]]);
+			let size = u32::from_le_bytes([entry[60], entry[61], entry[62], entry[63]]);
+
+			if (offs as u64 + size as u64) > len {
+				return Err(Error::Pak("entry is out of bounds"));
+			}
+
+			let mut components = ctx.components(&epath);
+
+			let Some(name) = components.pop() else {
+				ctx.warn(MountWarning::Skipped {
+					path: epath.clone(),
+					reason: "entry has no name".to_owned(),
+				});
+
+				continue;
+			};
+
+			let eparent = ctx.folder_path(components);
+			ctx.file(eparent, name, &reader, offs..(offs + size));
+		}
+
+		Ok(())
+	}
+
+	fn format(&self) -> MountFormat {
+		MountFormat::Pak
+	}
+
+	fn folder_kind(&self) -> FolderKind {
+		FolderKind::Pak
+	}
+
+	fn dir_kind(&self) -> FolderKind {
+		FolderKind::PakDir
+	}
+}
+
+/// The built-in [`ArchiveBackend`] for the Build engine's GRP format.
+///
+/// GRPs have no folders, and entry names are at most 12 bytes long.
+#[derive(Debug)]
+pub(crate) struct GrpBackend;
+
+impl ArchiveBackend for GrpBackend {
+	fn name(&self) -> &'static str {
+		"grp"
+	}
+
+	fn detect(&self, magic: &[u8], _: &str) -> bool {
+		magic.starts_with(b"KenSilverman")
+	}
+
+	fn mount(&self, ctx: &mut MountContext, mut source: Box<dyn Source>) -> Result<(), Error> {
+		const ENTRY_LEN: u64 = 16;
+
+		let len = source.seek(SeekFrom::End(0)).map_err(Error::Seek)?;
+		source.seek(SeekFrom::Start(0)).map_err(Error::Seek)?;
+
+		let mut header = [0; 16];
+		source.read_exact(&mut header).map_err(Error::FileRead)?;
+		let count = u32::from_le_bytes([header[12], header[13], header[14], header[15]]);
+
+		// Entry content follows the directory, back-to-back.
+		let mut offs = ENTRY_LEN + (count as u64 * ENTRY_LEN);
+
+		if offs > len {
+			return Err(Error::Grp("directory is truncated"));
 		}
 
-		let opt = vfs.folders[eparent]
+		let mut dir = vec![0; (count as u64 * ENTRY_LEN) as usize];
+		source.read_exact(&mut dir).map_err(Error::FileRead)?;
+		let reader = SharedReader::from_inner(Reader::Stream(source));
+
+		for entry in dir.chunks_exact(ENTRY_LEN as usize) {
+			let name = legacy_name(ctx, &entry[..12]);
+			let size = u32::from_le_bytes([entry[12], entry[13], entry[14], entry[15]]);
+			let end = offs + size as u64;
+
+			if end > len {
+				return Err(Error::Grp("entry is out of bounds"));
+			}
+
+			if name.is_empty() {
+				ctx.warn(MountWarning::Skipped {
+					path: format!("(entry at offset {offs})"),
+					reason: "entry has no name".to_owned(),
+				});
+			} else {
+				ctx.file(ctx.root, &name, &reader, (offs as u32)..(end as u32));
+			}
+
+			offs = end;
+		}
+
+		Ok(())
+	}
+
+	fn format(&self) -> MountFormat {
+		MountFormat::Grp
+	}
+
+	fn folder_kind(&self) -> FolderKind {
+		FolderKind::Grp
+	}
+
+	fn dir_kind(&self) -> FolderKind {
+		FolderKind::Grp
+	}
+}
+
+/// Decodes a fixed-length name field, which may or may not be null-terminated.
+#[must_use]
+fn legacy_name(ctx: &mut MountContext, field: &[u8]) -> String {
+	let len = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+	let name = String::from_utf8_lossy(&field[..len]);
+
+	if let Cow::Owned(path) = &name {
+		ctx.warn(MountWarning::LossyName { path: path.clone() });
+	}
+
+	name.trim().to_owned()
+}
+
+/// Backends registered later take priority.
+#[must_use]
+fn detect_backend(
+	vfs: &VirtualFs,
+	magic: &[u8],
+	file_name: &str,
+) -> Option<Arc<dyn ArchiveBackend>> {
+	vfs.backends
+		.iter()
+		.rev()
+		.find(|backend| backend.detect(magic, file_name))
+		.cloned()
+}
+
+/// Whether the real file at `real` would be expanded into a folder
+/// if it were mounted or found in a mounted directory.
+pub(super) fn is_archive(vfs: &VirtualFs, real: &Path) -> Result<bool, Error> {
+	let mut fh = std::fs::File::open(real).map_err(Error::FileOpen)?;
+	let (magic, len) = magic_and_length(&mut fh)?;
+	let magic = &magic[..(len.min(MAGIC_LEN as u64) as usize)];
+	let name = real_name(real, &mut vec![]);
+	Ok(detect_backend(vfs, magic, &name).is_some())
+}
+
+/// The last component of `real`, with a warning if it is not valid UTF-8.
+#[must_use]
+fn real_name(real: &Path, warnings: &mut Vec<MountWarning>) -> SmallString {
+	let name = real.file_name().unwrap_or(real.as_os_str());
+
+	if name.to_str().is_none() {
+		warnings.push(MountWarning::LossyName {
+			path: real.to_string_lossy().into_owned(),
+		});
+	}
+
+	SmallString::from(name.to_string_lossy())
+}
+
+/// Warns about every name shared by more than one child of any non-WAD folder in the
+/// subtree at `slot`, as well as by `slot` itself and any of its siblings other than `ignore`.
+fn warn_duplicates(
+	vfs: &VirtualFs,
+	slot: Slot,
+	ignore: Option<Slot>,
+	warnings: &mut Vec<MountWarning>,
+) {
+	let (parent, name) = match slot {
+		Slot::File(islot) => (vfs.files[islot].parent, &vfs.files[islot].name),
+		Slot::Folder(oslot) => (vfs.folders[oslot].parent.unwrap(), &vfs.folders[oslot].name),
+	};
+
+	let siblings = vfs.folders[parent]
+		.children_named(name)
+		.iter()
+		.filter(|sibling| Some(**sibling) != ignore)
+		.count();
+
+	if siblings > 1 {
+		warnings.push(MountWarning::DuplicateName {
+			folder: vfs.get_folder(parent).unwrap().path(),
+			name: name.to_string(),
+		});
+	}
+
+	if let Slot::Folder(oslot) = slot {
+		warn_duplicates_under(vfs, oslot, warnings);
+	}
+}
+
+fn warn_duplicates_under(vfs: &VirtualFs, oslot: FolderSlot, warnings: &mut Vec<MountWarning>) {
+	let folder = &vfs.folders[oslot];
+
+	if folder.kind != FolderKind::Wad {
+		let files = folder
+			.files
+			.iter()
+			.map(|islot| (Slot::File(*islot), &vfs.files[*islot].name));
+		let subfolders = folder
 			.subfolders
 			.iter()
-			.copied()
-			.find(|sfslot| vfs.folders[*sfslot].name == comp);
-
-		eparent = opt.unwrap_or_else(|| {
-			let s = vfs.folders.insert(VFolder {
-				name: SmallString::from(comp),
-				parent: Some(eparent),
-				files: indexmap::indexset![],
-				subfolders: indexmap::indexset![],
-				kind: FolderKind::ZipDir,
-			});
+			.map(|sfslot| (Slot::Folder(*sfslot), &vfs.folders[*sfslot].name));
 
-			vfs.folders[eparent].subfolders.insert(s);
+		for (slot, name) in files.chain(subfolders) {
+			let slots = folder.children_named(name);
 
-			s
-		});
+			// Only report each name once.
+			if slots.len() > 1 && slots[0] == slot {
+				warnings.push(MountWarning::DuplicateName {
+					folder: vfs.get_folder(oslot).unwrap().path(),
+					name: name.to_string(),
+				});
+			}
+		}
 	}
 
-	zip_slot
+	for sfslot in folder.subfolders.iter().copied() {
+		warn_duplicates_under(vfs, sfslot, warnings);
+	}
 }
 
 #[must_use]
@@ -350,9 +903,20 @@ fn wad_magic(magic: &[u8]) -> bool {
 		)
 }
 
-fn magic_and_length(fh: &mut File) -> Result<([u8; 8], u64), Error> {
-	let mut buf = [0; 8];
-	fh.read_exact(&mut buf).map_err(Error::FileRead)?;
+/// If the file is shorter than [`MAGIC_LEN`], the rest of the returned buffer is zeroed.
+fn magic_and_length(fh: &mut File) -> Result<([u8; MAGIC_LEN], u64), Error> {
+	let mut buf = [0; MAGIC_LEN];
+	let mut filled = 0;
+
+	while filled < buf.len() {
+		match fh.read(&mut buf[filled..]) {
+			Ok(0) => break,
+			Ok(n) => filled += n,
+			Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+			Err(err) => return Err(Error::FileRead(err)),
+		}
+	}
+
 	let r = fh.seek(SeekFrom::End(0)).map_err(Error::Seek)?;
 	Ok((buf, r))
 }
@@ -0,0 +1,640 @@
+//! [`OverlayStore`] and the VFS's write operations.
+
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+
+use indexmap::IndexMap;
+use parking_lot::Mutex;
+use util::SmallString;
+
+use crate::{
+	detail::{Compression, Reader},
+	fold_name, Error, FileSlot, FolderKind, FolderSlot, Slot, VFile, VFolder, VPath, VirtualFs,
+};
+
+/// Where the changes made by [`VirtualFs::write`], [`VirtualFs::create_folder`],
+/// and [`VirtualFs::delete`] are kept. See [`VirtualFs::mount_overlay`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OverlayStore {
+	/// Written content only lives as long as the overlay stays mounted.
+	Memory,
+	/// Written files and created folders are mirrored under this real directory
+	/// at the same relative path as in the VFS (so writing `/pk3/x.txt` creates
+	/// `<dir>/pk3/x.txt`). Each deletion is recorded as an empty "whiteout" file
+	/// named after what was deleted with a `.wh.` prefix (so deleting `/pk3/maps`
+	/// creates `<dir>/pk3/.wh.maps`). A whiteout next to a file or folder of the
+	/// same name means that it replaces whatever it would otherwise shadow.
+	Directory(PathBuf),
+}
+
+/// A mounted [`OverlayStore`] and every change made through it. These are kept
+/// apart from the mounts they apply over, so that they can be applied again
+/// after a [remount](VirtualFs::remount) and undone by [`VirtualFs::unmount_overlay`].
+#[derive(Debug)]
+pub(crate) struct Overlay {
+	store: OverlayStore,
+	/// Keyed by path, with each component folded, in the order they should be applied.
+	/// A change supersedes any earlier change at its path; a deletion also
+	/// supersedes every earlier change under its path.
+	changes: IndexMap<String, Change>,
+}
+
+#[derive(Debug)]
+struct Change {
+	/// As given by the caller, without a leading `/`.
+	path: String,
+	kind: ChangeKind,
+}
+
+#[derive(Debug)]
+enum ChangeKind {
+	/// If `replaces` is set, whatever was at the file's path gets deleted first.
+	File {
+		reader: Arc<Mutex<Reader>>,
+		len: usize,
+		replaces: bool,
+	},
+	/// See [`ChangeKind::File`] for `replaces`.
+	Folder {
+		replaces: bool,
+	},
+	Deleted,
+}
+
+impl VirtualFs {
+	/// Makes the VFS writable. Written files and created folders shadow whatever
+	/// mounted entries were at their paths, and deleted entries get hidden, so every
+	/// [`crate::FileRef`] and [`crate::FolderRef`] sees the merged view. The mounts
+	/// themselves are never changed, and the overlay gets applied again over any
+	/// mount which gets [remounted](Self::remount).
+	///
+	/// If `store` is [`OverlayStore::Directory`], the directory gets created if
+	/// it does not exist, and everything already in it gets applied on top of the
+	/// VFS as though it had just been written; as such, the overlay should be
+	/// mounted after everything it is meant to shadow.
+	///
+	/// Any previously-mounted overlay gets [unmounted](Self::unmount_overlay) first.
+	pub fn mount_overlay(&mut self, store: OverlayStore) -> Result<(), Error> {
+		self.unmount_overlay()?;
+
+		let dir = match &store {
+			OverlayStore::Memory => None,
+			OverlayStore::Directory(dir) => {
+				std::fs::create_dir_all(dir).map_err(Error::OverlayWrite)?;
+				Some(dir.canonicalize().map_err(Error::Canonicalize)?)
+			}
+		};
+
+		self.overlay = Some(Overlay {
+			store: match &dir {
+				Some(dir) => OverlayStore::Directory(dir.clone()),
+				None => OverlayStore::Memory,
+			},
+			changes: IndexMap::new(),
+		});
+
+		if let Some(dir) = dir {
+			self.load_overlay_dir(&dir, &mut vec![])?;
+		}
+
+		Ok(())
+	}
+
+	/// Makes the VFS read-only again, returning the overlay's store if one was
+	/// mounted. Every file and folder the overlay created outside of a mount is
+	/// removed, and every mount it changed gets [remounted](Self::remount) so that
+	/// whatever the overlay shadowed or deleted shows through again. As such,
+	/// slots under those mounts do not remain valid.
+	///
+	/// # Errors
+	/// The first error raised by remounting, in which case the overlay still gets
+	/// unmounted and every other mount it changed still gets remounted.
+	pub fn unmount_overlay(&mut self) -> Result<Option<OverlayStore>, Error> {
+		let Some(overlay) = self.overlay.take() else {
+			return Ok(None);
+		};
+
+		let root = &self.folders[self.root];
+
+		let created = root
+			.files
+			.iter()
+			.map(|islot| Slot::File(*islot))
+			.chain(root.subfolders.iter().map(|sfslot| Slot::Folder(*sfslot)))
+			.filter(|slot| !self.mounts.iter().any(|mntinfo| mntinfo.root == *slot))
+			.collect::<Vec<_>>();
+
+		for slot in created {
+			match slot {
+				Slot::File(islot) => {
+					self.remove_file(islot);
+				}
+				Slot::Folder(oslot) => self.remove_folder_recur(oslot),
+			}
+		}
+
+		let mut changed = vec![];
+
+		for key in overlay.changes.keys() {
+			let first = key.split('/').next().unwrap_or_default();
+
+			for mntinfo in &self.mounts {
+				if fold_name(self.slot_name(mntinfo.root)) == first
+					&& !changed.contains(&mntinfo.mount_point)
+				{
+					changed.push(mntinfo.mount_point.clone());
+				}
+			}
+		}
+
+		let mut result = Ok(Some(overlay.store));
+
+		for mpoint in changed {
+			if let Err(err) = self.remount(&mpoint) {
+				if result.is_ok() {
+					result = Err(err);
+				}
+			}
+		}
+
+		result
+	}
+
+	#[must_use]
+	pub fn overlay(&self) -> Option<&OverlayStore> {
+		self.overlay.as_ref().map(|overlay| &overlay.store)
+	}
+
+	/// Creates or replaces the file at `vpath`, creating any missing folders
+	/// along the way. If a file was already at `vpath`, it keeps its slot.
+	pub fn write(&mut self, vpath: &VPath, bytes: &[u8]) -> Result<FileSlot, Error> {
+		let Some(store) = self.overlay_store() else {
+			return Err(Error::NoOverlay);
+		};
+
+		let components = overlay_components(vpath)?;
+
+		let Some((name, parents)) = components.split_last() else {
+			return Err(Error::OverlayPath);
+		};
+
+		let parent = self.overlay_folder(parents)?;
+
+		if let Some(Slot::Folder(_)) = self.folders[parent].find_child(name) {
+			return Err(Error::OverlayPath);
+		}
+
+		let reader = match store {
+			OverlayStore::Memory => Reader::Memory(bytes.to_vec()),
+			OverlayStore::Directory(dir) => {
+				let real = real_path(&dir, &components);
+
+				if let Some(real_parent) = real.parent() {
+					std::fs::create_dir_all(real_parent).map_err(Error::OverlayWrite)?;
+				}
+
+				std::fs::write(&real, bytes).map_err(Error::OverlayWrite)?;
+				Reader::File(std::fs::File::open(&real).map_err(Error::FileOpen)?)
+			}
+		};
+
+		let kind = ChangeKind::File {
+			reader: Arc::new(Mutex::new(reader)),
+			len: bytes.len(),
+			replaces: false,
+		};
+
+		match self.record_change(&components, kind)? {
+			Some(Slot::File(islot)) => Ok(islot),
+			_ => unreachable!(),
+		}
+	}
+
+	/// Creates the folder at `vpath` and any missing folders along the way.
+	/// Succeeds without doing anything if the folder already exists.
+	pub fn create_folder(&mut self, vpath: &VPath) -> Result<FolderSlot, Error> {
+		let Some(store) = self.overlay_store() else {
+			return Err(Error::NoOverlay);
+		};
+
+		let components = overlay_components(vpath)?;
+
+		if let OverlayStore::Directory(dir) = store {
+			std::fs::create_dir_all(real_path(&dir, &components)).map_err(Error::OverlayWrite)?;
+		}
+
+		match self.record_change(&components, ChangeKind::Folder { replaces: false })? {
+			Some(Slot::Folder(oslot)) => Ok(oslot),
+			_ => unreachable!(),
+		}
+	}
+
+	/// Hides the file or folder at `vpath` (and everything under it) from the tree.
+	/// The root of a mount can not be deleted; see [`Self::unmount`] instead.
+	pub fn delete(&mut self, vpath: &VPath) -> Result<(), Error> {
+		let Some(store) = self.overlay_store() else {
+			return Err(Error::NoOverlay);
+		};
+
+		let components = overlay_components(vpath)?;
+
+		let Some((name, parents)) = components.split_last() else {
+			return Err(Error::OverlayPath);
+		};
+
+		let slot = self.lookup(vpath).ok_or(Error::NotFound)?.slot();
+
+		if self.mounts.iter().any(|mntinfo| mntinfo.root == slot) {
+			return Err(Error::OverlayPath);
+		}
+
+		if let OverlayStore::Directory(dir) = store {
+			let real = real_path(&dir, &components);
+
+			let result = if real.is_dir() {
+				std::fs::remove_dir_all(&real)
+			} else if real.exists() {
+				std::fs::remove_file(&real)
+			} else {
+				Ok(())
+			};
+
+			result.map_err(Error::OverlayWrite)?;
+
+			let real_parent = real_path(&dir, parents);
+			std::fs::create_dir_all(&real_parent).map_err(Error::OverlayWrite)?;
+			std::fs::write(real_parent.join(format!("{WHITEOUT}{name}")), [])
+				.map_err(Error::OverlayWrite)?;
+		}
+
+		self.record_change(&components, ChangeKind::Deleted)?;
+		Ok(())
+	}
+
+	/// Applies every overlay change at or under the root-level entry named `name`,
+	/// in the order they were made. Changes which conflict with the tree are skipped.
+	pub(crate) fn reapply_overlay(&mut self, name: &str) {
+		self.reapply_overlay_key(fold_name(name).as_str());
+	}
+
+	/// Returns `true` if the overlay has any changes at or under
+	/// the root-level entry `root` (e.g. the root of a mount).
+	#[must_use]
+	pub(crate) fn overlay_changed(&self, root: Slot) -> bool {
+		let Some(overlay) = &self.overlay else {
+			return false;
+		};
+
+		let first = fold_name(self.slot_name(root));
+
+		overlay
+			.changes
+			.keys()
+			.any(|key| key.split('/').next() == Some(first.as_str()))
+	}
+
+	/// Like [`Self::reapply_overlay`], but for the entry named `name` in `parent`.
+	/// Returns `true` if the overlay has any changes at or under that entry.
+	pub(crate) fn reapply_overlay_at(&mut self, parent: FolderSlot, name: &str) -> bool {
+		let key = self.overlay_key(parent, name);
+		self.reapply_overlay_key(&key)
+	}
+
+	/// Returns `true` if the overlay has a change at the entry named `name` in
+	/// `parent` which takes precedence over the real file system; that is,
+	/// anything other than creating a folder where one already was.
+	#[must_use]
+	pub(crate) fn overlay_shadows(&self, parent: FolderSlot, name: &str) -> bool {
+		let Some(overlay) = &self.overlay else {
+			return false;
+		};
+
+		overlay
+			.changes
+			.get(&self.overlay_key(parent, name))
+			.is_some_and(|change| !matches!(change.kind, ChangeKind::Folder { replaces: false }))
+	}
+
+	fn reapply_overlay_key(&mut self, key: &str) -> bool {
+		let Some(overlay) = self.overlay.take() else {
+			return false;
+		};
+
+		let under = format!("{key}/");
+		let mut any = false;
+
+		for (k, change) in &overlay.changes {
+			if k == key || k.starts_with(&under) {
+				let components = change.path.split('/').collect::<Vec<_>>();
+				let _ = self.apply_change(&components, &change.kind);
+				any = true;
+			}
+		}
+
+		self.overlay = Some(overlay);
+		any
+	}
+
+	/// The key in [`Overlay::changes`] of the entry named `name` in `parent`.
+	#[must_use]
+	fn overlay_key(&self, parent: FolderSlot, name: &str) -> String {
+		let mut key = String::from(fold_name(name).as_str());
+		let mut oslot = parent;
+
+		while let Some(grandparent) = self.folders[oslot].parent {
+			key.insert(0, '/');
+			key.insert_str(0, fold_name(&self.folders[oslot].name).as_str());
+			oslot = grandparent;
+		}
+
+		key
+	}
+
+	#[must_use]
+	fn overlay_store(&self) -> Option<OverlayStore> {
+		self.overlay.as_ref().map(|overlay| overlay.store.clone())
+	}
+
+	/// Applies a change to the tree and then records it in the overlay,
+	/// merging it with any earlier change at the same path.
+	fn record_change(
+		&mut self,
+		components: &[&str],
+		kind: ChangeKind,
+	) -> Result<Option<Slot>, Error> {
+		let key = components
+			.iter()
+			.map(|comp| fold_name(comp))
+			.collect::<Vec<_>>()
+			.join("/");
+
+		let overlay = self.overlay.as_mut().unwrap();
+		let prev = overlay.changes.get(&key).map(|change| &change.kind);
+
+		let replaced = matches!(
+			prev,
+			Some(
+				ChangeKind::Deleted
+					| ChangeKind::File { replaces: true, .. }
+					| ChangeKind::Folder { replaces: true }
+			)
+		);
+
+		let kind = match kind {
+			ChangeKind::File { reader, len, .. } => ChangeKind::File {
+				reader,
+				len,
+				replaces: replaced,
+			},
+			ChangeKind::Folder { .. } => ChangeKind::Folder { replaces: replaced },
+			ChangeKind::Deleted => {
+				let under = format!("{key}/");
+				overlay.changes.retain(|k, _| !k.starts_with(&under));
+				ChangeKind::Deleted
+			}
+		};
+
+		// Whatever `replaces` would delete is already gone.
+		let ret = match &kind {
+			ChangeKind::File { reader, len, .. } => {
+				let unreplaced = ChangeKind::File {
+					reader: reader.clone(),
+					len: *len,
+					replaces: false,
+				};
+
+				self.apply_change(components, &unreplaced)?
+			}
+			ChangeKind::Folder { .. } => {
+				self.apply_change(components, &ChangeKind::Folder { replaces: false })?
+			}
+			ChangeKind::Deleted => self.apply_change(components, &ChangeKind::Deleted)?,
+		};
+
+		let change = Change {
+			path: components.join("/"),
+			kind,
+		};
+
+		// Keeping the position of an earlier change at the same path ensures that
+		// a replacement still gets applied before anything written under it.
+		let overlay = self.overlay.as_mut().unwrap();
+
+		match overlay.changes.get_mut(&key) {
+			Some(prev) if matches!(change.kind, ChangeKind::Folder { .. }) => {
+				if let ChangeKind::Deleted = prev.kind {
+					*prev = change;
+				}
+			}
+			_ => {
+				overlay.changes.insert(key, change);
+			}
+		}
+
+		Ok(ret)
+	}
+
+	/// Returns the slot of whatever the change left at `components`, if anything.
+	fn apply_change(
+		&mut self,
+		components: &[&str],
+		kind: &ChangeKind,
+	) -> Result<Option<Slot>, Error> {
+		let replaces = match kind {
+			ChangeKind::File { replaces, .. } | ChangeKind::Folder { replaces } => *replaces,
+			ChangeKind::Deleted => true,
+		};
+
+		if replaces {
+			let existing = components
+				.iter()
+				.try_fold(Slot::Folder(self.root), |slot, comp| match slot {
+					Slot::Folder(oslot) => self.folders[oslot].find_child(comp),
+					Slot::File(_) => None,
+				});
+
+			match existing {
+				Some(slot) if self.mounts.iter().any(|mntinfo| mntinfo.root == slot) => {}
+				Some(Slot::File(islot)) => {
+					self.remove_file(islot);
+				}
+				Some(Slot::Folder(oslot)) if oslot != self.root => self.remove_folder_recur(oslot),
+				Some(Slot::Folder(_)) | None => {}
+			}
+		}
+
+		match kind {
+			ChangeKind::File { reader, len, .. } => {
+				let Some((name, parents)) = components.split_last() else {
+					return Err(Error::OverlayPath);
+				};
+
+				let parent = self.overlay_folder(parents)?;
+
+				if let Some(Slot::Folder(_)) = self.folders[parent].find_child(name) {
+					return Err(Error::OverlayPath);
+				}
+
+				let islot = self.overlay_link(parent, name, reader.clone(), *len);
+				Ok(Some(Slot::File(islot)))
+			}
+			ChangeKind::Folder { .. } => Ok(Some(Slot::Folder(self.overlay_folder(components)?))),
+			ChangeKind::Deleted => Ok(None),
+		}
+	}
+
+	/// Returns the slot of the folder at `components`, creating any
+	/// missing folders as [`FolderKind::Overlay`].
+	fn overlay_folder(&mut self, components: &[&str]) -> Result<FolderSlot, Error> {
+		let mut oslot = self.root;
+
+		for comp in components {
+			oslot = match self.folders[oslot].find_child(comp) {
+				Some(Slot::Folder(sfslot)) => sfslot,
+				Some(Slot::File(_)) => return Err(Error::OverlayPath),
+				None => {
+					let sfslot = self.folders.insert(VFolder {
+						name: SmallString::from(*comp),
+						parent: Some(oslot),
+						files: indexmap::indexset![],
+						subfolders: indexmap::indexset![],
+						index: HashMap::default(),
+						kind: FolderKind::Overlay,
+					});
+
+					self.folders[oslot].link_subfolder(sfslot, comp);
+					sfslot
+				}
+			};
+		}
+
+		Ok(oslot)
+	}
+
+	/// Points the file named `name` in `parent` at `reader`, creating it first
+	/// if it does not exist. The caller must ensure no folder has that name.
+	fn overlay_link(
+		&mut self,
+		parent: FolderSlot,
+		name: &str,
+		reader: Arc<Mutex<Reader>>,
+		len: usize,
+	) -> FileSlot {
+		let span = 0..(len as u32);
+
+		if let Some(Slot::File(islot)) = self.folders[parent].find_child(name) {
+			let vfile = &mut self.files[islot];
+
+			if Arc::ptr_eq(&vfile.reader, &reader) {
+				return islot;
+			}
+
+			vfile.reader = reader;
+			vfile.span = span;
+			vfile.compression = Compression::None;
+			self.forget_content(islot);
+			// No longer backed by the real file it was mounted from.
+			self.stamps.remove(&Slot::File(islot));
+			return islot;
+		}
+
+		let islot = self.files.insert(VFile {
+			name: SmallString::from(name),
+			parent,
+			reader,
+			span,
+			compression: Compression::None,
+		});
+
+		self.folders[parent].link_file(islot, name);
+		islot
+	}
+
+	/// `components` is the virtual path of `real`, which is in the overlay's directory.
+	/// Within each directory, whiteouts get applied first.
+	fn load_overlay_dir(&mut self, real: &Path, components: &mut Vec<String>) -> Result<(), Error> {
+		let mut entries = vec![];
+
+		for result in std::fs::read_dir(real).map_err(Error::DirRead)? {
+			let d_ent = result.map_err(Error::DirRead)?;
+			let name = d_ent.file_name().to_string_lossy().into_owned();
+			entries.push((name, d_ent.path()));
+		}
+
+		entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+		let mut whiteouts = vec![];
+
+		for (name, _) in &entries {
+			if let Some(deleted) = name.strip_prefix(WHITEOUT) {
+				components.push(deleted.to_owned());
+				let comps = components.iter().map(String::as_str).collect::<Vec<_>>();
+				self.record_change(&comps, ChangeKind::Deleted)?;
+				components.pop();
+				whiteouts.push(deleted.to_owned());
+			}
+		}
+
+		for (name, path) in entries {
+			if name.starts_with(WHITEOUT) {
+				continue;
+			}
+
+			components.push(name);
+			let comps = components.iter().map(String::as_str).collect::<Vec<_>>();
+
+			if path.is_dir() {
+				self.record_change(&comps, ChangeKind::Folder { replaces: false })?;
+				self.load_overlay_dir(&path, components)?;
+			} else {
+				let fh = std::fs::File::open(&path).map_err(Error::FileOpen)?;
+				let len = fh.metadata().map_err(Error::Metadata)?.len();
+
+				let kind = ChangeKind::File {
+					reader: Arc::new(Mutex::new(Reader::File(fh))),
+					len: len as usize,
+					replaces: false,
+				};
+
+				self.record_change(&comps, kind)?;
+			}
+
+			components.pop();
+		}
+
+		Ok(())
+	}
+
+	#[must_use]
+	fn slot_name(&self, slot: Slot) -> &str {
+		match slot {
+			Slot::File(islot) => self.files[islot].name.as_str(),
+			Slot::Folder(oslot) => self.folders[oslot].name.as_str(),
+		}
+	}
+}
+
+/// Prefixes the names of the files which record deletions in an [`OverlayStore::Directory`].
+const WHITEOUT: &str = ".wh.";
+
+/// Rejects `.` and `..` components, so that an overlay directory can not be escaped,
+/// and names which could be mistaken for whiteouts.
+fn overlay_components(vpath: &VPath) -> Result<Vec<&str>, Error> {
+	vpath
+		.components()
+		.map(|comp| match comp.as_str() {
+			"." | ".." => Err(Error::OverlayPath),
+			other if other.starts_with(WHITEOUT) => Err(Error::OverlayPath),
+			other => Ok(other),
+		})
+		.collect()
+}
+
+#[must_use]
+fn real_path(dir: &Path, components: &[&str]) -> PathBuf {
+	let mut ret = dir.to_path_buf();
+	ret.extend(components);
+	ret
+}
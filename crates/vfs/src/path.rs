@@ -6,6 +6,7 @@ use util::Id8;
 
 /// Components are separated by `/` like in Unixes.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VPathBuf(String);
 
 impl VPathBuf {
@@ -0,0 +1,213 @@
+//! [`Query`] and its related symbols.
+
+use globset::{GlobBuilder, GlobMatcher};
+use rayon::prelude::*;
+
+use crate::{Error, FileRef, FolderRef, FolderSlot, VirtualFs};
+
+/// Finds every virtual file under a folder which passes a set of filters.
+///
+/// Created by [`VirtualFs::query`] or [`FolderRef::query`].
+/// A file has to pass every glob and every predicate to be yielded.
+pub struct Query<'vfs> {
+	vfs: &'vfs VirtualFs,
+	root: FolderSlot,
+	globs: Vec<Glob>,
+	predicates: Vec<Predicate<'vfs>>,
+}
+
+type Predicate<'vfs> = Box<dyn Fn(&FileRef<'vfs>) -> bool + Send + Sync + 'vfs>;
+
+#[derive(Debug)]
+struct Glob {
+	matcher: GlobMatcher,
+	/// If `false`, only the file's name gets matched.
+	full_path: bool,
+}
+
+impl<'vfs> Query<'vfs> {
+	/// Matching is ASCII case-insensitive, and `*` never matches a `/`
+	/// (use `**` for that).
+	///
+	/// If `pattern` contains a `/`, it is matched against each file's
+	/// absolute path (e.g. `/pk3/zscript/**/*.zs`); otherwise it is only
+	/// matched against the file's name (e.g. `DECORATE*`).
+	pub fn glob(mut self, pattern: &str) -> Result<Self, Error> {
+		let glob = GlobBuilder::new(pattern)
+			.case_insensitive(true)
+			.literal_separator(true)
+			.build()
+			.map_err(Error::Glob)?;
+
+		self.globs.push(Glob {
+			matcher: glob.compile_matcher(),
+			full_path: pattern.contains('/'),
+		});
+
+		Ok(self)
+	}
+
+	#[must_use]
+	pub fn filter<F>(mut self, predicate: F) -> Self
+	where
+		F: 'vfs + Fn(&FileRef<'vfs>) -> bool + Send + Sync,
+	{
+		self.predicates.push(Box::new(predicate));
+		self
+	}
+
+	/// Yields matches lazily, depth-first; each folder's files
+	/// come before the contents of its subfolders.
+	pub fn iter<'q>(&'q self) -> impl Iterator<Item = FileRef<'vfs>> + 'q {
+		QueryIter {
+			query: self,
+			stack: vec![(self.root, self.root_path())],
+			current: None,
+		}
+	}
+
+	/// Like [`Self::iter`], but every folder gets searched in parallel,
+	/// and so matches are not yielded in any particular order.
+	pub fn par_iter<'q>(&'q self) -> impl ParallelIterator<Item = FileRef<'vfs>> + 'q {
+		let mut folders = vec![];
+		let mut stack = vec![(self.root, self.root_path())];
+
+		while let Some((oslot, path)) = stack.pop() {
+			for sfslot in self.vfs.folders[oslot].subfolders.iter().copied() {
+				let name = self.vfs.folders[sfslot].name.as_str();
+				stack.push((sfslot, format!("{path}/{name}")));
+			}
+
+			folders.push((oslot, path));
+		}
+
+		folders.into_par_iter().flat_map_iter(move |(oslot, path)| {
+			self.vfs.folders[oslot]
+				.files
+				.iter()
+				.copied()
+				.map(|islot| FileRef {
+					vfs: self.vfs,
+					slot: islot,
+					vfile: &self.vfs.files[islot],
+				})
+				.filter(move |iref| self.matches(&path, iref))
+		})
+	}
+
+	/// The root folder's path is treated as being empty,
+	/// so that a slash can be put between it and any child's name.
+	#[must_use]
+	fn root_path(&self) -> String {
+		if self.root == self.vfs.root {
+			String::new()
+		} else {
+			let oref = FolderRef {
+				vfs: self.vfs,
+				slot: self.root,
+				vfolder: &self.vfs.folders[self.root],
+			};
+
+			oref.path().as_str().to_owned()
+		}
+	}
+
+	/// `parent_path` is that of the folder containing `iref`.
+	#[must_use]
+	fn matches(&self, parent_path: &str, iref: &FileRef<'vfs>) -> bool {
+		let name = iref.name.as_str();
+		let mut full_path = None;
+
+		for glob in &self.globs {
+			let matched = if glob.full_path {
+				let path = full_path.get_or_insert_with(|| format!("{parent_path}/{name}"));
+				glob.matcher.is_match(path.as_str())
+			} else {
+				glob.matcher.is_match(name)
+			};
+
+			if !matched {
+				return false;
+			}
+		}
+
+		self.predicates.iter().all(|predicate| predicate(iref))
+	}
+}
+
+impl std::fmt::Debug for Query<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Query")
+			.field("root", &self.root)
+			.field("globs", &self.globs)
+			.field("predicates", &self.predicates.len())
+			.finish()
+	}
+}
+
+struct QueryIter<'q, 'vfs> {
+	query: &'q Query<'vfs>,
+	/// Folders yet to be searched, and their paths.
+	stack: Vec<(FolderSlot, String)>,
+	/// The folder being searched, its path, and the index of its next file.
+	current: Option<(FolderSlot, String, usize)>,
+}
+
+impl<'vfs> Iterator for QueryIter<'_, 'vfs> {
+	type Item = FileRef<'vfs>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let vfs = self.query.vfs;
+
+		loop {
+			if let Some((oslot, path, i)) = &mut self.current {
+				let folder = &vfs.folders[*oslot];
+
+				while let Some(islot) = folder.files.get_index(*i).copied() {
+					*i += 1;
+
+					let iref = FileRef {
+						vfs,
+						slot: islot,
+						vfile: &vfs.files[islot],
+					};
+
+					if self.query.matches(path, &iref) {
+						return Some(iref);
+					}
+				}
+
+				for sfslot in folder.subfolders.iter().rev().copied() {
+					let name = vfs.folders[sfslot].name.as_str();
+					self.stack.push((sfslot, format!("{path}/{name}")));
+				}
+
+				self.current = None;
+			}
+
+			let (oslot, path) = self.stack.pop()?;
+			self.current = Some((oslot, path, 0));
+		}
+	}
+}
+
+impl VirtualFs {
+	/// Starts a [`Query`] over every file in this VFS.
+	#[must_use]
+	pub fn query(&self) -> Query<'_> {
+		self.root().query()
+	}
+}
+
+impl<'vfs> FolderRef<'vfs> {
+	/// Starts a [`Query`] over every file under this folder, however deep.
+	#[must_use]
+	pub fn query(&self) -> Query<'vfs> {
+		Query {
+			vfs: self.vfs,
+			root: self.slot,
+			globs: vec![],
+			predicates: vec![],
+		}
+	}
+}
@@ -0,0 +1,298 @@
+//! [`FileStream`] and its related symbols.
+
+use std::{
+	io::{Cursor, Read, Seek, SeekFrom},
+	ops::Range,
+	sync::Arc,
+};
+
+use bzip2::read::BzDecoder;
+use flate2::read::DeflateDecoder;
+use parking_lot::Mutex;
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+use crate::{
+	detail::{io_error, Compression, Reader},
+	FileRef,
+};
+
+/// A [`Read`] and [`Seek`] handle over the content of one virtual file,
+/// created by [`FileRef::open`].
+///
+/// Bzip2, deflate, and zstd content gets decompressed incrementally as it is read.
+/// Reading after seeking forward through such content decompresses and discards
+/// everything in between, and after seeking backward, decompression restarts
+/// from the beginning.
+/// LZMA and XZ content, as well as entries of archives mounted by a registered
+/// [`crate::ArchiveBackend`], get read and decompressed in full upon the first read.
+///
+/// The backing reader is only locked for the duration of each read, so other
+/// handles to files in the same archive can be used in between. This handle keeps
+/// reading the same content even if its file gets replaced or removed afterwards.
+#[derive(Debug)]
+pub struct FileStream {
+	reader: Arc<Mutex<Reader>>,
+	span: Range<usize>,
+	compression: Compression,
+	/// `None` until the first read, and after seeking
+	/// backwards through incrementally-decompressed content.
+	decoder: Option<Decoder>,
+	/// Within the decompressed content.
+	pos: u64,
+	/// How far an incremental `decoder` has gotten.
+	decoded: u64,
+	/// Of the decompressed content, if known yet.
+	len: Option<u64>,
+}
+
+enum Decoder {
+	Raw(RawStream),
+	Bzip2(BzDecoder<RawStream>),
+	Deflate(DeflateDecoder<RawStream>),
+	Zstd(ZstdDecoder<'static, std::io::BufReader<RawStream>>),
+	Buffered(Cursor<Vec<u8>>),
+}
+
+impl std::fmt::Debug for Decoder {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Raw(raw) => f.debug_tuple("Raw").field(raw).finish(),
+			Self::Bzip2(_) => write!(f, "Bzip2"),
+			Self::Deflate(_) => write!(f, "Deflate"),
+			Self::Zstd(_) => write!(f, "Zstd"),
+			Self::Buffered(cursor) => f
+				.debug_tuple("Buffered")
+				.field(&cursor.get_ref().len())
+				.finish(),
+		}
+	}
+}
+
+impl FileStream {
+	/// `len` is that of the decompressed content, if known up-front.
+	#[must_use]
+	pub(crate) fn new(
+		reader: Arc<Mutex<Reader>>,
+		span: Range<usize>,
+		compression: Compression,
+		len: Option<u64>,
+	) -> Self {
+		Self {
+			reader,
+			span,
+			compression,
+			decoder: None,
+			pos: 0,
+			decoded: 0,
+			len,
+		}
+	}
+
+	/// The length of the file's decompressed content. If this is not known yet,
+	/// the content gets decompressed to the end to find out, so prefer
+	/// [`crate::VFile::size`] for uncompressed files.
+	pub fn len(&mut self) -> std::io::Result<u64> {
+		if self.len.is_none() {
+			// Creating a buffered decoder determines the length.
+			let _ = self.decoder()?;
+		}
+
+		if let Some(len) = self.len {
+			return Ok(len);
+		}
+
+		self.skip_to(u64::MAX)?;
+		Ok(self.decoded)
+	}
+
+	pub fn is_empty(&mut self) -> std::io::Result<bool> {
+		self.len().map(|len| len == 0)
+	}
+
+	fn decoder(&mut self) -> std::io::Result<&mut Decoder> {
+		let decoder = match self.decoder.take() {
+			Some(decoder) => decoder,
+			None => {
+				let raw = RawStream {
+					reader: self.reader.clone(),
+					span: self.span.clone(),
+					pos: 0,
+				};
+
+				let custom = matches!(*self.reader.lock(), Reader::Custom(_));
+
+				match self.compression {
+					// Custom readers might not support reading part of an entry.
+					_ if custom => self.buffered()?,
+					Compression::None => Decoder::Raw(raw),
+					Compression::Bzip2 => Decoder::Bzip2(BzDecoder::new(raw)),
+					Compression::Deflate => Decoder::Deflate(DeflateDecoder::new(raw)),
+					Compression::Zstd => Decoder::Zstd(ZstdDecoder::new(raw)?),
+					Compression::Lzma { .. } | Compression::Xz | Compression::Unsupported(_) => {
+						self.buffered()?
+					}
+				}
+			}
+		};
+
+		Ok(self.decoder.insert(decoder))
+	}
+
+	/// `false` if the content was (or will be) decompressed in full by the first read.
+	#[must_use]
+	pub(crate) fn is_incremental(&self) -> bool {
+		!matches!(self.decoder, Some(Decoder::Buffered(_)))
+	}
+
+	fn buffered(&mut self) -> std::io::Result<Decoder> {
+		let mut guard = self.reader.lock();
+
+		let bytes = guard
+			.read(self.span.clone(), self.compression)
+			.map_err(io_error)?
+			.into_owned();
+
+		self.len = Some(bytes.len() as u64);
+		Ok(Decoder::Buffered(Cursor::new(bytes)))
+	}
+
+	/// Advances an incremental decoder to `target`, or to the end of the
+	/// content if that comes first, restarting it first if it is past `target`.
+	fn skip_to(&mut self, target: u64) -> std::io::Result<()> {
+		if target < self.decoded {
+			self.decoder = None;
+			self.decoded = 0;
+		}
+
+		let mut scratch = [0; 4096];
+
+		while self.decoded < target {
+			let max = (target - self.decoded).min(scratch.len() as u64) as usize;
+
+			if self.read_incremental(&mut scratch[..max])? == 0 {
+				break;
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Reads from wherever an incremental decoder currently is,
+	/// updating `decoded`, and `len` if the end gets reached.
+	fn read_incremental(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let n = match self.decoder()? {
+			Decoder::Bzip2(bzip2) => bzip2.read(buf)?,
+			Decoder::Deflate(deflate) => deflate.read(buf)?,
+			Decoder::Zstd(zstd) => zstd.read(buf)?,
+			Decoder::Raw(_) | Decoder::Buffered(_) => unreachable!(),
+		};
+
+		self.decoded += n as u64;
+
+		if n == 0 && !buf.is_empty() {
+			self.len = Some(self.decoded);
+		}
+
+		Ok(n)
+	}
+}
+
+impl Read for FileStream {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let pos = self.pos;
+
+		let n = match self.decoder()? {
+			Decoder::Raw(raw) => {
+				raw.pos = pos.try_into().unwrap_or(usize::MAX);
+				raw.read(buf)?
+			}
+			Decoder::Buffered(cursor) => {
+				cursor.set_position(pos);
+				cursor.read(buf)?
+			}
+			Decoder::Bzip2(_) | Decoder::Deflate(_) | Decoder::Zstd(_) => {
+				if pos != self.decoded {
+					self.skip_to(pos)?;
+
+					if pos != self.decoded {
+						// Past the end.
+						return Ok(0);
+					}
+				}
+
+				self.read_incremental(buf)?
+			}
+		};
+
+		self.pos += n as u64;
+		Ok(n)
+	}
+}
+
+impl Seek for FileStream {
+	fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+		let target = match pos {
+			SeekFrom::Start(offset) => Some(offset),
+			SeekFrom::End(offset) => self.len()?.checked_add_signed(offset),
+			SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+		};
+
+		let Some(target) = target else {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				"attempted to seek before the start of a virtual file",
+			));
+		};
+
+		// Any decompression needed to get there happens upon the next read.
+		self.pos = target;
+		Ok(target)
+	}
+}
+
+/// Reads a span of a [`Reader`] without decompressing it.
+#[derive(Debug)]
+struct RawStream {
+	reader: Arc<Mutex<Reader>>,
+	span: Range<usize>,
+	/// Relative to the start of `span`.
+	pos: usize,
+}
+
+impl Read for RawStream {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let n = self.span.len().saturating_sub(self.pos).min(buf.len());
+
+		if n == 0 {
+			return Ok(0);
+		}
+
+		self.reader
+			.lock()
+			.read_at(self.span.start + self.pos, &mut buf[..n])
+			.map_err(io_error)?;
+
+		self.pos += n;
+		Ok(n)
+	}
+}
+
+impl FileRef<'_> {
+	/// Unlike [`Self::lock`], this does not read the file's entire content at once.
+	/// See [`FileStream`].
+	#[must_use]
+	pub fn open(&self) -> FileStream {
+		let len = match self.compression {
+			Compression::None => Some(self.size() as u64),
+			Compression::Lzma { unpacked_size } => Some(unpacked_size as u64),
+			_ => None,
+		};
+
+		FileStream::new(
+			self.vfile.reader.clone(),
+			self.vfile.span(),
+			self.compression,
+			len,
+		)
+	}
+}
@@ -1,3 +1,5 @@
+use std::{collections::HashMap, io::Read};
+
 use super::*;
 
 #[test]
@@ -112,6 +114,1723 @@ fn read_smoke() {
 	);
 }
 
+#[test]
+fn custom_backend() {
+	/// Magic number, then a `u8` entry count, then for each entry:
+	/// a `u8` name length, the name, a `u8` data length, and the data.
+	#[derive(Debug)]
+	struct ToyBackend;
+
+	#[derive(Debug)]
+	struct ToyReader(Vec<u8>);
+
+	impl ArchiveBackend for ToyBackend {
+		fn name(&self) -> &'static str {
+			"toy"
+		}
+
+		fn detect(&self, magic: &[u8], _: &str) -> bool {
+			magic.starts_with(b"TOY!")
+		}
+
+		fn mount(&self, ctx: &mut MountContext, mut source: Box<dyn Source>) -> Result<(), Error> {
+			let mut bytes = vec![];
+			source.read_to_end(&mut bytes).map_err(Error::FileRead)?;
+			let reader = SharedReader::new(ToyReader(bytes.clone()));
+			let mut pos = 5;
+
+			for _ in 0..bytes[4] {
+				let name_len = bytes[pos] as usize;
+				let name = std::str::from_utf8(&bytes[(pos + 1)..(pos + 1 + name_len)])
+					.map_err(|err| Error::Archive(Box::new(err)))?;
+				pos += 1 + name_len;
+
+				let data_len = bytes[pos] as u32;
+				let start = (pos + 1) as u32;
+				let (dir, name) = name.rsplit_once('/').unwrap_or(("", name));
+				let parent = ctx.folder_path(dir.split('/').filter(|c| !c.is_empty()));
+				ctx.file(parent, name, &reader, start..(start + data_len));
+				pos += 1 + data_len as usize;
+			}
+
+			Ok(())
+		}
+	}
+
+	impl ArchiveReader for ToyReader {
+		fn read(&mut self, span: Range<usize>) -> Result<Vec<u8>, Error> {
+			Ok(self.0[span].to_vec())
+		}
+	}
+
+	let mut bytes = Vec::from(*b"TOY!\x02");
+	bytes.extend([6]);
+	bytes.extend(b"README");
+	bytes.extend([3, 0xAA, 0xBB, 0xCC]);
+	bytes.extend([9]);
+	bytes.extend(b"data/CONF");
+	bytes.extend([1, 0xDD]);
+
+	let tmp = TempDir::new("custom");
+	let path = tmp.join("archive.toy");
+	std::fs::write(&path, &bytes).unwrap();
+
+	let mut vfs = VirtualFs::default();
+	vfs.mount(&path, VPath::new("plain")).unwrap();
+	assert_eq!(vfs.mounts()[0].format, MountFormat::Uncompressed);
+
+	vfs.register_backend(ToyBackend);
+	vfs.mount(&path, VPath::new("toy")).unwrap();
+
+	assert_eq!(vfs.mounts()[1].format, MountFormat::Other("toy"));
+	assert_eq!(vfs.backends().count(), 6);
+
+	let folder = vfs
+		.lookup(VPath::new("/toy"))
+		.unwrap()
+		.into_folder()
+		.unwrap();
+	assert_eq!(folder.kind(), FolderKind::Other("toy"));
+
+	let data = vfs
+		.lookup(VPath::new("/toy/data"))
+		.unwrap()
+		.into_folder()
+		.unwrap();
+	assert_eq!(data.kind(), FolderKind::OtherDir("toy"));
+
+	let readme = vfs
+		.lookup(VPath::new("/toy/README"))
+		.unwrap()
+		.into_file()
+		.unwrap();
+	assert_eq!(readme.lock().read().unwrap().as_ref(), &[0xAA, 0xBB, 0xCC]);
+
+	let conf = vfs
+		.lookup(VPath::new("/toy/data/conf"))
+		.unwrap()
+		.into_file()
+		.unwrap();
+	assert_eq!(conf.lock().read().unwrap().as_ref(), &[0xDD]);
+
+	vfs.ingest_all();
+
+	let conf = vfs
+		.lookup(VPath::new("/toy/data/conf"))
+		.unwrap()
+		.into_file()
+		.unwrap();
+	assert_eq!(conf.lock().read().unwrap().as_ref(), &[0xDD]);
+}
+
+#[test]
+fn zip_compression() {
+	use std::io::Write;
+
+	let plain = b"All work and no play makes Jack a dull boy. ".repeat(16);
+
+	let deflated = {
+		let mut enc = flate2::write::DeflateEncoder::new(vec![], flate2::Compression::best());
+		enc.write_all(&plain).unwrap();
+		enc.finish().unwrap()
+	};
+
+	let bzipped = {
+		let mut enc = bzip2::write::BzEncoder::new(vec![], bzip2::Compression::best());
+		enc.write_all(&plain).unwrap();
+		enc.finish().unwrap()
+	};
+
+	let lzma = {
+		let mut alone = vec![];
+		lzma_rs::lzma_compress(&mut plain.as_slice(), &mut alone).unwrap();
+		// Swap the `.lzma` header's unpacked size for the zip format's version
+		// and properties length.
+		let mut ret = vec![9, 20, 5, 0];
+		ret.extend_from_slice(&alone[..5]);
+		ret.extend_from_slice(&alone[13..]);
+		ret
+	};
+
+	let xz = {
+		let mut ret = vec![];
+		lzma_rs::xz_compress(&mut plain.as_slice(), &mut ret).unwrap();
+		ret
+	};
+
+	let zstd = zstd::encode_all(plain.as_slice(), 0).unwrap();
+
+	let entries: &[(&str, u16, &[u8])] = &[
+		("stored.txt", 0, &plain),
+		("deflate.txt", 8, &deflated),
+		("bzip2.txt", 12, &bzipped),
+		("lzma.txt", 14, &lzma),
+		("zstd.txt", 93, &zstd),
+		("xz.txt", 95, &xz),
+		("ppmd.txt", 98, &[0x00; 8]),
+	];
+
+	let zip = zip_archive(
+		&entries
+			.iter()
+			.map(|(name, method, data)| (*name, *method, *data, plain.len()))
+			.collect::<Vec<_>>(),
+	);
+
+	let tmp = TempDir::new("zip");
+	let path = tmp.join("test.zip");
+	std::fs::write(&path, &zip).unwrap();
+	let mut vfs = VirtualFs::default();
+	vfs.mount(&path, VPath::new("zip")).unwrap();
+
+	for (name, method, _) in entries {
+		let vpath = VPathBuf::new(format!("/zip/{name}"));
+		let file = vfs.lookup(&vpath).unwrap().into_file().unwrap();
+		let mut guard = file.lock();
+
+		if *method == 98 {
+			assert!(matches!(
+				guard.read(),
+				Err(Error::UnsupportedCompression(98))
+			));
+		} else {
+			assert_eq!(guard.read().unwrap().as_ref(), plain.as_slice(), "{name}");
+		}
+	}
+
+	// A corrupt or malicious archive's claimed size is not allocated up front.
+	let zip = zip_archive(&[("lzma.txt", 14, &lzma, u32::MAX as usize)]);
+	std::fs::write(&path, &zip).unwrap();
+	vfs.remount(VPath::new("zip")).unwrap();
+	let file = vfs
+		.lookup(VPath::new("/zip/lzma.txt"))
+		.unwrap()
+		.into_file()
+		.unwrap();
+	assert!(file.lock().read().is_err());
+}
+
+#[test]
+fn stream() {
+	use std::io::{Seek, SeekFrom, Write};
+
+	let plain = (0..20000_u32)
+		.flat_map(|i| (i * 7919 % 65521).to_le_bytes())
+		.collect::<Vec<_>>();
+
+	let deflated = {
+		let mut enc = flate2::write::DeflateEncoder::new(vec![], flate2::Compression::best());
+		enc.write_all(&plain).unwrap();
+		enc.finish().unwrap()
+	};
+
+	let bzipped = {
+		let mut enc = bzip2::write::BzEncoder::new(vec![], bzip2::Compression::best());
+		enc.write_all(&plain).unwrap();
+		enc.finish().unwrap()
+	};
+
+	let xz = {
+		let mut ret = vec![];
+		lzma_rs::xz_compress(&mut plain.as_slice(), &mut ret).unwrap();
+		ret
+	};
+
+	let zstd = zstd::encode_all(plain.as_slice(), 0).unwrap();
+
+	let entries: &[(&str, u16, &[u8], usize)] = &[
+		("stored.bin", 0, &plain, plain.len()),
+		("deflate.bin", 8, &deflated, plain.len()),
+		("bzip2.bin", 12, &bzipped, plain.len()),
+		("zstd.bin", 93, &zstd, plain.len()),
+		("xz.bin", 95, &xz, plain.len()),
+		("ppmd.bin", 98, &[0x00; 8], 8),
+	];
+
+	let tmp = TempDir::new("stream");
+	let path = tmp.join("test.zip");
+	std::fs::write(&path, zip_archive(entries)).unwrap();
+	let mut vfs = VirtualFs::default();
+	vfs.mount(&path, VPath::new("zip")).unwrap();
+
+	for (name, method, _, _) in &entries[..(entries.len() - 1)] {
+		let vpath = VPathBuf::new(format!("/zip/{name}"));
+		let file = vfs.lookup(&vpath).unwrap().into_file().unwrap();
+		let mut stream = file.open();
+
+		let mut buf = vec![0; 100];
+		stream.read_exact(&mut buf).unwrap();
+		assert_eq!(buf, plain[..100], "{name}");
+
+		// Forwards...
+		stream.seek(SeekFrom::Start(30000)).unwrap();
+		stream.read_exact(&mut buf).unwrap();
+		assert_eq!(buf, plain[30000..30100], "{name}");
+
+		// ...backwards...
+		stream.seek(SeekFrom::Current(-20100)).unwrap();
+		stream.read_exact(&mut buf).unwrap();
+		assert_eq!(buf, plain[10000..10100], "{name}");
+
+		// ...and from the end.
+		assert_eq!(stream.seek(SeekFrom::End(-50)).unwrap(), 79950, "{name}");
+		let mut tail = vec![];
+		stream.read_to_end(&mut tail).unwrap();
+		assert_eq!(tail, plain[79950..], "{name}");
+
+		assert_eq!(stream.len().unwrap(), plain.len() as u64, "{name}");
+		stream.seek(SeekFrom::Start(1 << 20)).unwrap();
+		assert_eq!(stream.read(&mut buf).unwrap(), 0, "{name}");
+		assert!(stream.seek(SeekFrom::Current(-(1 << 21))).is_err());
+
+		stream.rewind().unwrap();
+		let mut all = vec![];
+		stream.read_to_end(&mut all).unwrap();
+		assert_eq!(all, plain, "{name} (method {method})");
+	}
+
+	let ppmd = vfs
+		.lookup(VPath::new("/zip/ppmd.bin"))
+		.unwrap()
+		.into_file()
+		.unwrap();
+	assert!(ppmd.open().read(&mut [0; 8]).is_err());
+}
+
+#[test]
+fn content_cache() {
+	let a = [b'a'; 100];
+	let b = [b'b'; 200];
+	let c = [b'c'; 300];
+	let big = [b'd'; 600];
+
+	let zip = zip_archive(&[
+		("a", 0, &a, a.len()),
+		("b", 0, &b, b.len()),
+		("c", 0, &c, c.len()),
+		("big", 0, &big, big.len()),
+	]);
+
+	let tmp = TempDir::new("cache");
+	let path = tmp.join("test.zip");
+	std::fs::write(&path, zip).unwrap();
+	let mut vfs = VirtualFs::default();
+	vfs.mount(&path, VPath::new("zip")).unwrap();
+
+	let file = |vfs: &VirtualFs, name: &str| {
+		vfs.lookup(&VPathBuf::new(format!("/zip/{name}")))
+			.unwrap()
+			.into_file()
+			.unwrap()
+			.slot()
+	};
+
+	let [a_slot, b_slot, c_slot, big_slot] = ["a", "b", "c", "big"].map(|n| file(&vfs, n));
+
+	let read = |vfs: &VirtualFs, slot: FileSlot| vfs.get_file(slot).unwrap().read_cached().unwrap();
+
+	// Disabled by default.
+	assert_eq!(read(&vfs, a_slot).as_ref(), a);
+	assert!(!vfs.cache().contains(a_slot));
+
+	vfs.cache().set_budget(500);
+	vfs.cache().reset_stats();
+	assert_eq!(read(&vfs, a_slot).as_ref(), a);
+	assert_eq!(read(&vfs, b_slot).as_ref(), b);
+	assert_eq!(read(&vfs, a_slot).as_ref(), a);
+	// `b` is the least recently used, so it makes room for `c`.
+	assert_eq!(read(&vfs, c_slot).as_ref(), c);
+	// Too big to be cached at all.
+	assert_eq!(read(&vfs, big_slot).as_ref(), big);
+
+	assert!(vfs.cache().contains(a_slot));
+	assert!(!vfs.cache().contains(b_slot));
+	assert!(vfs.cache().contains(c_slot));
+	assert!(!vfs.cache().contains(big_slot));
+
+	assert_eq!(
+		vfs.cache().stats(),
+		CacheStats {
+			hits: 1,
+			misses: 4,
+			evictions: 1,
+			entries: 2,
+			bytes: 400,
+		}
+	);
+
+	(0..64).into_par_iter().for_each(|i| {
+		let slot = if i % 2 == 0 { a_slot } else { c_slot };
+		let bytes = read(&vfs, slot);
+		assert_eq!(bytes.len(), if i % 2 == 0 { 100 } else { 300 });
+	});
+
+	assert_eq!(vfs.cache().stats().hits, 65);
+
+	// Changing or removing a file drops it from the cache.
+	vfs.mount_overlay(OverlayStore::Memory).unwrap();
+	vfs.write(VPath::new("/zip/a"), b"new").unwrap();
+	assert!(!vfs.cache().contains(a_slot));
+	assert_eq!(read(&vfs, a_slot).as_ref(), b"new");
+	assert!(vfs.cache().contains(a_slot));
+
+	vfs.remove_file_by_slot(c_slot);
+	assert!(!vfs.cache().contains(c_slot));
+	assert_eq!(vfs.cache().stats().bytes, 3);
+
+	vfs.cache().set_budget(0);
+	assert_eq!(vfs.cache().stats().entries, 0);
+	assert_eq!(vfs.cache().stats().bytes, 0);
+}
+
+#[test]
+fn nested_wad() {
+	let things = b"Thing data. ".repeat(8);
+	let linedefs = b"Linedef data. ".repeat(8);
+	let wad = wad_archive(&[("MAP01", &[]), ("THINGS", &things), ("LINEDEFS", &linedefs)]);
+
+	let deflated = {
+		use std::io::Write;
+
+		let mut enc = flate2::write::DeflateEncoder::new(vec![], flate2::Compression::best());
+		enc.write_all(&wad).unwrap();
+		enc.finish().unwrap()
+	};
+
+	let pk3 = zip_archive(&[
+		("readme.txt", 0, b"hello", 5),
+		("stored.wad", 0, &wad, wad.len()),
+		("maps/deflated.wad", 8, &deflated, wad.len()),
+	]);
+
+	let tmp = TempDir::new("nested");
+	let dir = tmp.join("mod");
+	std::fs::create_dir_all(&dir).unwrap();
+	std::fs::write(dir.join("loose.wad"), &wad).unwrap();
+	std::fs::write(dir.join("outer.pk3"), &pk3).unwrap();
+
+	let mut vfs = VirtualFs::default();
+	vfs.mount(&dir, VPath::new("dir")).unwrap();
+	vfs.mount(&dir.join("outer.pk3"), VPath::new("pk3"))
+		.unwrap();
+
+	// Directory mounts are named after the real directory, and archives
+	// in them get expanded along with any WADs nested within those.
+	let kinds = [
+		("/mod/loose.wad", FolderKind::Wad),
+		("/mod/outer.pk3", FolderKind::Zip),
+		("/mod/outer.pk3/stored.wad", FolderKind::Wad),
+		("/mod/outer.pk3/maps/deflated.wad", FolderKind::Wad),
+		("/pk3/stored.wad", FolderKind::Wad),
+		("/pk3/maps/deflated.wad", FolderKind::Wad),
+	];
+
+	for (vpath, kind) in kinds {
+		let folder = vfs
+			.lookup(VPath::new(vpath))
+			.unwrap()
+			.into_folder()
+			.unwrap();
+		assert_eq!(folder.kind(), kind, "{vpath}");
+	}
+
+	let expected: &[(&str, &[u8])] = &[
+		("/mod/loose.wad/LINEDEFS", &linedefs),
+		("/mod/loose.wad/THINGS", &things),
+		("/mod/outer.pk3/readme.txt", b"hello"),
+		("/mod/outer.pk3/stored.wad/THINGS", &things),
+		("/mod/outer.pk3/maps/deflated.wad/LINEDEFS", &linedefs),
+		("/pk3/readme.txt", b"hello"),
+		("/pk3/stored.wad/LINEDEFS", &linedefs),
+		("/pk3/stored.wad/THINGS", &things),
+		// Read back-to-front, forcing decompression to restart.
+		("/pk3/maps/deflated.wad/LINEDEFS", &linedefs),
+		("/pk3/maps/deflated.wad/THINGS", &things),
+		("/pk3/maps/deflated.wad/MAP01", &[]),
+	];
+
+	for (vpath, content) in expected {
+		let file = vfs.lookup(VPath::new(vpath)).unwrap().into_file().unwrap();
+		assert_eq!(file.lock().read().unwrap().as_ref(), *content, "{vpath}");
+	}
+
+	vfs.ingest_all();
+
+	for (vpath, content) in expected {
+		let file = vfs.lookup(VPath::new(vpath)).unwrap().into_file().unwrap();
+		assert_eq!(file.lock().read().unwrap().as_ref(), *content, "{vpath}");
+	}
+}
+
+#[test]
+fn lump_index() {
+	let iwad = wad_archive(&[
+		("PLAYPAL", b"iwad"),
+		("S_START", &[]),
+		("TROOA1", b"iwad sprite"),
+		("S_END", &[]),
+		("F_START", &[]),
+		("FLOOR0_1", b"iwad flat"),
+		("F_END", &[]),
+	]);
+
+	let nested = wad_archive(&[("PLAYPAL", b"nested"), ("COLORMAP", b"nested")]);
+
+	let pk3 = zip_archive(&[
+		("inner.wad", 0, &nested, nested.len()),
+		("playpal.lmp", 0, b"pk3", 3),
+		("sprites/monsters/trooa1.png", 0, b"pk3 sprite", 10),
+		("flats/floor0_1.png", 0, b"pk3 flat", 8),
+		("graphics/titlepic.png", 0, b"pk3 graphic", 11),
+		("filter/doom.doom2/playpal.pal", 0, b"doom2", 5),
+		("filter/heretic/playpal.pal", 0, b"heretic", 7),
+	]);
+
+	let tmp = TempDir::new("lumps");
+	let iwad_path = tmp.join("iwad.wad");
+	let pk3_path = tmp.join("mod.pk3");
+	std::fs::write(&iwad_path, &iwad).unwrap();
+	std::fs::write(&pk3_path, &pk3).unwrap();
+
+	let mut vfs = VirtualFs::default();
+	vfs.mount(&iwad_path, VPath::new("iwad")).unwrap();
+	vfs.mount(&pk3_path, VPath::new("pk3")).unwrap();
+
+	let content = |slot: FileSlot| -> Vec<u8> {
+		let file = vfs.get_file(slot).unwrap();
+		let ret = file.lock().read().unwrap().into_owned();
+		ret
+	};
+
+	let index = vfs.lump_index(&["doom", "doom.doom2"]);
+
+	let playpal = index.get(Namespace::Global, "playpal").unwrap();
+	assert_eq!(content(playpal.winner), b"nested");
+
+	assert_eq!(
+		playpal
+			.chain
+			.iter()
+			.map(|slot| content(*slot))
+			.collect::<Vec<_>>(),
+		[&b"iwad"[..], b"pk3", b"doom2", b"nested"]
+	);
+
+	assert_eq!(playpal.overridden().len(), 3);
+
+	let sprite = index.get(Namespace::Sprites, "TROOA1").unwrap();
+	assert_eq!(content(sprite.winner), b"pk3 sprite");
+	assert_eq!(sprite.chain.len(), 2);
+	assert!(index.get(Namespace::Global, "TROOA1").is_none());
+
+	let flat = index.get(Namespace::Flats, "FLOOR0_1").unwrap();
+	assert_eq!(content(flat.winner), b"pk3 flat");
+
+	assert!(index.get(Namespace::Global, "TITLEPIC").is_none());
+	assert!(index.get(Namespace::Global, "S_START").is_none());
+	assert!(index.get(Namespace::Sprites, "S_START").is_none());
+
+	let index = vfs.lump_index(&["heretic"]);
+	let playpal = index.get(Namespace::Global, "PLAYPAL").unwrap();
+	assert_eq!(content(playpal.chain[2]), b"heretic");
+}
+
+#[test]
+fn query() {
+	let wad = wad_archive(&[
+		("DECORATE", b"actor"),
+		("decorate", b"actor"),
+		("ZSCRIPT", b""),
+	]);
+
+	let pk3 = zip_archive(&[
+		("zscript.zs", 0, b"version", 7),
+		("zscript/actors/imp.zs", 0, b"class Imp", 9),
+		("zscript/actors/notes.txt", 0, b"", 0),
+		("decorate.txt", 0, b"actor Imp", 9),
+		("inner.wad", 0, &wad, wad.len()),
+	]);
+
+	let tmp = TempDir::new("query");
+	let path = tmp.join("mod.pk3");
+	std::fs::write(&path, &pk3).unwrap();
+	let mut vfs = VirtualFs::default();
+	vfs.mount(&path, VPath::new("pk3")).unwrap();
+
+	let paths = |query: &Query| {
+		let mut ret = query
+			.iter()
+			.map(|iref| iref.path().to_string())
+			.collect::<Vec<_>>();
+
+		let mut par = query
+			.par_iter()
+			.map(|iref| iref.path().to_string())
+			.collect::<Vec<_>>();
+
+		ret.sort();
+		par.sort();
+		assert_eq!(ret, par);
+		ret
+	};
+
+	let q = vfs.query().glob("*.ZS").unwrap();
+	assert_eq!(paths(&q), ["/pk3/zscript.zs", "/pk3/zscript/actors/imp.zs"]);
+
+	let q = vfs.query().glob("decorate*").unwrap();
+
+	assert_eq!(
+		paths(&q),
+		[
+			"/pk3/decorate.txt",
+			"/pk3/inner.wad/DECORATE",
+			"/pk3/inner.wad/decorate"
+		]
+	);
+
+	let q = vfs.query().glob("/pk3/*.zs").unwrap();
+	assert_eq!(paths(&q), ["/pk3/zscript.zs"]);
+
+	let q = vfs.query().glob("/pk3/zscript/**").unwrap();
+	assert_eq!(
+		paths(&q),
+		[
+			"/pk3/zscript/actors/imp.zs",
+			"/pk3/zscript/actors/notes.txt"
+		]
+	);
+
+	let q = vfs
+		.query()
+		.glob("zscript*")
+		.unwrap()
+		.filter(|iref| !iref.is_empty());
+	assert_eq!(paths(&q), ["/pk3/zscript.zs"]);
+
+	let q = vfs.query().filter(|iref| iref.size() >= 7);
+	assert_eq!(paths(&q).len(), 3);
+
+	let actors = vfs
+		.lookup(VPath::new("/pk3/zscript"))
+		.unwrap()
+		.into_folder()
+		.unwrap();
+	let q = actors.query().glob("/pk3/zscript/actors/*").unwrap();
+	assert_eq!(paths(&q).len(), 2);
+
+	// Lazy iteration in depth-first order.
+	let q = vfs.query();
+	let mut iter = q.iter();
+	assert_eq!(iter.next().unwrap().path().as_str(), "/pk3/zscript.zs");
+	assert_eq!(iter.next().unwrap().path().as_str(), "/pk3/decorate.txt");
+
+	assert!(matches!(vfs.query().glob("[a-"), Err(Error::Glob(_))));
+}
+
+#[test]
+fn child_index() {
+	#[track_caller]
+	fn assert_consistent(vfs: &VirtualFs) {
+		for (oslot, folder) in &vfs.folders {
+			let mut expected = HashMap::<SmallString, Vec<Slot>>::new();
+
+			for sfslot in folder.subfolders.iter().copied() {
+				assert_eq!(vfs.folders[sfslot].parent, Some(oslot));
+				let key = fold_name(&vfs.folders[sfslot].name);
+				expected.entry(key).or_default().push(sfslot.into());
+			}
+
+			for islot in folder.files.iter().copied() {
+				assert_eq!(vfs.files[islot].parent, oslot);
+				let key = fold_name(&vfs.files[islot].name);
+				expected.entry(key).or_default().push(islot.into());
+			}
+
+			assert_eq!(folder.index.len(), expected.len());
+
+			for (key, mut slots) in expected {
+				let mut actual = folder.index[&key].clone();
+				slots.sort_by_key(|slot| format!("{slot:?}"));
+				actual.sort_by_key(|slot| format!("{slot:?}"));
+				assert_eq!(actual, slots, "{key}");
+			}
+		}
+	}
+
+	let wad = wad_archive(&[("THINGS", b"1"), ("things", b"2"), ("Sectors", b"")]);
+
+	let pk3 = zip_archive(&[
+		("maps/map01.wad", 0, &wad, wad.len()),
+		("maps/README", 0, b"", 0),
+		("readme", 0, b"text", 4),
+		("README/notes.txt", 0, b"", 0),
+		("Sprites/a.lmp", 0, b"", 0),
+		("sprites/b.lmp", 0, b"", 0),
+	]);
+
+	let tmp = TempDir::new("index");
+	let pk3_path = tmp.join("mod.pk3");
+	let wad_path = tmp.join("mod.wad");
+	std::fs::write(&pk3_path, &pk3).unwrap();
+	std::fs::write(&wad_path, &wad).unwrap();
+
+	let mut vfs = VirtualFs::default();
+	vfs.mount(&pk3_path, VPath::new("pk3")).unwrap();
+	vfs.mount(&wad_path, VPath::new("wad")).unwrap();
+	assert_consistent(&vfs);
+
+	// The earliest-added of several same-named files wins...
+	let things = vfs
+		.lookup(VPath::new("/WAD/things"))
+		.unwrap()
+		.into_file()
+		.unwrap();
+	assert_eq!(things.lock().read().unwrap().as_ref(), b"1");
+	// ...but subfolders take priority over files.
+	assert!(vfs.lookup(VPath::new("/pk3/readme")).unwrap().is_folder());
+	assert!(vfs
+		.lookup(VPath::new("/pk3/MAPS/MAP01.WAD/sectors"))
+		.is_some());
+	assert!(vfs.lookup(VPath::new("/pk3/maps/readme")).is_some());
+	assert!(vfs.lookup(VPath::new("/pk3/nonexistent")).is_none());
+	// Entries under differently-cased folders share one folder.
+	let sprites = vfs
+		.lookup(VPath::new("/pk3/SPRITES"))
+		.unwrap()
+		.into_folder()
+		.unwrap();
+	assert_eq!(sprites.child_count(), 2);
+
+	vfs.normalize_names();
+	assert_consistent(&vfs);
+	assert!(vfs.lookup(VPath::new("/pk3/MAPS/README")).is_some());
+
+	let things = vfs.lookup(VPath::new("/wad/things")).unwrap().slot();
+	let Slot::File(things) = things else {
+		unreachable!()
+	};
+
+	assert!(vfs.remove_file_by_slot(things));
+	assert_consistent(&vfs);
+	let things = vfs
+		.lookup(VPath::new("/wad/THINGS"))
+		.unwrap()
+		.into_file()
+		.unwrap();
+	assert_eq!(things.lock().read().unwrap().as_ref(), b"2");
+
+	let maps = vfs
+		.lookup(VPath::new("/pk3/maps"))
+		.unwrap()
+		.into_folder()
+		.unwrap()
+		.slot();
+
+	let folder_count = vfs.folder_count();
+	vfs.remove_folder_by_slot(maps);
+	assert_consistent(&vfs);
+	assert!(vfs.lookup(VPath::new("/pk3/maps")).is_none());
+	// `maps` and the WAD nested within it.
+	assert_eq!(vfs.folder_count(), folder_count - 2);
+
+	vfs.retain(|mntinfo| mntinfo.format != MountFormat::Wad)
+		.unwrap();
+	assert_consistent(&vfs);
+	assert!(vfs.lookup(VPath::new("/wad")).is_none());
+	assert!(vfs.lookup(VPath::new("/pk3/readme/notes.txt")).is_some());
+
+	vfs.clear();
+	assert_consistent(&vfs);
+	assert!(vfs.lookup(VPath::new("/pk3")).is_none());
+	assert_eq!(vfs.total_count(), 1);
+}
+
+#[test]
+fn sevenz() {
+	use sevenz_rust::{SeqReader, SevenZArchiveEntry, SevenZWriter, SourceReader};
+
+	let lorem = b"Lorem ipsum dolor sit amet. ".repeat(32);
+	let ipsum = b"Consectetur adipiscing elit. ".repeat(16);
+	let dolor = b"Sed do eiusmod tempor. ".repeat(8);
+	let wad = wad_archive(&[("MAP01", &[]), ("THINGS", &lorem)]);
+
+	let entry = |name: &str| {
+		let mut ret = SevenZArchiveEntry::new();
+		ret.name = name.to_string();
+		// `push_archive_entries` does not set this itself.
+		ret.has_stream = true;
+		ret
+	};
+
+	let mut writer = SevenZWriter::new(std::io::Cursor::new(vec![])).unwrap();
+
+	let mut dir = entry("maps");
+	dir.is_directory = true;
+	writer.push_archive_entry::<&[u8]>(dir, None).unwrap();
+
+	writer
+		.push_archive_entry(entry("loose.txt"), Some(lorem.as_slice()))
+		.unwrap();
+
+	writer
+		.push_archive_entry::<&[u8]>(entry("empty.txt"), None)
+		.unwrap();
+
+	writer
+		.push_archive_entry(entry("maps/map01.wad"), Some(wad.as_slice()))
+		.unwrap();
+
+	writer
+		.push_archive_entries(
+			vec![
+				entry("solid/lorem.txt"),
+				entry("solid/ipsum.txt"),
+				entry("solid/dolor.txt"),
+			],
+			SeqReader::new(vec![
+				SourceReader::new(lorem.as_slice()),
+				SourceReader::new(ipsum.as_slice()),
+				SourceReader::new(dolor.as_slice()),
+			]),
+		)
+		.unwrap();
+
+	let bytes = writer.finish().unwrap().into_inner();
+
+	let tmp = TempDir::new("7z");
+	let path = tmp.join("mod.pk7");
+	std::fs::write(&path, &bytes).unwrap();
+	let mut vfs = VirtualFs::default();
+	vfs.mount(&path, VPath::new("pk7")).unwrap();
+
+	assert_eq!(vfs.mounts()[0].format, MountFormat::SevenZip);
+
+	let root = vfs
+		.lookup(VPath::new("/pk7"))
+		.unwrap()
+		.into_folder()
+		.unwrap();
+	assert_eq!(root.kind(), FolderKind::SevenZip);
+
+	let maps = vfs
+		.lookup(VPath::new("/pk7/maps"))
+		.unwrap()
+		.into_folder()
+		.unwrap();
+	assert_eq!(maps.kind(), FolderKind::SevenZipDir);
+
+	let map01 = vfs
+		.lookup(VPath::new("/pk7/maps/map01.wad"))
+		.unwrap()
+		.into_folder()
+		.unwrap();
+	assert_eq!(map01.kind(), FolderKind::Wad);
+
+	let expected: &[(&str, &[u8])] = &[
+		("/pk7/maps/map01.wad/THINGS", &lorem),
+		("/pk7/maps/map01.wad/MAP01", &[]),
+		// Read out of order to ensure the whole block gets decompressed.
+		("/pk7/solid/dolor.txt", &dolor),
+		("/pk7/solid/lorem.txt", &lorem),
+		("/pk7/solid/ipsum.txt", &ipsum),
+		("/pk7/loose.txt", &lorem),
+		("/pk7/empty.txt", &[]),
+	];
+
+	for (vpath, content) in expected {
+		let file = vfs.lookup(VPath::new(vpath)).unwrap().into_file().unwrap();
+		assert_eq!(file.lock().read().unwrap().as_ref(), *content, "{vpath}");
+	}
+
+	vfs.ingest_all();
+
+	let file = vfs
+		.lookup(VPath::new("/pk7/solid/ipsum.txt"))
+		.unwrap()
+		.into_file()
+		.unwrap();
+	assert_eq!(file.lock().read().unwrap().as_ref(), ipsum.as_slice());
+}
+
+#[test]
+fn pak_grp() {
+	let pak = pak_archive(&[
+		("progs.dat", b"progs"),
+		("maps/e1m1.bsp", b"bsp"),
+		("sound/misc/null.wav", b""),
+	]);
+	let grp = grp_archive(&[("GAME.CON", b"define"), ("TILES000.ART", b"art")]);
+
+	let tmp = TempDir::new("legacy");
+	let pak_path = tmp.join("legacy.pak");
+	let grp_path = tmp.join("legacy.grp");
+	std::fs::write(&pak_path, &pak).unwrap();
+	std::fs::write(&grp_path, &grp).unwrap();
+
+	let mut vfs = VirtualFs::default();
+	vfs.mount(&pak_path, VPath::new("pak")).unwrap();
+	vfs.mount(&grp_path, VPath::new("grp")).unwrap();
+
+	// Truncated directories are rejected without leaving anything behind.
+	let bad_pak_path = tmp.join("bad.pak");
+	let bad_grp_path = tmp.join("bad.grp");
+	std::fs::write(&bad_pak_path, &pak[..(pak.len() - 1)]).unwrap();
+	std::fs::write(&bad_grp_path, &grp[..20]).unwrap();
+	let bad_pak = vfs.mount(&bad_pak_path, VPath::new("bad_pak"));
+	let bad_grp = vfs.mount(&bad_grp_path, VPath::new("bad_grp"));
+	assert!(matches!(bad_pak, Err(Error::Pak(_))));
+	assert!(matches!(bad_grp, Err(Error::Grp(_))));
+	assert_eq!(vfs.mounts().len(), 2);
+	assert_eq!(vfs.root().subfolders().count(), 2);
+
+	assert_eq!(vfs.mounts()[0].format, MountFormat::Pak);
+	assert_eq!(vfs.mounts()[1].format, MountFormat::Grp);
+
+	let kinds = [
+		("/pak", FolderKind::Pak),
+		("/pak/sound/misc", FolderKind::PakDir),
+		("/grp", FolderKind::Grp),
+	];
+
+	for (vpath, kind) in kinds {
+		let folder = vfs
+			.lookup(VPath::new(vpath))
+			.unwrap()
+			.into_folder()
+			.unwrap();
+		assert_eq!(folder.kind(), kind, "{vpath}");
+	}
+
+	let expected: &[(&str, &[u8])] = &[
+		("/pak/progs.dat", b"progs"),
+		("/pak/maps/e1m1.bsp", b"bsp"),
+		("/pak/sound/misc/null.wav", b""),
+		("/grp/GAME.CON", b"define"),
+		("/grp/TILES000.ART", b"art"),
+	];
+
+	for (vpath, content) in expected {
+		let file = vfs.lookup(VPath::new(vpath)).unwrap().into_file().unwrap();
+		assert_eq!(file.size(), content.len(), "{vpath}");
+
+		if !content.is_empty() {
+			assert_eq!(file.lock().read().unwrap().as_ref(), *content, "{vpath}");
+		}
+	}
+}
+
+#[test]
+fn content_hash() {
+	let patch = b"Patch data. ".repeat(16);
+
+	let deflated = {
+		use std::io::Write;
+
+		let mut enc = flate2::write::DeflateEncoder::new(vec![], flate2::Compression::best());
+		enc.write_all(&patch).unwrap();
+		enc.finish().unwrap()
+	};
+
+	let wad1 = wad_archive(&[("P_START", &[]), ("PATCH1", &patch), ("P_END", &[])]);
+	let wad2 = wad_archive(&[("P_START", &[]), ("PATCH2", &patch), ("P_END", &[])]);
+	let pk3 = zip_archive(&[
+		("patches/patch3.lmp", 8, &deflated, patch.len()),
+		("abc.txt", 0, b"abc", 3),
+	]);
+
+	let tmp = TempDir::new("hash");
+	let paths = [
+		(tmp.join("1.wad"), wad1),
+		(tmp.join("2.wad"), wad2),
+		(tmp.join("3.pk3"), pk3),
+	];
+
+	let mut vfs = VirtualFs::default();
+
+	for (i, (path, bytes)) in paths.iter().enumerate() {
+		std::fs::write(path, bytes).unwrap();
+		vfs.mount(path, VPath::new(&format!("mnt{i}"))).unwrap();
+	}
+
+	let file = |vfs: &VirtualFs, vpath: &str| {
+		vfs.lookup(VPath::new(vpath))
+			.unwrap()
+			.into_file()
+			.unwrap()
+			.slot()
+	};
+
+	let patch1 = file(&vfs, "/mnt0/PATCH1");
+	let patch2 = file(&vfs, "/mnt1/PATCH2");
+	let patch3 = file(&vfs, "/mnt2/patches/patch3.lmp");
+	let abc = file(&vfs, "/mnt2/abc.txt");
+
+	let duplicates = vfs.duplicates().unwrap();
+	assert_eq!(duplicates.len(), 1);
+	assert_eq!(duplicates[0].files, [patch1, patch2, patch3]);
+	assert_eq!(
+		vfs.get_file(patch3).unwrap().hash().unwrap(),
+		duplicates[0].hash
+	);
+
+	assert_eq!(
+		vfs.get_file(abc).unwrap().hash().unwrap().to_string(),
+		"a9993e364706816aba3e25717850c26c9cd0d89d"
+	);
+
+	let marker = vfs
+		.lookup(VPath::new("/mnt0/P_START"))
+		.unwrap()
+		.into_file()
+		.unwrap();
+	assert_eq!(marker.hash().unwrap(), ContentHash::EMPTY);
+
+	// Hashes are forgotten when content changes.
+	vfs.mount_overlay(OverlayStore::Memory).unwrap();
+	vfs.write(VPath::new("/mnt1/PATCH2"), b"abc").unwrap();
+	let duplicates = vfs.duplicates().unwrap();
+	assert_eq!(duplicates.len(), 2);
+	assert_eq!(duplicates[0].files, [patch1, patch3]);
+	assert_eq!(duplicates[1].files, [patch2, abc]);
+}
+
+#[test]
+fn mount_warnings() {
+	let mut pk3 = zip_archive(&[
+		("dir/", 0, &[], 0),
+		("dir/a.txt", 0, b"a", 1),
+		("DIR/b.txt", 0, b"b", 1),
+		("../escape.txt", 0, b"escape", 6),
+		("lossy@.txt", 0, b"lossy", 5),
+		("method.txt", 99, b"?", 1),
+	]);
+
+	for i in 0..(pk3.len() - 10) {
+		if &pk3[i..(i + 10)] == b"lossy@.txt" {
+			pk3[i + 5] = 0xFF;
+		}
+	}
+
+	let tmp = TempDir::new("warnings");
+	let dir = tmp.join("mod");
+	std::fs::create_dir_all(dir.join("sub")).unwrap();
+	std::fs::write(tmp.join("mod.pk3"), &pk3).unwrap();
+	std::fs::write(dir.join("readme.txt"), b"").unwrap();
+	std::fs::write(dir.join("sub/readme.txt"), b"").unwrap();
+	std::fs::write(dir.join("packing.txt"), b"PACKING LIST").unwrap();
+
+	#[cfg(unix)]
+	std::os::unix::fs::symlink(dir.join("nowhere"), dir.join("broken")).unwrap();
+
+	let mut vfs = VirtualFs::default();
+	vfs.mount(&tmp.join("mod.pk3"), VPath::new("pk3")).unwrap();
+	vfs.mount(&dir, VPath::new("dir")).unwrap();
+	vfs.mount(&dir.join("readme.txt"), VPath::new("file1"))
+		.unwrap();
+	vfs.mount(&dir.join("sub/readme.txt"), VPath::new("file2"))
+		.unwrap();
+
+	let lossy = "lossy\u{FFFD}.txt".to_string();
+
+	let expected = [
+		MountWarning::ComponentsDropped {
+			path: "../escape.txt".to_string(),
+		},
+		MountWarning::LossyName {
+			path: lossy.clone(),
+		},
+		MountWarning::UnsupportedCompression {
+			path: "method.txt".to_string(),
+			method: 99,
+		},
+	];
+	assert_eq!(vfs.mounts()[0].warnings, expected);
+
+	// Directory entries do not become files, and
+	// differently-cased folders are merged.
+	let pk3_dir = vfs.lookup(VPath::new("/pk3/dir")).unwrap();
+	assert!(pk3_dir.is_folder());
+	assert_eq!(pk3_dir.into_folder().unwrap().files().count(), 2);
+	assert!(vfs.exists(VPath::new("/pk3/escape.txt")));
+	assert!(vfs.exists(VPath::new(&format!("/pk3/{lossy}"))));
+	assert!(vfs.exists(VPath::new("/pk3/method.txt")));
+
+	// A file which only looks like an archive gets added as-is.
+	assert!(vfs
+		.lookup(VPath::new("/mod/packing.txt"))
+		.unwrap()
+		.is_file());
+
+	let skipped = |name: &str| {
+		vfs.mounts()[1].warnings.iter().any(
+			|warning| matches!(warning, MountWarning::Skipped { path, .. } if path.ends_with(name)),
+		)
+	};
+
+	assert!(skipped("packing.txt"));
+	#[cfg(unix)]
+	assert!(skipped("broken"));
+	assert_eq!(
+		vfs.mounts()[1].warnings.len(),
+		if cfg!(unix) { 2 } else { 1 }
+	);
+
+	// A single-file mount named like an earlier one.
+	assert!(vfs.mounts()[2].warnings.is_empty());
+	assert_eq!(
+		vfs.mounts()[3].warnings,
+		[MountWarning::DuplicateName {
+			folder: VPathBuf::from("/"),
+			name: "readme.txt".to_string(),
+		}]
+	);
+}
+
+#[test]
+fn overlay() {
+	#[track_caller]
+	fn read(vfs: &VirtualFs, vpath: &str) -> Vec<u8> {
+		let file = vfs.lookup(VPath::new(vpath)).unwrap().into_file().unwrap();
+		let bytes = file.lock().read().unwrap().into_owned();
+		bytes
+	}
+
+	let pk3 = zip_archive(&[("decorate.txt", 0, b"lower", 5), ("maps/readme", 0, b"", 0)]);
+
+	let tmp = TempDir::new("overlay");
+	let pk3_path = tmp.join("mod.pk3");
+	std::fs::write(&pk3_path, &pk3).unwrap();
+
+	let mut vfs = VirtualFs::default();
+	vfs.mount(&pk3_path, VPath::new("pk3")).unwrap();
+
+	assert!(matches!(
+		vfs.write(VPath::new("/pk3/decorate.txt"), b"upper"),
+		Err(Error::NoOverlay)
+	));
+
+	vfs.mount_overlay(OverlayStore::Memory).unwrap();
+
+	let lower = vfs.lookup(VPath::new("/pk3/decorate.txt")).unwrap().slot();
+	let upper = vfs
+		.write(VPath::new("/pk3/DECORATE.txt"), b"upper")
+		.unwrap();
+	assert_eq!(lower, Slot::File(upper));
+	assert_eq!(read(&vfs, "/pk3/decorate.txt"), b"upper");
+
+	vfs.write(VPath::new("/saves/slot0/info.json"), b"{}")
+		.unwrap();
+	assert_eq!(read(&vfs, "/saves/slot0/info.json"), b"{}");
+	let saves = vfs
+		.lookup(VPath::new("/saves"))
+		.unwrap()
+		.into_folder()
+		.unwrap();
+	assert_eq!(saves.kind(), FolderKind::Overlay);
+
+	let shots = vfs.create_folder(VPath::new("/screenshots")).unwrap();
+	assert_eq!(
+		vfs.create_folder(VPath::new("/screenshots")).unwrap(),
+		shots
+	);
+
+	for bad in ["/pk3/maps", "/pk3/decorate.txt/x", "/pk3/../x", "/"] {
+		assert!(
+			matches!(vfs.write(VPath::new(bad), b""), Err(Error::OverlayPath)),
+			"{bad}"
+		);
+	}
+
+	vfs.delete(VPath::new("/pk3/maps")).unwrap();
+	assert!(vfs.lookup(VPath::new("/pk3/maps/readme")).is_none());
+	assert!(matches!(
+		vfs.delete(VPath::new("/pk3/maps")),
+		Err(Error::NotFound)
+	));
+	assert!(matches!(
+		vfs.delete(VPath::new("/")),
+		Err(Error::OverlayPath)
+	));
+	assert!(matches!(
+		vfs.delete(VPath::new("/pk3")),
+		Err(Error::OverlayPath)
+	));
+
+	// Remounting what the overlay changed keeps the changes.
+	vfs.remount(VPath::new("/pk3")).unwrap();
+	assert_eq!(read(&vfs, "/pk3/decorate.txt"), b"upper");
+	assert!(vfs.lookup(VPath::new("/pk3/maps")).is_none());
+
+	// Unmounting the overlay reveals what it shadowed.
+	assert_eq!(vfs.unmount_overlay().unwrap(), Some(OverlayStore::Memory));
+	assert_eq!(read(&vfs, "/pk3/decorate.txt"), b"lower");
+	assert!(vfs.lookup(VPath::new("/pk3/maps/readme")).is_some());
+	assert!(vfs.lookup(VPath::new("/saves")).is_none());
+	assert!(vfs.lookup(VPath::new("/screenshots")).is_none());
+	assert_eq!(vfs.unmount_overlay().unwrap(), None);
+
+	// Changes to a directory overlay persist and get re-applied.
+	let dir = tmp.join("overlay");
+	vfs.mount_overlay(OverlayStore::Directory(dir.clone()))
+		.unwrap();
+	vfs.write(VPath::new("/pk3/decorate.txt"), b"on disk")
+		.unwrap();
+	vfs.write(VPath::new("/pk3/gldefs.txt"), b"new").unwrap();
+	vfs.write(VPath::new("/config/doomrc"), b"old").unwrap();
+	vfs.write(VPath::new("/config/doomrc"), b"rc").unwrap();
+	vfs.create_folder(VPath::new("/nodes")).unwrap();
+	vfs.delete(VPath::new("/pk3/gldefs.txt")).unwrap();
+	vfs.delete(VPath::new("/pk3/maps")).unwrap();
+	assert_eq!(read(&vfs, "/config/doomrc"), b"rc");
+	assert_eq!(
+		std::fs::read(dir.join("pk3/decorate.txt")).unwrap(),
+		b"on disk"
+	);
+	assert!(!dir.join("pk3/gldefs.txt").exists());
+	assert!(dir.join("nodes").is_dir());
+	assert!(dir.join("pk3/.wh.maps").is_file());
+	assert!(matches!(
+		vfs.write(VPath::new("/pk3/.wh.decorate.txt"), b""),
+		Err(Error::OverlayPath)
+	));
+
+	let mut vfs = VirtualFs::default();
+	vfs.mount(&pk3_path, VPath::new("pk3")).unwrap();
+	vfs.mount_overlay(OverlayStore::Directory(dir.clone()))
+		.unwrap();
+	assert_eq!(read(&vfs, "/pk3/decorate.txt"), b"on disk");
+	assert_eq!(read(&vfs, "/config/doomrc"), b"rc");
+	assert!(vfs.lookup(VPath::new("/nodes")).unwrap().is_folder());
+	assert!(vfs.lookup(VPath::new("/pk3/gldefs.txt")).is_none());
+	assert!(vfs.lookup(VPath::new("/pk3/maps")).is_none());
+	assert!(vfs.lookup(VPath::new("/pk3/.wh.maps")).is_none());
+
+	// A deleted folder which gets created again replaces what it shadowed.
+	vfs.write(VPath::new("/pk3/maps/map01.txt"), b"map")
+		.unwrap();
+	let mut vfs = VirtualFs::default();
+	vfs.mount(&pk3_path, VPath::new("pk3")).unwrap();
+	vfs.mount_overlay(OverlayStore::Directory(dir.clone()))
+		.unwrap();
+	assert_eq!(read(&vfs, "/pk3/maps/map01.txt"), b"map");
+	assert!(vfs.lookup(VPath::new("/pk3/maps/readme")).is_none());
+}
+
+#[test]
+fn remount() {
+	let tmp = TempDir::new("remount");
+	let pk3_path = tmp.join("mod.pk3");
+	let wad_path = tmp.join("mod.wad");
+	let txt_path = tmp.join("mod.txt");
+	std::fs::write(&pk3_path, zip_archive(&[("a.txt", 0, b"a", 1)])).unwrap();
+	std::fs::write(&wad_path, wad_archive(&[("OLD", b"1")])).unwrap();
+	std::fs::write(&txt_path, b"text").unwrap();
+
+	let mut vfs = VirtualFs::default();
+	vfs.mount(&pk3_path, VPath::new("pk3")).unwrap();
+	vfs.mount(&wad_path, VPath::new("wad")).unwrap();
+	vfs.mount(&txt_path, VPath::new("txt")).unwrap();
+
+	let wad_bytes = wad_archive(&[("NEW", b"2"), ("NEWER", b"3")]);
+	std::fs::write(&wad_path, &wad_bytes).unwrap();
+	let a = vfs.lookup(VPath::new("/pk3/a.txt")).unwrap().slot();
+	vfs.remount(VPath::new("WAD")).unwrap();
+
+	// Slots under other mounts are untouched.
+	assert_eq!(vfs.lookup(VPath::new("/pk3/a.txt")).unwrap().slot(), a);
+	assert!(vfs.lookup(VPath::new("/wad/OLD")).is_none());
+	assert!(vfs.lookup(VPath::new("/wad/NEWER")).is_some());
+
+	let mpoints = vfs
+		.mounts()
+		.iter()
+		.map(|mntinfo| mntinfo.mount_point.as_str())
+		.collect::<Vec<_>>();
+	assert_eq!(mpoints, ["/pk3", "/wad", "/txt"]);
+
+	let subfolders = vfs
+		.root()
+		.subfolders()
+		.map(|oref| oref.name().as_str().to_owned())
+		.collect::<Vec<_>>();
+	assert_eq!(subfolders, ["pk3", "wad"]);
+	assert_eq!(
+		vfs.mounts()[1].root,
+		vfs.lookup(VPath::new("/wad")).unwrap().slot()
+	);
+	// The old root does not count as sharing the new one's name.
+	assert!(vfs.mounts()[1].warnings.is_empty());
+
+	// A failed remount leaves the old mount in place.
+	let file_count = vfs.file_count();
+	std::fs::write(&wad_path, b"PWAD").unwrap();
+	let result = vfs.remount(VPath::new("/wad"));
+	std::fs::write(&wad_path, &wad_bytes).unwrap();
+	assert!(result.is_err());
+	assert_eq!(vfs.file_count(), file_count);
+	assert!(vfs.lookup(VPath::new("/wad/NEW")).is_some());
+	assert_eq!(vfs.root().subfolders().count(), 2);
+
+	assert!(matches!(
+		vfs.remount(VPath::new("/nonexistent")),
+		Err(Error::NotFound)
+	));
+
+	let txt = vfs.unmount(VPath::new("/txt")).unwrap();
+	assert_eq!(txt.format, MountFormat::Uncompressed);
+	assert!(vfs.unmount(VPath::new("/txt")).is_none());
+	assert_eq!(vfs.mounts().len(), 2);
+	assert_eq!(vfs.root().file_count(), 0);
+
+	let Slot::Folder(pk3) = vfs.mounts()[0].root else {
+		unreachable!()
+	};
+
+	vfs.remove_folder_by_slot(pk3);
+	assert_eq!(vfs.mounts().len(), 1);
+	assert_eq!(vfs.mounts()[0].mount_point.as_str(), "/wad");
+}
+
+#[test]
+fn refresh() {
+	#[track_caller]
+	fn read(vfs: &VirtualFs, vpath: &str) -> Vec<u8> {
+		let file = vfs.lookup(VPath::new(vpath)).unwrap().into_file().unwrap();
+		let bytes = file.lock().read().unwrap().into_owned();
+		bytes
+	}
+
+	#[track_caller]
+	fn file_slot(vfs: &VirtualFs, vpath: &str) -> FileSlot {
+		vfs.lookup(VPath::new(vpath))
+			.unwrap()
+			.into_file()
+			.unwrap()
+			.slot()
+	}
+
+	let tmp = TempDir::new("refresh");
+	let dir = tmp.join("mod");
+	std::fs::create_dir_all(dir.join("sub")).unwrap();
+	std::fs::write(dir.join("a.txt"), b"a").unwrap();
+	std::fs::write(dir.join("sub/b.txt"), b"b").unwrap();
+	std::fs::write(dir.join("maps.wad"), wad_archive(&[("MAP01", b"")])).unwrap();
+	let loose = tmp.join("mod.txt");
+	std::fs::write(&loose, b"loose").unwrap();
+
+	let mut vfs = VirtualFs::default();
+	vfs.mount(&dir, VPath::new("dir")).unwrap();
+	vfs.mount(&loose, VPath::new("loose")).unwrap();
+
+	let root = "/mod";
+	let loose_vpath = "/mod.txt";
+	let mut changes = vec![];
+
+	let a = file_slot(&vfs, &format!("{root}/a.txt"));
+	let b = file_slot(&vfs, &format!("{root}/sub/b.txt"));
+	let map01 = file_slot(&vfs, &format!("{root}/maps.wad/MAP01"));
+	let loose_slot = file_slot(&vfs, loose_vpath);
+
+	std::fs::write(dir.join("a.txt"), b"modified").unwrap();
+	vfs.refresh(&dir.join("a.txt"), &mut changes).unwrap();
+	assert_eq!(changes, [FileChange::Modified(a)]);
+	assert_eq!(read(&vfs, &format!("{root}/a.txt")), b"modified");
+	changes.clear();
+
+	std::fs::create_dir_all(dir.join("sub/new/deeper")).unwrap();
+	std::fs::write(dir.join("sub/new/deeper/c.txt"), b"c").unwrap();
+	vfs.refresh(&dir.join("sub/new/deeper/c.txt"), &mut changes)
+		.unwrap();
+	let c = file_slot(&vfs, &format!("{root}/sub/new/deeper/c.txt"));
+	assert_eq!(changes, [FileChange::Created(c)]);
+	changes.clear();
+
+	std::fs::remove_file(dir.join("sub/b.txt")).unwrap();
+	vfs.refresh(&dir.join("sub/b.txt"), &mut changes).unwrap();
+	assert_eq!(changes, [FileChange::Removed(b)]);
+	assert!(!vfs.file_exists(b));
+	changes.clear();
+
+	std::fs::write(dir.join("maps.wad"), wad_archive(&[("MAP02", b"")])).unwrap();
+	vfs.refresh(&dir.join("maps.wad"), &mut changes).unwrap();
+	let map02 = file_slot(&vfs, &format!("{root}/maps.wad/MAP02"));
+	assert_eq!(
+		changes,
+		[FileChange::Removed(map01), FileChange::Created(map02)]
+	);
+	changes.clear();
+
+	// Refreshing a directory catches everything under it.
+	std::fs::remove_dir_all(dir.join("sub")).unwrap();
+	std::fs::write(dir.join("d.txt"), b"d").unwrap();
+	vfs.refresh(&dir, &mut changes).unwrap();
+	let d = file_slot(&vfs, &format!("{root}/d.txt"));
+	assert_eq!(changes.len(), 2);
+	assert!(changes.contains(&FileChange::Removed(c)));
+	assert!(changes.contains(&FileChange::Created(d)));
+	assert!(vfs.lookup(VPath::new(&format!("{root}/sub"))).is_none());
+	changes.clear();
+
+	vfs.refresh(&tmp.join("nonexistent"), &mut changes).unwrap();
+	assert!(changes.is_empty());
+
+	// An archive which fails to mount leaves no folder behind,
+	// and gets added as a plain file instead.
+	let folder_count = vfs.folder_count();
+	std::fs::write(
+		dir.join("partial.wad"),
+		&wad_archive(&[("MAP03", b"")])[..14],
+	)
+	.unwrap();
+	vfs.refresh(&dir.join("partial.wad"), &mut changes).unwrap();
+	let partial = vfs
+		.lookup(VPath::new(&format!("{root}/partial.wad")))
+		.unwrap()
+		.into_file()
+		.unwrap();
+	assert_eq!(changes, [FileChange::Created(partial.slot())]);
+	assert_eq!(vfs.folder_count(), folder_count);
+	changes.clear();
+	std::fs::remove_file(dir.join("partial.wad")).unwrap();
+	vfs.refresh(&dir.join("partial.wad"), &mut changes).unwrap();
+	changes.clear();
+
+	std::fs::write(&loose, b"changed").unwrap();
+	vfs.refresh(&loose, &mut changes).unwrap();
+	assert_eq!(changes, [FileChange::Modified(loose_slot)]);
+	assert_eq!(read(&vfs, loose_vpath), b"changed");
+	changes.clear();
+
+	std::fs::remove_file(&loose).unwrap();
+	vfs.refresh(&loose, &mut changes).unwrap();
+	assert_eq!(changes, [FileChange::Removed(loose_slot)]);
+	assert_eq!(vfs.mounts().len(), 1);
+}
+
+#[test]
+fn refresh_overlay() {
+	#[track_caller]
+	fn read(vfs: &VirtualFs, vpath: &str) -> Vec<u8> {
+		let file = vfs.lookup(VPath::new(vpath)).unwrap().into_file().unwrap();
+		let bytes = file.lock().read().unwrap().into_owned();
+		bytes
+	}
+
+	let tmp = TempDir::new("refresh-overlay");
+	let dir = tmp.join("mod");
+	std::fs::create_dir_all(dir.join("sub")).unwrap();
+	std::fs::write(dir.join("a.txt"), b"a").unwrap();
+	std::fs::write(dir.join("b.txt"), b"b").unwrap();
+	std::fs::write(dir.join("sub/c.txt"), b"c").unwrap();
+
+	let mut vfs = VirtualFs::default();
+	vfs.mount(&dir, VPath::new("dir")).unwrap();
+	vfs.mount_overlay(OverlayStore::Memory).unwrap();
+
+	let c = vfs
+		.lookup(VPath::new("/mod/sub/c.txt"))
+		.unwrap()
+		.into_file()
+		.unwrap()
+		.slot();
+	vfs.write(VPath::new("/mod/a.txt"), b"upper").unwrap();
+	vfs.write(VPath::new("/mod/new.txt"), b"new").unwrap();
+	vfs.write(VPath::new("/mod/made/x.txt"), b"x").unwrap();
+	let y = vfs.write(VPath::new("/mod/sub/y.txt"), b"y").unwrap();
+	vfs.delete(VPath::new("/mod/b.txt")).unwrap();
+
+	// Changes on disk do not override changes made through the overlay.
+	std::fs::write(dir.join("a.txt"), b"changed").unwrap();
+	std::fs::write(dir.join("b.txt"), b"changed").unwrap();
+	let mut changes = vec![];
+	vfs.refresh(&dir, &mut changes).unwrap();
+	vfs.refresh(&dir.join("a.txt"), &mut changes).unwrap();
+	vfs.refresh(&dir.join("b.txt"), &mut changes).unwrap();
+	assert!(changes.is_empty());
+
+	assert_eq!(read(&vfs, "/mod/a.txt"), b"upper");
+	assert_eq!(read(&vfs, "/mod/new.txt"), b"new");
+	assert_eq!(read(&vfs, "/mod/made/x.txt"), b"x");
+	assert_eq!(read(&vfs, "/mod/sub/c.txt"), b"c");
+	assert!(!vfs.exists(VPath::new("/mod/b.txt")));
+
+	// Whatever the overlay wrote under a removed directory is put back.
+	std::fs::remove_dir_all(dir.join("sub")).unwrap();
+	vfs.refresh(&dir, &mut changes).unwrap();
+	let new_y = vfs
+		.lookup(VPath::new("/mod/sub/y.txt"))
+		.unwrap()
+		.into_file()
+		.unwrap()
+		.slot();
+	assert_eq!(changes.len(), 3);
+	assert!(changes.contains(&FileChange::Removed(c)));
+	assert!(changes.contains(&FileChange::Removed(y)));
+	assert!(changes.contains(&FileChange::Created(new_y)));
+	assert_eq!(read(&vfs, "/mod/sub/y.txt"), b"y");
+	assert!(!vfs.exists(VPath::new("/mod/sub/c.txt")));
+}
+
+#[cfg(feature = "watch")]
+#[test]
+fn watcher() {
+	let tmp = TempDir::new("watch");
+	let dir = tmp.join("mod");
+	std::fs::create_dir_all(&dir).unwrap();
+	std::fs::write(dir.join("a.txt"), b"a").unwrap();
+
+	let mut vfs = VirtualFs::default();
+	vfs.mount(&dir, VPath::new("dir")).unwrap();
+
+	let mut watcher = Watcher::new().unwrap();
+	watcher.watch(&vfs, VPath::new("dir")).unwrap();
+	std::fs::write(dir.join("b.txt"), b"b").unwrap();
+
+	let vpath = "/mod/b.txt";
+	let mut changes = vec![];
+
+	for _ in 0..100 {
+		watcher.apply(&mut vfs, &mut changes).unwrap();
+
+		if vfs.exists(VPath::new(vpath)) {
+			break;
+		}
+
+		std::thread::sleep(std::time::Duration::from_millis(50));
+	}
+
+	let b = vfs.lookup(VPath::new(vpath)).unwrap().into_file().unwrap();
+	assert!(changes.contains(&FileChange::Created(b.slot())));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn manifest() {
+	let wad = wad_archive(&[
+		("MAP01", &[]),
+		("THINGS", b"things"),
+		("LINEDEFS", b"linedefs"),
+	]);
+
+	let deflated = {
+		use std::io::Write;
+
+		let mut enc = flate2::write::DeflateEncoder::new(vec![], flate2::Compression::best());
+		enc.write_all(b"deflated text ".repeat(8).as_slice())
+			.unwrap();
+		enc.finish().unwrap()
+	};
+
+	let pk3 = zip_archive(&[
+		("stored.wad", 0, &wad, wad.len()),
+		("sub/deflated.txt", 8, &deflated, 14 * 8),
+	]);
+
+	let tmp = TempDir::new("manifest");
+	let pk3_path = tmp.join("mod.pk3");
+	let wad_path = tmp.join("mod.wad");
+	let txt_path = tmp.join("mod.txt");
+	std::fs::write(&pk3_path, pk3).unwrap();
+	std::fs::write(&wad_path, &wad).unwrap();
+	std::fs::write(&txt_path, b"text").unwrap();
+
+	let mut vfs = VirtualFs::default();
+	vfs.mount(&pk3_path, VPath::new("pk3")).unwrap();
+	vfs.mount(&wad_path, VPath::new("wad")).unwrap();
+	vfs.mount(&txt_path, VPath::new("txt")).unwrap();
+
+	let json = serde_json::to_string(&vfs.manifest()).unwrap();
+	let manifest: Manifest = serde_json::from_str(&json).unwrap();
+	assert_eq!(manifest.len(), 3);
+
+	let mut restored = VirtualFs::default();
+	let count = restored.restore(&manifest);
+	let txt_name = txt_path.file_name().unwrap().to_string_lossy().into_owned();
+
+	let mut vpaths = vec![
+		"/pk3/stored.wad/THINGS".to_string(),
+		"/pk3/stored.wad/LINEDEFS".to_string(),
+		"/pk3/sub/deflated.txt".to_string(),
+		"/wad/THINGS".to_string(),
+		"/wad/MAP01".to_string(),
+		format!("/{txt_name}"),
+	];
+
+	let assert_same = |vfs: &VirtualFs, restored: &VirtualFs, vpaths: &[String]| {
+		assert_eq!(restored.file_count(), vfs.file_count());
+		assert_eq!(restored.folder_count(), vfs.folder_count());
+		assert_eq!(restored.mounts().len(), vfs.mounts().len());
+
+		for vpath in vpaths {
+			let expected = vfs.lookup(VPath::new(vpath)).unwrap().into_file().unwrap();
+			let file = restored
+				.lookup(VPath::new(vpath))
+				.unwrap()
+				.into_file()
+				.unwrap();
+			assert_eq!(file.size(), expected.size(), "{vpath}");
+
+			assert_eq!(
+				file.lock().read().unwrap(),
+				expected.lock().read().unwrap(),
+				"{vpath}"
+			);
+		}
+	};
+
+	assert_eq!(count.unwrap(), 3);
+	assert_same(&vfs, &restored, &vpaths);
+	assert!(matches!(
+		restored.restore(&manifest),
+		Err(Error::MountPointDuplicate)
+	));
+
+	// Changed sources get mounted anew.
+	let wad = wad_archive(&[("THINGS", b"new things")]);
+	std::fs::write(&wad_path, &wad).unwrap();
+	vfs.remount(VPath::new("wad")).unwrap();
+	vpaths.retain(|vpath| vpath != "/wad/MAP01");
+
+	let mut restored = VirtualFs::default();
+	let count = restored.restore(&manifest);
+	assert_eq!(count.unwrap(), 2);
+	assert_same(&vfs, &restored, &vpaths);
+
+	// So do mounts the overlay has changed, which means its deletions do not stick.
+	vfs.mount_overlay(OverlayStore::Memory).unwrap();
+	vfs.delete(VPath::new("/pk3/sub/deflated.txt")).unwrap();
+	let manifest = vfs.manifest();
+
+	let mut restored = VirtualFs::default();
+	let count = restored.restore(&manifest);
+	assert_eq!(count.unwrap(), 2);
+	assert!(restored.exists(VPath::new("/pk3/sub/deflated.txt")));
+}
+
+/// A directory under [`std::env::temp_dir`] for one test's real files,
+/// removed along with its contents when dropped (including on panic).
+#[derive(Debug)]
+struct TempDir(PathBuf);
+
+impl TempDir {
+	#[must_use]
+	fn new(test: &str) -> Self {
+		let path = std::env::temp_dir().join(format!("viletech-fs-{}-{test}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&path);
+		std::fs::create_dir_all(&path).unwrap();
+		Self(path)
+	}
+}
+
+impl std::ops::Deref for TempDir {
+	type Target = Path;
+
+	fn deref(&self) -> &Self::Target {
+		&self.0
+	}
+}
+
+impl Drop for TempDir {
+	fn drop(&mut self) {
+		let _ = std::fs::remove_dir_all(&self.0);
+	}
+}
+
+/// Each entry is a name, compression method, (compressed) data, and uncompressed length.
+#[must_use]
+fn zip_archive(entries: &[(&str, u16, &[u8], usize)]) -> Vec<u8> {
+	let mut zip = vec![];
+	let mut cdir = vec![];
+
+	for (name, method, data, unpacked_len) in entries {
+		let offs = zip.len() as u32;
+		let mut fields = vec![];
+		fields.extend(20_u16.to_le_bytes()); // Version needed to extract.
+		fields.extend(0_u16.to_le_bytes()); // Flags.
+		fields.extend(method.to_le_bytes());
+		fields.extend([0; 8]); // Modification time and date; CRC-32.
+		fields.extend((data.len() as u32).to_le_bytes());
+		fields.extend((*unpacked_len as u32).to_le_bytes());
+		fields.extend((name.len() as u16).to_le_bytes());
+		fields.extend(0_u16.to_le_bytes()); // Extra field length.
+
+		zip.extend(0x04034B50_u32.to_le_bytes());
+		zip.extend(&fields);
+		zip.extend(name.as_bytes());
+		zip.extend(*data);
+
+		cdir.extend(0x02014B50_u32.to_le_bytes());
+		cdir.extend(20_u16.to_le_bytes()); // Version made by.
+		cdir.extend(&fields);
+		cdir.extend([0; 10]); // Comment length through external attributes.
+		cdir.extend(offs.to_le_bytes());
+		cdir.extend(name.as_bytes());
+	}
+
+	let cdir_offs = zip.len() as u32;
+	zip.extend(&cdir);
+	zip.extend(0x06054B50_u32.to_le_bytes());
+	zip.extend([0; 4]); // Disk numbers.
+	zip.extend((entries.len() as u16).to_le_bytes());
+	zip.extend((entries.len() as u16).to_le_bytes());
+	zip.extend((cdir.len() as u32).to_le_bytes());
+	zip.extend(cdir_offs.to_le_bytes());
+	zip.extend(0_u16.to_le_bytes()); // Comment length.
+	zip
+}
+
+#[must_use]
+fn wad_archive(lumps: &[(&str, &[u8])]) -> Vec<u8> {
+	let cursor = std::io::Cursor::new(vec![]);
+	let mut writer = wadload::Writer::new(cursor, wadload::WadKind::PWad).unwrap();
+
+	for (name, data) in lumps {
+		writer.write_lump(name, data).unwrap();
+	}
+
+	writer.finish().unwrap().into_inner()
+}
+
+#[must_use]
+fn pak_archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+	let data_len = entries.iter().map(|(_, data)| data.len()).sum::<usize>();
+	let mut pak = Vec::from(*b"PACK");
+	pak.extend(((12 + data_len) as u32).to_le_bytes());
+	pak.extend(((entries.len() * 64) as u32).to_le_bytes());
+	let mut dir = vec![];
+
+	for (name, data) in entries {
+		let mut name56 = [0; 56];
+		name56[..name.len()].copy_from_slice(name.as_bytes());
+		dir.extend(name56);
+		dir.extend((pak.len() as u32).to_le_bytes());
+		dir.extend((data.len() as u32).to_le_bytes());
+		pak.extend(*data);
+	}
+
+	pak.extend(dir);
+	pak
+}
+
+#[must_use]
+fn grp_archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+	let mut grp = Vec::from(*b"KenSilverman");
+	grp.extend((entries.len() as u32).to_le_bytes());
+
+	for (name, data) in entries {
+		let mut name12 = [0; 12];
+		name12[..name.len()].copy_from_slice(name.as_bytes());
+		grp.extend(name12);
+		grp.extend((data.len() as u32).to_le_bytes());
+	}
+
+	for (_, data) in entries {
+		grp.extend(*data);
+	}
+
+	grp
+}
+
 #[must_use]
 fn sample_vfs() -> Option<VirtualFs> {
 	let mut vfs = VirtualFs::default();
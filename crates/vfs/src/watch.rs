@@ -0,0 +1,506 @@
+//! [`VirtualFs::refresh`], [`Watcher`], and their related symbols.
+
+use std::{
+	path::{Path, PathBuf},
+	sync::Arc,
+	time::SystemTime,
+};
+
+use parking_lot::Mutex;
+
+use crate::{
+	detail::{Compression, Reader},
+	mount, Error, FileSlot, FolderKind, FolderSlot, MountFormat, Slot, VirtualFs,
+};
+
+/// A change to the VFS tree made by [`VirtualFs::refresh`] or [`Watcher::apply`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileChange {
+	Created(FileSlot),
+	/// The file's slot stays valid, but its content may be different.
+	Modified(FileSlot),
+	/// The file's slot is no longer valid.
+	Removed(FileSlot),
+}
+
+impl FileChange {
+	#[must_use]
+	pub fn slot(&self) -> FileSlot {
+		match self {
+			Self::Created(islot) | Self::Modified(islot) | Self::Removed(islot) => *islot,
+		}
+	}
+}
+
+impl VirtualFs {
+	/// Brings the part of the tree corresponding to the real file or directory
+	/// at `real_path` up-to-date with the physical file system, pushing every
+	/// file created, modified, or removed as a result to `changes`. A file counts
+	/// as modified if its length or modification time have changed.
+	///
+	/// `real_path` does not need to exist any longer. If it is a directory, its
+	/// entire subtree is refreshed. Only [`MountFormat::Directory`] and
+	/// [`MountFormat::Uncompressed`] mounts are affected; if `real_path` is not
+	/// under one, this does nothing. Deleting the real path of a mount unmounts it.
+	///
+	/// A WAD in a mounted directory is expanded into a folder, so changing it
+	/// removes every one of its files and creates a new file for each of its lumps.
+	/// Whatever was written, created, or deleted through the overlay stays that way.
+	/// [`crate::MountInfo::warnings`] are not updated.
+	pub fn refresh(
+		&mut self,
+		real_path: &Path,
+		changes: &mut Vec<FileChange>,
+	) -> Result<(), Error> {
+		let real_path = canonicalize_lossy(real_path);
+
+		let target = self
+			.mounts
+			.iter()
+			.find_map(|mntinfo| match (mntinfo.format, mntinfo.root) {
+				(MountFormat::Uncompressed, Slot::File(_)) if mntinfo.real_path == real_path => {
+					Some((mntinfo.root, PathBuf::new()))
+				}
+				(MountFormat::Directory, Slot::Folder(_)) => real_path
+					.strip_prefix(&mntinfo.real_path)
+					.ok()
+					.map(|relative| (mntinfo.root, relative.to_path_buf())),
+				_ => None,
+			});
+
+		match target {
+			Some((Slot::File(islot), _)) => self.refresh_mount_file(islot, &real_path, changes),
+			Some((Slot::Folder(oslot), relative)) => {
+				if relative.as_os_str().is_empty() {
+					self.refresh_mount_dir(oslot, &real_path, changes)
+				} else {
+					self.refresh_under(oslot, &real_path, &relative, changes)
+				}
+			}
+			None => Ok(()),
+		}
+	}
+
+	fn refresh_mount_file(
+		&mut self,
+		islot: FileSlot,
+		real: &Path,
+		changes: &mut Vec<FileChange>,
+	) -> Result<(), Error> {
+		if real.is_file() {
+			let name = self.files[islot].name.clone();
+
+			if !self.overlay_shadows(self.root, &name) && self.reopen_file(islot, real)? {
+				changes.push(FileChange::Modified(islot));
+			}
+		} else {
+			self.remove_file_by_slot(islot);
+			changes.push(FileChange::Removed(islot));
+		}
+
+		Ok(())
+	}
+
+	fn refresh_mount_dir(
+		&mut self,
+		oslot: FolderSlot,
+		real: &Path,
+		changes: &mut Vec<FileChange>,
+	) -> Result<(), Error> {
+		if real.is_dir() {
+			self.refresh_dir(oslot, real, changes)
+		} else {
+			collect_removed(self, Slot::Folder(oslot), changes);
+			self.remove_folder_by_slot(oslot);
+			Ok(())
+		}
+	}
+
+	/// `relative` is the non-empty path of `real` relative to the directory
+	/// represented by `root`.
+	fn refresh_under(
+		&mut self,
+		root: FolderSlot,
+		real: &Path,
+		relative: &Path,
+		changes: &mut Vec<FileChange>,
+	) -> Result<(), Error> {
+		let mut parent = root;
+		let mut target = real.to_path_buf();
+		let ancestors = relative.components().count() - 1;
+
+		for (i, comp) in relative.components().take(ancestors).enumerate() {
+			let name = comp.as_os_str().to_string_lossy();
+
+			match self.exact_child(parent, &name) {
+				Some(Slot::Folder(sfslot))
+					if self.folders[sfslot].kind == FolderKind::Directory =>
+				{
+					parent = sfslot;
+				}
+				_ => {
+					// This ancestor is new, or it used to be something else;
+					// it needs refreshing as a whole.
+					target = real.to_path_buf();
+
+					for _ in 0..(ancestors - i) {
+						target.pop();
+					}
+
+					break;
+				}
+			}
+		}
+
+		self.refresh_entry(parent, &target, changes)
+	}
+
+	/// Syncs the child of `parent` with the same name as the real file or directory at `real`.
+	/// Whatever the overlay wrote or deleted there is left as-is.
+	fn refresh_entry(
+		&mut self,
+		parent: FolderSlot,
+		real: &Path,
+		changes: &mut Vec<FileChange>,
+	) -> Result<(), Error> {
+		let Some(name) = real.file_name() else {
+			return Ok(());
+		};
+
+		let name = name.to_string_lossy();
+
+		if self.overlay_shadows(parent, &name) {
+			return Ok(());
+		}
+
+		let existing = self.exact_child(parent, &name);
+		let archive = real.is_file() && mount::is_archive(self, real)?;
+
+		match existing {
+			Some(Slot::Folder(sfslot))
+				if real.is_dir() && self.folders[sfslot].kind == FolderKind::Directory =>
+			{
+				return self.refresh_dir(sfslot, real, changes);
+			}
+			Some(Slot::Folder(sfslot))
+				if !real.exists() && self.folders[sfslot].kind == FolderKind::Overlay =>
+			{
+				return Ok(());
+			}
+			Some(Slot::File(islot)) if real.is_file() && !archive => {
+				if self.reopen_file(islot, real)? {
+					changes.push(FileChange::Modified(islot));
+				}
+
+				return Ok(());
+			}
+			Some(Slot::Folder(sfslot))
+				if self.folders[sfslot].kind != FolderKind::Directory
+					&& self.stamps.get(&Slot::Folder(sfslot)) == Stamp::of_path(real).as_ref() =>
+			{
+				return Ok(());
+			}
+			Some(slot) => {
+				collect_removed(self, slot, changes);
+
+				match slot {
+					Slot::File(islot) => {
+						self.remove_file_by_slot(islot);
+					}
+					Slot::Folder(sfslot) => self.remove_folder_by_slot(sfslot),
+				}
+			}
+			None => {}
+		}
+
+		let created = if real.is_dir() {
+			Some(Slot::Folder(mount::mount_dir(
+				self,
+				real,
+				&name,
+				parent,
+				&mut vec![],
+			)?))
+		} else if real.is_file() {
+			Some(mount::mount_dir_file(self, real, parent, &mut vec![])?)
+		} else {
+			None
+		};
+
+		self.restore_overlay(parent, &name, created, changes);
+		Ok(())
+	}
+
+	/// After the entry named `name` in `parent` gets replaced with `created` (or removed,
+	/// if `created` is `None`), puts back whatever the overlay changed under it.
+	fn restore_overlay(
+		&mut self,
+		parent: FolderSlot,
+		name: &str,
+		created: Option<Slot>,
+		changes: &mut Vec<FileChange>,
+	) {
+		let restored = self.reapply_overlay_at(parent, name);
+
+		let created = match created {
+			Some(slot) => Some(slot),
+			None if restored => self.folders[parent].find_child(name),
+			None => None,
+		};
+
+		if let Some(slot) = created {
+			collect_created(self, slot, changes);
+		}
+	}
+
+	/// Syncs every child of `oslot` with the content of the real directory at `real`.
+	fn refresh_dir(
+		&mut self,
+		oslot: FolderSlot,
+		real: &Path,
+		changes: &mut Vec<FileChange>,
+	) -> Result<(), Error> {
+		let mut on_disk = vec![];
+
+		for result in std::fs::read_dir(real).map_err(Error::DirRead)? {
+			on_disk.push(result.map_err(Error::DirRead)?.path());
+		}
+
+		let folder = &self.folders[oslot];
+
+		// Anything the overlay created has nothing on disk, but is not stale.
+		let stale = folder
+			.subfolders
+			.iter()
+			.filter(|sfslot| self.folders[**sfslot].kind != FolderKind::Overlay)
+			.map(|sfslot| Slot::Folder(*sfslot))
+			.chain(folder.files.iter().map(|islot| Slot::File(*islot)))
+			.filter_map(|slot| {
+				let name = match slot {
+					Slot::File(islot) => self.files[islot].name.clone(),
+					Slot::Folder(sfslot) => self.folders[sfslot].name.clone(),
+				};
+
+				let on_disk = on_disk.iter().any(|path| {
+					path.file_name()
+						.is_some_and(|n| n.to_string_lossy() == name.as_str())
+				});
+
+				(!on_disk && !self.overlay_shadows(oslot, &name)).then_some((slot, name))
+			})
+			.collect::<Vec<_>>();
+
+		for (slot, name) in stale {
+			collect_removed(self, slot, changes);
+
+			match slot {
+				Slot::File(islot) => {
+					self.remove_file_by_slot(islot);
+				}
+				Slot::Folder(sfslot) => self.remove_folder_by_slot(sfslot),
+			}
+
+			self.restore_overlay(oslot, &name, None, changes);
+		}
+
+		for path in on_disk {
+			self.refresh_entry(oslot, &path, changes)?;
+		}
+
+		Ok(())
+	}
+
+	/// Returns `false` without doing anything if the
+	/// real file has not changed since it was last read.
+	fn reopen_file(&mut self, islot: FileSlot, real: &Path) -> Result<bool, Error> {
+		let fh = std::fs::File::open(real).map_err(Error::FileOpen)?;
+		let stamp = Stamp::of(&fh.metadata().map_err(Error::Metadata)?);
+
+		if self.stamps.get(&Slot::File(islot)) == Some(&stamp) {
+			return Ok(false);
+		}
+
+		let vfile = &mut self.files[islot];
+		vfile.reader = Arc::new(Mutex::new(Reader::File(fh)));
+		vfile.span = 0..(stamp.len as u32);
+		vfile.compression = Compression::None;
+		self.stamps.insert(Slot::File(islot), stamp);
+		self.forget_content(islot);
+		Ok(true)
+	}
+
+	/// Real file systems are case-sensitive (as far as this is concerned),
+	/// so unlike `VFolder::find_child` this only matches names exactly.
+	#[must_use]
+	fn exact_child(&self, parent: FolderSlot, name: &str) -> Option<Slot> {
+		self.folders[parent]
+			.children_named(name)
+			.iter()
+			.copied()
+			.find(|slot| match slot {
+				Slot::File(islot) => self.files[*islot].name == name,
+				Slot::Folder(sfslot) => self.folders[*sfslot].name == name,
+			})
+	}
+}
+
+/// Used to tell if a real file has changed since it was last read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Stamp {
+	len: u64,
+	modified: Option<SystemTime>,
+}
+
+impl Stamp {
+	#[must_use]
+	pub(crate) fn of(metadata: &std::fs::Metadata) -> Self {
+		Self {
+			len: metadata.len(),
+			modified: metadata.modified().ok(),
+		}
+	}
+
+	#[must_use]
+	fn of_path(real: &Path) -> Option<Self> {
+		std::fs::metadata(real).ok().map(|m| Self::of(&m))
+	}
+}
+
+/// Pushes [`FileChange::Removed`] for every file at or under `slot`.
+fn collect_removed(vfs: &VirtualFs, slot: Slot, changes: &mut Vec<FileChange>) {
+	collect_files(vfs, slot, &mut |islot| {
+		changes.push(FileChange::Removed(islot))
+	});
+}
+
+/// Pushes [`FileChange::Created`] for every file at or under `slot`.
+fn collect_created(vfs: &VirtualFs, slot: Slot, changes: &mut Vec<FileChange>) {
+	collect_files(vfs, slot, &mut |islot| {
+		changes.push(FileChange::Created(islot))
+	});
+}
+
+fn collect_files(vfs: &VirtualFs, slot: Slot, callback: &mut dyn FnMut(FileSlot)) {
+	match slot {
+		Slot::File(islot) => callback(islot),
+		Slot::Folder(oslot) => {
+			let folder = &vfs.folders[oslot];
+
+			for islot in folder.files.iter().copied() {
+				callback(islot);
+			}
+
+			for sfslot in folder.subfolders.iter().copied() {
+				collect_files(vfs, Slot::Folder(sfslot), callback);
+			}
+		}
+	}
+}
+
+/// Canonicalizes as much of `path` as still exists, since a deleted
+/// file or directory can not be canonicalized itself.
+#[must_use]
+fn canonicalize_lossy(path: &Path) -> PathBuf {
+	if let Ok(canon) = path.canonicalize() {
+		return canon;
+	}
+
+	match (path.parent(), path.file_name()) {
+		(Some(parent), Some(name)) => canonicalize_lossy(parent).join(name),
+		_ => path.to_path_buf(),
+	}
+}
+
+/// Watches mounted directories and single files for changes, so that they can be
+/// applied to a VFS via [`Self::apply`]. Requires the `watch` feature.
+///
+/// Only [`MountFormat::Directory`] and [`MountFormat::Uncompressed`] mounts can be watched.
+#[cfg(feature = "watch")]
+#[derive(Debug)]
+pub struct Watcher {
+	inner: notify::RecommendedWatcher,
+	events: std::sync::mpsc::Receiver<notify::Result<notify::Event>>,
+	/// Real paths which have changed but have not yet been refreshed.
+	pending: indexmap::IndexSet<PathBuf>,
+}
+
+#[cfg(feature = "watch")]
+impl Watcher {
+	pub fn new() -> Result<Self, Error> {
+		let (sender, events) = std::sync::mpsc::channel();
+		let inner = notify::recommended_watcher(sender).map_err(Error::Watch)?;
+
+		Ok(Self {
+			inner,
+			events,
+			pending: indexmap::IndexSet::new(),
+		})
+	}
+
+	/// Starts watching the real path of the mount at `mount_point`.
+	/// See [`VirtualFs::unmount`] regarding how `mount_point` is matched.
+	pub fn watch(&mut self, vfs: &VirtualFs, mount_point: &crate::VPath) -> Result<(), Error> {
+		use notify::{RecursiveMode, Watcher as _};
+
+		let i = vfs.mount_index(mount_point).ok_or(Error::NotFound)?;
+		let mntinfo = &vfs.mounts[i];
+
+		match mntinfo.format {
+			MountFormat::Directory => self
+				.inner
+				.watch(&mntinfo.real_path, RecursiveMode::Recursive)
+				.map_err(Error::Watch),
+			// Watch the parent directory, since many editors save
+			// by replacing a file rather than writing to it.
+			MountFormat::Uncompressed => self
+				.inner
+				.watch(
+					mntinfo.real_path.parent().unwrap_or(&mntinfo.real_path),
+					RecursiveMode::NonRecursive,
+				)
+				.map_err(Error::Watch),
+			other => Err(Error::WatchFormat(other)),
+		}
+	}
+
+	/// Applies every change observed since the last call to the tree of `vfs`,
+	/// pushing what happened to it to `changes`. Never blocks.
+	///
+	/// A real path which fails to [refresh](VirtualFs::refresh) does not stop
+	/// the others from being applied; it gets dropped, and the first such error
+	/// is returned after everything else has been applied. A path which gets
+	/// dropped is applied again once another change to it is observed.
+	pub fn apply(
+		&mut self,
+		vfs: &mut VirtualFs,
+		changes: &mut Vec<FileChange>,
+	) -> Result<(), Error> {
+		let mut ret = Ok(());
+
+		while let Ok(result) = self.events.try_recv() {
+			match result {
+				Ok(event) => {
+					if !event.kind.is_access() {
+						self.pending.extend(event.paths);
+					}
+				}
+				Err(err) => {
+					if ret.is_ok() {
+						ret = Err(Error::Watch(err));
+					}
+				}
+			}
+		}
+
+		for path in std::mem::take(&mut self.pending) {
+			if let Err(err) = vfs.refresh(&path, changes) {
+				if ret.is_ok() {
+					ret = Err(err);
+				}
+			}
+		}
+
+		ret
+	}
+}
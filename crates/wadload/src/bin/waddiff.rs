@@ -0,0 +1,86 @@
+//! Reports lump-level differences between two WADs,
+//! or merges patch WADs into a base WAD.
+//!
+//! ```text
+//! waddiff <old.wad> <new.wad>
+//! waddiff --merge <out.wad> <base.wad> <patch.wad>...
+//! ```
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+    process::ExitCode,
+};
+
+use wadload::Wad;
+
+const USAGE: &str = "usage:
+    waddiff <old.wad> <new.wad>
+    waddiff --merge <out.wad> <base.wad> <patch.wad>...";
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    let result = match args.as_slice() {
+        [flag, out, base, patches @ ..] if flag == "--merge" && !patches.is_empty() => {
+            merge(Path::new(out), Path::new(base), patches)
+        }
+        [old, new] if !old.starts_with("--") => diff(Path::new(old), Path::new(new)),
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match result {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Exits with 0 if the WADs have no differences, and 1 otherwise, like `diff`.
+fn diff(old: &Path, new: &Path) -> Result<ExitCode, String> {
+    let old_wad = read(old)?;
+    let new_wad = read(new)?;
+
+    if old_wad.wad_kind() != new_wad.wad_kind() {
+        println!("! {:?} -> {:?}", old_wad.wad_kind(), new_wad.wad_kind());
+    }
+
+    let diff = wadload::diff(&old_wad, &new_wad);
+    print!("{diff}");
+
+    if diff.is_empty() && old_wad.wad_kind() == new_wad.wad_kind() {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::from(1))
+    }
+}
+
+fn merge(out: &Path, base: &Path, patches: &[String]) -> Result<ExitCode, String> {
+    let base = read(base)?;
+
+    let patches = patches
+        .iter()
+        .map(|p| read(Path::new(p)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let merged = wadload::merge(&base, &patches);
+
+    let fh = File::create(out).map_err(|err| format!("{}: {err}", out.display()))?;
+
+    merged
+        .write(BufWriter::new(fh))
+        .map_err(|err| format!("{}: {err}", out.display()))?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn read(path: &Path) -> Result<Wad, String> {
+    let fh = File::open(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    Wad::read(BufReader::new(fh)).map_err(|err| format!("{}: {err}", path.display()))
+}
@@ -0,0 +1,335 @@
+//! [`classify`] and its related symbols.
+
+use std::ops::Range;
+
+/// Walks a WAD directory's lump names (in directory order) and determines the
+/// [`Namespace`] of every entry, as well as which entries make up maps.
+///
+/// Marker lumps are tagged with the namespace they delimit. Sub-markers like
+/// `F1_START` and `P2_END` are treated as belonging to their parent namespace,
+/// and a namespace being re-opened inside itself (e.g. `SS_START` directly
+/// after `S_START`, as written by DeuTex) is accepted silently; any other
+/// nesting or imbalance is reported via [`Classification::warnings`].
+///
+/// ```
+/// let names = ["MAP01", "THINGS", "LINEDEFS", "S_START", "TROOA1", "S_END"];
+/// let classified = wadload::classify(names);
+/// assert_eq!(classified.namespaces[4], wadload::Namespace::Sprites);
+/// assert_eq!(classified.maps[0].components, 1..3);
+/// ```
+#[must_use]
+pub fn classify<I, S>(names: I) -> Classification
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let names = names.into_iter().collect::<Vec<_>>();
+    let mut namespaces = Vec::with_capacity(names.len());
+    let mut warnings = vec![];
+    let mut stack: Vec<(Namespace, usize)> = vec![];
+
+    for (i, name) in names.iter().enumerate() {
+        let name = name.as_ref();
+
+        if let Some(ns) = marker(name, "_START") {
+            if let Some((outer, _)) = stack.last().filter(|(outer, _)| *outer != ns) {
+                warnings.push(ClassifyWarning::NestedMarker {
+                    index: i,
+                    namespace: ns,
+                    outer: *outer,
+                });
+            }
+
+            stack.push((ns, i));
+            namespaces.push(ns);
+            continue;
+        }
+
+        if let Some(ns) = marker(name, "_END") {
+            match stack.iter().rposition(|(open, _)| *open == ns) {
+                Some(pos) => {
+                    for (unclosed, start) in stack.drain((pos + 1)..) {
+                        warnings.push(ClassifyWarning::UnclosedStart {
+                            index: start,
+                            namespace: unclosed,
+                        });
+                    }
+
+                    stack.pop();
+                }
+                None => warnings.push(ClassifyWarning::UnopenedEnd {
+                    index: i,
+                    namespace: ns,
+                }),
+            }
+
+            namespaces.push(ns);
+            continue;
+        }
+
+        namespaces.push(stack.last().map_or(Namespace::Global, |(ns, _)| *ns));
+    }
+
+    for (unclosed, start) in stack {
+        warnings.push(ClassifyWarning::UnclosedStart {
+            index: start,
+            namespace: unclosed,
+        });
+    }
+
+    let maps = find_maps(&names, &mut warnings);
+
+    Classification {
+        namespaces,
+        maps,
+        warnings,
+    }
+}
+
+/// The output of [`classify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Classification {
+    /// Parallel to the names passed to [`classify`].
+    pub namespaces: Vec<Namespace>,
+    /// In the order the map headers appear in the directory.
+    pub maps: Vec<MapGroup>,
+    pub warnings: Vec<ClassifyWarning>,
+}
+
+/// A group of lumps delimited by a pair of marker lumps, which determines how
+/// a source port interprets them (a flat and a patch can share a name, for
+/// example). Lumps outside of any markers are [`Namespace::Global`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Namespace {
+    Global,
+    /// `A_START`/`A_END`. ZDoom's auto-loaded ACS libraries.
+    Acs,
+    /// `C_START`/`C_END`. Boom's extra colormaps.
+    Colormaps,
+    /// `F_START`/`F_END` or `FF_START`/`FF_END`.
+    Flats,
+    /// `HI_START`/`HI_END`. ZDoom's high-resolution texture replacements.
+    HiRes,
+    /// `P_START`/`P_END` or `PP_START`/`PP_END`.
+    Patches,
+    /// `S_START`/`S_END` or `SS_START`/`SS_END`.
+    Sprites,
+    /// `TX_START`/`TX_END`. ZDoom's standalone textures.
+    Textures,
+    /// `V_START`/`V_END`. Strife's voice lines.
+    Voices,
+    /// `VX_START`/`VX_END`. ZDoom's voxel models.
+    Voxels,
+}
+
+impl std::fmt::Display for Namespace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Global => write!(f, "global"),
+            Self::Acs => write!(f, "ACS libraries"),
+            Self::Colormaps => write!(f, "colormaps"),
+            Self::Flats => write!(f, "flats"),
+            Self::HiRes => write!(f, "high-resolution textures"),
+            Self::Patches => write!(f, "patches"),
+            Self::Sprites => write!(f, "sprites"),
+            Self::Textures => write!(f, "textures"),
+            Self::Voices => write!(f, "voices"),
+            Self::Voxels => write!(f, "voxels"),
+        }
+    }
+}
+
+impl Namespace {
+    /// The start and end marker names used by [`crate::merge`] when creating
+    /// a namespace. Returns `None` for [`Namespace::Global`].
+    #[must_use]
+    pub fn markers(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Self::Global => None,
+            Self::Acs => Some(("A_START", "A_END")),
+            Self::Colormaps => Some(("C_START", "C_END")),
+            Self::Flats => Some(("FF_START", "FF_END")),
+            Self::HiRes => Some(("HI_START", "HI_END")),
+            Self::Patches => Some(("PP_START", "PP_END")),
+            Self::Sprites => Some(("SS_START", "SS_END")),
+            Self::Textures => Some(("TX_START", "TX_END")),
+            Self::Voices => Some(("V_START", "V_END")),
+            Self::Voxels => Some(("VX_START", "VX_END")),
+        }
+    }
+}
+
+/// A map header lump (e.g. `MAP01` or `E1M1`) and the lumps belonging to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MapGroup {
+    /// The index of the map header lump.
+    pub header: usize,
+    /// Indices of every component lump after the header, e.g. `THINGS`
+    /// through `BEHAVIOR`, or `TEXTMAP` through `ENDMAP` (inclusive).
+    pub components: Range<usize>,
+    pub format: MapFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MapFormat {
+    /// Vanilla binary lumps.
+    Doom,
+    /// Binary lumps, with a `BEHAVIOR` lump.
+    Hexen,
+    /// A `TEXTMAP` lump.
+    Udmf,
+}
+
+/// Non-fatal oddities found by [`classify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ClassifyWarning {
+    /// A start marker was found while a different namespace was still open.
+    NestedMarker {
+        index: usize,
+        namespace: Namespace,
+        outer: Namespace,
+    },
+    /// A start marker was never closed by a matching end marker.
+    UnclosedStart { index: usize, namespace: Namespace },
+    /// An end marker was found with no matching start marker before it.
+    UnopenedEnd { index: usize, namespace: Namespace },
+    /// A UDMF map's `TEXTMAP` was not followed by an `ENDMAP`.
+    /// Only the `TEXTMAP` is considered part of the map.
+    MissingEndMap { header: usize },
+}
+
+impl std::fmt::Display for ClassifyWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NestedMarker {
+                index,
+                namespace,
+                outer,
+            } => {
+                write!(
+                    f,
+                    "lump {index} opens the {namespace} namespace inside the {outer} namespace"
+                )
+            }
+            Self::UnclosedStart { index, namespace } => {
+                write!(
+                    f,
+                    "lump {index} opens the {namespace} namespace but it is never closed"
+                )
+            }
+            Self::UnopenedEnd { index, namespace } => {
+                write!(
+                    f,
+                    "lump {index} closes the {namespace} namespace but it was never opened"
+                )
+            }
+            Self::MissingEndMap { header } => {
+                write!(f, "UDMF map at lump {header} has no `ENDMAP`")
+            }
+        }
+    }
+}
+
+/// Binary map lumps, as they may appear after a map header.
+const BINARY_MAP_LUMPS: &[&str] = &[
+    "THINGS", "LINEDEFS", "SIDEDEFS", "VERTEXES", "SEGS", "SSECTORS", "NODES", "SECTORS", "REJECT",
+    "BLOCKMAP", "BEHAVIOR", "SCRIPTS",
+];
+
+#[must_use]
+pub(crate) fn marker(name: &str, suffix: &str) -> Option<Namespace> {
+    let split = name.len().checked_sub(suffix.len())?;
+    let (prefix, tail) = (name.get(..split)?, name.get(split..)?);
+
+    if !tail.eq_ignore_ascii_case(suffix) {
+        return None;
+    }
+
+    let ns = match prefix.to_ascii_uppercase().as_str() {
+        "A" => Namespace::Acs,
+        "C" => Namespace::Colormaps,
+        "F" | "FF" | "F1" | "F2" | "F3" => Namespace::Flats,
+        "HI" => Namespace::HiRes,
+        "P" | "PP" | "P1" | "P2" | "P3" => Namespace::Patches,
+        "S" | "SS" => Namespace::Sprites,
+        "TX" => Namespace::Textures,
+        "V" => Namespace::Voices,
+        "VX" => Namespace::Voxels,
+        _ => return None,
+    };
+
+    Some(ns)
+}
+
+#[must_use]
+fn find_maps<S: AsRef<str>>(names: &[S], warnings: &mut Vec<ClassifyWarning>) -> Vec<MapGroup> {
+    let mut maps = vec![];
+    let mut i = 0;
+
+    while i + 1 < names.len() {
+        let next = names[i + 1].as_ref();
+
+        if next.eq_ignore_ascii_case("TEXTMAP") {
+            let end = names[(i + 2)..]
+                .iter()
+                .position(|n| n.as_ref().eq_ignore_ascii_case("ENDMAP"));
+
+            let components = match end {
+                Some(e) => (i + 1)..(i + 2 + e + 1),
+                None => {
+                    warnings.push(ClassifyWarning::MissingEndMap { header: i });
+                    (i + 1)..(i + 2)
+                }
+            };
+
+            let next_i = components.end;
+
+            maps.push(MapGroup {
+                header: i,
+                components,
+                format: MapFormat::Udmf,
+            });
+
+            i = next_i;
+        } else if next.eq_ignore_ascii_case("THINGS") {
+            let len = names[(i + 1)..]
+                .iter()
+                .take_while(|n| {
+                    BINARY_MAP_LUMPS
+                        .iter()
+                        .any(|c| n.as_ref().eq_ignore_ascii_case(c))
+                })
+                .count();
+
+            let components = (i + 1)..(i + 1 + len);
+
+            let format = if names[components.clone()]
+                .iter()
+                .any(|n| n.as_ref().eq_ignore_ascii_case("BEHAVIOR"))
+            {
+                MapFormat::Hexen
+            } else {
+                MapFormat::Doom
+            };
+
+            let next_i = components.end;
+
+            maps.push(MapGroup {
+                header: i,
+                components,
+                format,
+            });
+
+            i = next_i;
+        } else {
+            i += 1;
+        }
+    }
+
+    maps
+}
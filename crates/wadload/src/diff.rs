@@ -0,0 +1,421 @@
+//! [`diff`], [`merge`], and their related symbols.
+
+use std::collections::HashMap;
+
+use util::Id8;
+
+use crate::{classify::marker, Lump, Namespace, Wad};
+
+/// Identifies "the same lump" across two WADs, even if its position changes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LumpKey {
+    pub namespace: Namespace,
+    /// The (ASCII uppercase) name of the map header this lump belongs to,
+    /// if it is a map component like `THINGS` or `TEXTMAP`.
+    pub map: Option<Id8>,
+    /// Always ASCII uppercase.
+    pub name: Id8,
+    /// How many lumps with the same namespace, map, and name precede this one.
+    /// Almost always 0.
+    pub occurrence: usize,
+}
+
+impl std::fmt::Display for LumpKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.namespace != Namespace::Global {
+            write!(f, "{}/", self.namespace)?;
+        }
+
+        if let Some(map) = &self.map {
+            write!(f, "{map}/")?;
+        }
+
+        write!(f, "{}", self.name)?;
+
+        if self.occurrence > 0 {
+            write!(f, "#{}", self.occurrence)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// One difference found by [`diff`]. Hashes are CRC32 (see [`Lump::crc32`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LumpChange {
+    /// `index` is into the new WAD.
+    Added {
+        key: LumpKey,
+        index: usize,
+        hash: u32,
+    },
+    /// `index` is into the old WAD.
+    Removed {
+        key: LumpKey,
+        index: usize,
+        hash: u32,
+    },
+    Modified {
+        key: LumpKey,
+        old_index: usize,
+        new_index: usize,
+        old_hash: u32,
+        new_hash: u32,
+        /// Whether this lump was also [`LumpChange::Moved`].
+        moved: bool,
+    },
+    /// The lump's content is unchanged, but its position relative to
+    /// the other lumps common to both WADs is different.
+    Moved {
+        key: LumpKey,
+        old_index: usize,
+        new_index: usize,
+        hash: u32,
+    },
+}
+
+impl LumpChange {
+    #[must_use]
+    pub fn key(&self) -> &LumpKey {
+        match self {
+            Self::Added { key, .. }
+            | Self::Removed { key, .. }
+            | Self::Modified { key, .. }
+            | Self::Moved { key, .. } => key,
+        }
+    }
+}
+
+impl std::fmt::Display for LumpChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Added { key, index, hash } => {
+                write!(f, "+ {key} (at {index}; {hash:08X})")
+            }
+            Self::Removed { key, index, hash } => {
+                write!(f, "- {key} (was at {index}; {hash:08X})")
+            }
+            Self::Modified {
+                key,
+                old_index,
+                new_index,
+                old_hash,
+                new_hash,
+                moved,
+            } => {
+                let m = if *moved { "~" } else { " " };
+
+                write!(
+                    f,
+                    "*{m}{key} ({old_index} -> {new_index}; {old_hash:08X} -> {new_hash:08X})"
+                )
+            }
+            Self::Moved {
+                key,
+                old_index,
+                new_index,
+                hash,
+            } => {
+                write!(f, "~ {key} ({old_index} -> {new_index}; {hash:08X})")
+            }
+        }
+    }
+}
+
+/// The output of [`diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WadDiff {
+    /// Additions, modifications, and moves in the new WAD's directory order,
+    /// followed by removals in the old WAD's directory order.
+    pub changes: Vec<LumpChange>,
+}
+
+impl WadDiff {
+    /// Returns `true` if every lump in one WAD has an identical counterpart in
+    /// the same relative position in the other.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl std::fmt::Display for WadDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{change}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Matches lumps in `old` and `new` by [`LumpKey`] and reports what changed.
+/// Namespace markers themselves are not compared.
+///
+/// A lump counts as moved if it is not part of the longest sequence of common
+/// lumps whose relative order is the same in both WADs, so moving one lump
+/// does not cause every lump it passed over to be reported too.
+#[must_use]
+pub fn diff(old: &Wad, new: &Wad) -> WadDiff {
+    let old_keys = keys(old);
+    let new_keys = keys(new);
+
+    let old_map = old_keys
+        .iter()
+        .enumerate()
+        .filter_map(|(i, k)| k.as_ref().map(|k| (k, i)))
+        .collect::<HashMap<_, _>>();
+
+    let new_map = new_keys
+        .iter()
+        .enumerate()
+        .filter_map(|(i, k)| k.as_ref().map(|k| (k, i)))
+        .collect::<HashMap<_, _>>();
+
+    // In the new WAD's order.
+    let common = new_keys
+        .iter()
+        .enumerate()
+        .filter_map(|(n, k)| k.as_ref().and_then(|k| old_map.get(k).map(|o| (*o, n))))
+        .collect::<Vec<_>>();
+
+    let in_order = longest_increasing(&common.iter().map(|(o, _)| *o).collect::<Vec<_>>());
+    let mut moved = vec![true; common.len()];
+
+    for i in in_order {
+        moved[i] = false;
+    }
+
+    let mut changes = vec![];
+    let mut common_iter = common.iter().zip(moved).peekable();
+
+    for (n, key) in new_keys.iter().enumerate() {
+        let Some(key) = key else {
+            continue;
+        };
+
+        let new_lump = &new[n];
+
+        match common_iter.peek() {
+            Some(((o, cn), moved)) if *cn == n => {
+                let (o, moved) = (*o, *moved);
+                let old_lump = &old[o];
+                common_iter.next();
+
+                if old_lump.bytes() != new_lump.bytes() {
+                    changes.push(LumpChange::Modified {
+                        key: key.clone(),
+                        old_index: o,
+                        new_index: n,
+                        old_hash: old_lump.crc32(),
+                        new_hash: new_lump.crc32(),
+                        moved,
+                    });
+                } else if moved {
+                    changes.push(LumpChange::Moved {
+                        key: key.clone(),
+                        old_index: o,
+                        new_index: n,
+                        hash: new_lump.crc32(),
+                    });
+                }
+            }
+            _ => {
+                changes.push(LumpChange::Added {
+                    key: key.clone(),
+                    index: n,
+                    hash: new_lump.crc32(),
+                });
+            }
+        }
+    }
+
+    for (o, key) in old_keys.iter().enumerate() {
+        let Some(key) = key else {
+            continue;
+        };
+
+        if !new_map.contains_key(key) {
+            changes.push(LumpChange::Removed {
+                key: key.clone(),
+                index: o,
+                hash: old[o].crc32(),
+            });
+        }
+    }
+
+    WadDiff { changes }
+}
+
+/// Produces a new WAD by applying each of `patches` on top of `base`, in order,
+/// the same way a source port would resolve them if they were all loaded:
+/// - a map in a patch replaces the base map of the same name wholesale,
+///   or is appended if there is none;
+/// - any other lump replaces the base lump with the same [`LumpKey`],
+///   or is added to the end of its namespace (which is itself appended
+///   with [`Namespace::markers`] if the base does not have it yet).
+///
+/// The merged WAD has the same [`crate::WadKind`] as `base`.
+#[must_use]
+pub fn merge<'p>(base: &Wad, patches: impl IntoIterator<Item = &'p Wad>) -> Wad {
+    let mut ret = base.clone();
+
+    for patch in patches {
+        let classified = patch.classify();
+        let patch_keys = keys(patch);
+        let mut ret_keys = None;
+        let mut i = 0;
+
+        while i < patch.lump_count() {
+            if let Some(group) = classified.maps.iter().find(|g| g.header == i) {
+                let lumps = &patch.lumps()[i..group.components.end];
+                replace_map(&mut ret, lumps);
+                ret_keys = None;
+                i = group.components.end;
+                continue;
+            }
+
+            let Some(key) = &patch_keys[i] else {
+                i += 1;
+                continue;
+            };
+
+            let lookup = ret_keys.get_or_insert_with(|| {
+                keys(&ret)
+                    .into_iter()
+                    .enumerate()
+                    .filter_map(|(i, k)| k.map(|k| (k, i)))
+                    .collect::<HashMap<_, _>>()
+            });
+
+            match lookup.get(key) {
+                Some(r) => ret[*r].set_bytes(patch[i].bytes()),
+                None => {
+                    insert_into_namespace(&mut ret, key.namespace, patch[i].clone());
+                    ret_keys = None;
+                }
+            }
+
+            i += 1;
+        }
+    }
+
+    ret
+}
+
+/// Parallel to `wad`'s lumps. Namespace markers have no key.
+#[must_use]
+fn keys(wad: &Wad) -> Vec<Option<LumpKey>> {
+    let classified = wad.classify();
+    let mut ret = Vec::with_capacity(wad.lump_count());
+    let mut counts = HashMap::new();
+    let mut map_of = vec![None; wad.lump_count()];
+
+    for group in &classified.maps {
+        let header = util::id8_truncated(&wad[group.header].name().to_ascii_uppercase());
+
+        for i in group.components.clone() {
+            map_of[i] = Some(header);
+        }
+    }
+
+    for (i, lump) in wad.lumps().iter().enumerate() {
+        if marker(lump.name(), "_START").is_some() || marker(lump.name(), "_END").is_some() {
+            ret.push(None);
+            continue;
+        }
+
+        let mut key = LumpKey {
+            namespace: classified.namespaces[i],
+            map: map_of[i],
+            name: util::id8_truncated(&lump.name().to_ascii_uppercase()),
+            occurrence: 0,
+        };
+
+        let count = counts.entry(key.clone()).or_insert(0);
+        key.occurrence = *count;
+        *count += 1;
+        ret.push(Some(key));
+    }
+
+    ret
+}
+
+/// `lumps` starts with a map header, followed by all of its components.
+fn replace_map(wad: &mut Wad, lumps: &[Lump]) {
+    let header = lumps[0].name();
+    let classified = wad.classify();
+
+    let existing = classified
+        .maps
+        .iter()
+        .rev()
+        .find(|g| wad[g.header].name().eq_ignore_ascii_case(header));
+
+    match existing {
+        Some(group) => {
+            let range = group.header..group.components.end;
+            wad.splice(range, lumps.iter().cloned());
+        }
+        None => wad.extend(lumps.iter().cloned()),
+    }
+}
+
+fn insert_into_namespace(wad: &mut Wad, namespace: Namespace, lump: Lump) {
+    let Some((start, end)) = namespace.markers() else {
+        wad.push(lump);
+        return;
+    };
+
+    let classified = wad.classify();
+
+    let end_pos = wad.lumps().iter().enumerate().rposition(|(i, l)| {
+        classified.namespaces[i] == namespace && marker(l.name(), "_END") == Some(namespace)
+    });
+
+    match end_pos {
+        Some(e) => wad.insert(e, lump),
+        None => {
+            wad.push(Lump::new(start, []).unwrap());
+            wad.push(lump);
+            wad.push(Lump::new(end, []).unwrap());
+        }
+    }
+}
+
+/// Returns the indices (into `seq`) of one longest strictly-increasing subsequence.
+#[must_use]
+fn longest_increasing(seq: &[usize]) -> Vec<usize> {
+    // `tails[l]` is the index of the smallest tail of any increasing
+    // subsequence of length `l + 1` found so far.
+    let mut tails: Vec<usize> = vec![];
+    let mut prev = vec![None; seq.len()];
+
+    for (i, &x) in seq.iter().enumerate() {
+        let l = tails.partition_point(|&t| seq[t] < x);
+
+        if l > 0 {
+            prev[i] = Some(tails[l - 1]);
+        }
+
+        if l == tails.len() {
+            tails.push(i);
+        } else {
+            tails[l] = i;
+        }
+    }
+
+    let mut ret = vec![];
+    let mut cur = tails.last().copied();
+
+    while let Some(i) = cur {
+        ret.push(i);
+        cur = prev[i];
+    }
+
+    ret.reverse();
+    ret
+}
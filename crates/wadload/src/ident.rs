@@ -0,0 +1,390 @@
+//! [`identify`] and its related symbols.
+
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::{classify, DirReader, Error, MapFormat, MappedWad};
+
+/// Determines which well-known IWAD (if any) `reader` holds.
+///
+/// The entire stream (from its start, regardless of `reader`'s current position)
+/// is hashed and compared against [`KNOWN_IWADS`]. If nothing matches, the
+/// directory is read and passed to [`guess_game`].
+/// `reader`'s position upon return is **unspecified**.
+///
+/// # Errors
+/// - [`Error::Io`] if reading the stream fails while hashing.
+/// - Any error from [`DirReader::new`] if the stream is not a known IWAD and
+///   has no readable directory.
+pub fn identify<R: Read + Seek>(mut reader: R) -> Result<Option<Identity>, Error> {
+    reader.seek(SeekFrom::Start(0)).map_err(|err| Error::Io {
+        source: err,
+        context: "checksum rewind",
+    })?;
+
+    let checksums = Checksums::read(&mut reader).map_err(|err| Error::Io {
+        source: err,
+        context: "checksum read",
+    })?;
+
+    if let Some(known) = KnownIwad::find(&checksums) {
+        return Ok(Some(Identity::Known(known)));
+    }
+
+    reader.seek(SeekFrom::Start(0)).map_err(|err| Error::Io {
+        source: err,
+        context: "directory rewind",
+    })?;
+
+    let dir = DirReader::new(reader)?;
+    let mut names = Vec::with_capacity(dir.lump_count());
+
+    for result in dir {
+        names.push(result?.name);
+    }
+
+    Ok(guess_game(&names).map(Identity::Guessed))
+}
+
+/// Like [`identify`], but for a WAD which is already entirely in memory.
+///
+/// # Errors
+/// See [`MappedWad::new`]; only raised if `bytes` is not a known IWAD.
+pub fn identify_bytes(bytes: &[u8]) -> Result<Option<Identity>, Error> {
+    if let Some(known) = KnownIwad::find(&Checksums::compute(bytes)) {
+        return Ok(Some(Identity::Known(known)));
+    }
+
+    let mapped = MappedWad::new(bytes)?;
+    let names = mapped.entries().iter().map(|entry| entry.name.as_str());
+    Ok(guess_game(names).map(Identity::Guessed))
+}
+
+/// The output of [`identify`] and [`identify_bytes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Identity {
+    /// The file's checksums exactly match those of a known release.
+    Known(&'static KnownIwad),
+    /// No checksums matched, but the directory resembles this game's IWAD.
+    /// The file may be a modified or otherwise unrecognized release.
+    Guessed(Game),
+}
+
+impl Identity {
+    #[must_use]
+    pub fn game(&self) -> Game {
+        match self {
+            Self::Known(known) => known.game,
+            Self::Guessed(game) => *game,
+        }
+    }
+}
+
+/// A game (or expansion) whose data is distributed in an IWAD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Game {
+    /// The freely-distributed first episode of DOOM, in `DOOM1.WAD`.
+    DoomShareware,
+    /// The registered version of DOOM, with three episodes, in `DOOM.WAD`.
+    Doom,
+    /// The Ultimate DOOM, with a fourth episode, in `DOOM.WAD`.
+    UltimateDoom,
+    /// `DOOM2.WAD`.
+    Doom2,
+    /// No Rest for the Living, in `NERVE.WAD`. Not strictly an IWAD, since it
+    /// requires `DOOM2.WAD`, but distributed alongside it.
+    Nerve,
+    /// Final DOOM's TNT: Evilution, in `TNT.WAD`.
+    Tnt,
+    /// Final DOOM's The Plutonia Experiment, in `PLUTONIA.WAD`.
+    Plutonia,
+    /// `FREEDOOM1.WAD`.
+    Freedoom1,
+    /// `FREEDOOM2.WAD`.
+    Freedoom2,
+    /// `FREEDM.WAD`.
+    FreeDm,
+    /// `CHEX.WAD`.
+    Chex,
+    /// `HACX.WAD`.
+    Hacx,
+    /// `HERETIC.WAD` or `HERETIC1.WAD`.
+    Heretic,
+    /// `HEXEN.WAD`.
+    Hexen,
+    /// `STRIFE1.WAD`.
+    Strife,
+}
+
+impl std::fmt::Display for Game {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DoomShareware => write!(f, "DOOM (Shareware)"),
+            Self::Doom => write!(f, "DOOM (Registered)"),
+            Self::UltimateDoom => write!(f, "The Ultimate DOOM"),
+            Self::Doom2 => write!(f, "DOOM II: Hell on Earth"),
+            Self::Nerve => write!(f, "No Rest for the Living"),
+            Self::Tnt => write!(f, "Final DOOM: TNT: Evilution"),
+            Self::Plutonia => write!(f, "Final DOOM: The Plutonia Experiment"),
+            Self::Freedoom1 => write!(f, "Freedoom: Phase 1"),
+            Self::Freedoom2 => write!(f, "Freedoom: Phase 2"),
+            Self::FreeDm => write!(f, "FreeDM"),
+            Self::Chex => write!(f, "Chex Quest"),
+            Self::Hacx => write!(f, "HACX"),
+            Self::Heretic => write!(f, "Heretic"),
+            Self::Hexen => write!(f, "Hexen"),
+            Self::Strife => write!(f, "Strife"),
+        }
+    }
+}
+
+/// A particular release of an IWAD, identified by the checksums of the entire file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownIwad {
+    pub game: Game,
+    /// The name of the file as distributed, in ASCII uppercase.
+    pub file_name: &'static str,
+    pub version: &'static str,
+    pub checksums: Checksums,
+}
+
+impl KnownIwad {
+    /// Searches [`KNOWN_IWADS`] for an entry whose checksums all equal `checksums`.
+    #[must_use]
+    pub fn find(checksums: &Checksums) -> Option<&'static Self> {
+        KNOWN_IWADS
+            .iter()
+            .find(|known| known.checksums == *checksums)
+    }
+}
+
+/// CRC32, MD5, and SHA-1 digests of an entire file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Checksums {
+    pub crc32: u32,
+    pub md5: [u8; 16],
+    pub sha1: [u8; 20],
+}
+
+impl Checksums {
+    #[must_use]
+    pub fn compute(bytes: &[u8]) -> Self {
+        let mut hasher = Hasher::new();
+        hasher.update(bytes);
+        hasher.finish()
+    }
+
+    /// Hashes everything from `reader`'s current position to the end of the stream.
+    pub fn read<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let mut hasher = Hasher::new();
+        let mut buf = vec![0; 1024 * 64];
+
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => hasher.update(&buf[..n]),
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(hasher.finish())
+    }
+}
+
+/// Tries to determine which game's IWAD a directory belongs to, using only the
+/// names of its lumps (in directory order). Returns `None` if the directory
+/// does not resemble any IWAD, as would be the case for most PWADs.
+///
+/// This is a fallback for when [`KnownIwad::find`] has no answer; it can not
+/// tell versions apart, and a sufficiently thorough PWAD can fool it.
+#[must_use]
+pub fn guess_game<I, S>(names: I) -> Option<Game>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let names = names.into_iter().collect::<Vec<_>>();
+    let has = |name: &str| names.iter().any(|n| n.as_ref().eq_ignore_ascii_case(name));
+
+    if has("FREEDOOM") {
+        if has("FREEDM") {
+            return Some(Game::FreeDm);
+        } else if has("MAP01") {
+            return Some(Game::Freedoom2);
+        } else if has("E1M1") {
+            return Some(Game::Freedoom1);
+        }
+    }
+
+    if has("HACX-R") {
+        return Some(Game::Hacx);
+    }
+
+    if has("ENDSTRF") {
+        return Some(Game::Strife);
+    }
+
+    if has("W94_1") {
+        return Some(Game::Chex);
+    }
+
+    if has("ENDTEXT") && has("E1M1") {
+        return Some(Game::Heretic);
+    }
+
+    if has("MAP01") {
+        let classified = classify(&names);
+
+        if classified
+            .maps
+            .iter()
+            .any(|group| group.format == MapFormat::Hexen)
+            && !has("ENDOOM")
+        {
+            return Some(Game::Hexen);
+        }
+    }
+
+    if !has("ENDOOM") {
+        return None;
+    }
+
+    if has("MAP01") {
+        if has("CAMO1") {
+            Some(Game::Plutonia)
+        } else if has("REDTNT2") {
+            Some(Game::Tnt)
+        } else {
+            Some(Game::Doom2)
+        }
+    } else if has("E1M1") {
+        if has("E4M1") {
+            Some(Game::UltimateDoom)
+        } else if has("E2M1") {
+            Some(Game::Doom)
+        } else {
+            Some(Game::DoomShareware)
+        }
+    } else {
+        None
+    }
+}
+
+/// Sourced from the [Doom Wiki](https://doomwiki.org/wiki/IWAD).
+#[rustfmt::skip]
+pub static KNOWN_IWADS: &[KnownIwad] = &[
+    doom1("1.0", 0xeedae672, "90facab21eede7981be10790e3f82da2", "fc0359e191bd257b3507863ae412ef3250515866"),
+    doom1("1993-12-15 press release", 0x289f4d3f, "cea4989df52b65f4d481b706234a3dca", "9a24a7093ea0e78fd85f9923e55c55e79491b6a1"),
+    doom1("1.1", 0x981dcebb, "52cbc8882f445573ce421fa5453513c1", "d4dc6806abd96bd93570c8df436fb6956e13d910"),
+    doom1("1.2", 0xbc842626, "30aa5beb9e5ebfbbe1e1765561c08f38", "77ef34de7f13dc36b792fb82ed6805e9c1dc7afc"),
+    doom1("1.25", 0x225d7fb1, "17aebd6b5f2ed8ce07aa526a32af8d99", "72caf585f7ce56861d25f8580c1cc82bf50abd1b"),
+    doom1("1.4", 0xf5c2708d, "a21ae40c388cb6f2c3cc1b95589ee693", "b4a8e93f1f9544210a173035a0b04c19eb283a2a"),
+    doom1("1.5", 0x8653b0eb, "e280233d533dcc28c1acd6ccdc7742d4", "b559ba93d0a96e242eb6ded9deeedbd6f79d40fc"),
+    doom1("1.6", 0xf26dcad8, "762fd6d4b960d4b759730f01387a50a1", "1437fc1ac25a17d5b3cef4c9d2f74e40cae3d231"),
+    doom1("1.666", 0x505fb740, "c428ea394dc52835f2580d5bfd50d76f", "81535778d0d4c0c7aa8616fbfd3607dfb3dfd643"),
+    doom1("1.8", 0x331ebf07, "5f4eb849b1af12887dec04a2a12e5e62", "c6612ac5a8ac2e2a1d707f9b2869af820efb7c50"),
+    doom1("1.9", 0x162b696a, "f0cefca49926d00903cf57551d901abe", "5b2e249b9c5133ec987b3ea77596381dc0d6bc1d"),
+    known(Game::Doom, "DOOM.WAD", "1.9", 0x723e60f9, "1cd63c5ddff1bf8ce844237f580e9cf3", "7742089b4468a736cadb659a7deca3320fe6dcbd"),
+    known(Game::UltimateDoom, "DOOM.WAD", "1.9ud", 0xbf0eaac0, "c4fe9fd920207691a9f493668e0a2083", "9b07b02ab3c275a6a7570c3f73cc20d63a0e3833"),
+    known(Game::Doom2, "DOOM2.WAD", "1.9", 0xec8725db, "25e1459ca71d321525f84628f45ca8cd", "7ec7652fcfce8ddc6e801839291f0e28ef1d5ae7"),
+    known(Game::Nerve, "NERVE.WAD", "DOOM 3: BFG Edition", 0xad7f9292, "967d5ae23daf45196212ae1b605da3b0", "3451288383fb16e196f273d9f85d58c1fda97bf4"),
+    known(Game::Nerve, "NERVE.WAD", "KEX", 0x07d9faab, "23422eb42833ac7b0dd59c0c7ae18a6f", "4522eaec7a13aac24b456e29520e99ac879e6989"),
+    tnt("1.9", 0x903dcc27, "4e158d9953c79ccf97bd0663244cc6b6", "9fbc66aedef7fe3bae0986cdb9323d2b8db4c9d3"),
+    tnt("Anthology", 0xd4bb05c0, "1d39e405bf6ee3df69a8d2646c8d5c49", "4a65c8b960225505187c36040b41a40b152f8f3e"),
+    tnt("PlayStation Network", 0x7f572c1f, "be626c12b7c9d94b1dfb9c327566b4ff", "139e26d801a64b404b8d898defca10227a61867b"),
+    tnt("KEX", 0x15f18ddb, "8974e3117ed4a1839c752d5e11ab1b7b", "9820e2a3035f0cdd87f69a7d57c59a7a267c9409"),
+    known(Game::Plutonia, "PLUTONIA.WAD", "1.9", 0x15cd1448, "75c8cf89566741fa9d22447604053bd7", "90361e2a538d2388506657252ae41aceeb1ba360"),
+    known(Game::Heretic, "HERETIC.WAD", "1.3", 0x5b16049e, "66d686b1ed6d35ff103f15dbd30e0341", "f489d479371df32f6d280a0cb23b59a35ba2b833"),
+    known(Game::Hexen, "HEXEN.WAD", "1.1", 0xdca9114c, "abb033caf81e26f12a2103e1fa25453f", "4b53832f0733c1e29e5f1de2428e5475e891af29"),
+    known(Game::Strife, "STRIFE1.WAD", "1.2", 0x4234ace5, "2fed2031a5b03892106e0f117f17901f", "64c13b951a845ca7f8081f68138a6181557458d1"),
+];
+
+#[must_use]
+const fn doom1(version: &'static str, crc32: u32, md5: &str, sha1: &str) -> KnownIwad {
+    known(Game::DoomShareware, "DOOM1.WAD", version, crc32, md5, sha1)
+}
+
+#[must_use]
+const fn tnt(version: &'static str, crc32: u32, md5: &str, sha1: &str) -> KnownIwad {
+    known(Game::Tnt, "TNT.WAD", version, crc32, md5, sha1)
+}
+
+#[must_use]
+const fn known(
+    game: Game,
+    file_name: &'static str,
+    version: &'static str,
+    crc32: u32,
+    md5: &str,
+    sha1: &str,
+) -> KnownIwad {
+    KnownIwad {
+        game,
+        file_name,
+        version,
+        checksums: Checksums {
+            crc32,
+            md5: hex(md5),
+            sha1: hex(sha1),
+        },
+    }
+}
+
+/// Panics (at compile time, when used in a constant) if `string`
+/// is not exactly `N * 2` lowercase hexadecimal digits.
+#[must_use]
+const fn hex<const N: usize>(string: &str) -> [u8; N] {
+    const fn nibble(c: u8) -> u8 {
+        match c {
+            b'0'..=b'9' => c - b'0',
+            b'a'..=b'f' => c - b'a' + 10,
+            _ => panic!("invalid hexadecimal digit"),
+        }
+    }
+
+    let bytes = string.as_bytes();
+    assert!(bytes.len() == N * 2);
+    let mut ret = [0; N];
+    let mut i = 0;
+
+    while i < N {
+        ret[i] = (nibble(bytes[i * 2]) << 4) | nibble(bytes[i * 2 + 1]);
+        i += 1;
+    }
+
+    ret
+}
+
+struct Hasher {
+    crc32: crc32fast::Hasher,
+    md5: md5::Context,
+    sha1: sha1_smol::Sha1,
+}
+
+impl Hasher {
+    #[must_use]
+    fn new() -> Self {
+        Self {
+            crc32: crc32fast::Hasher::new(),
+            md5: md5::Context::new(),
+            sha1: sha1_smol::Sha1::new(),
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        self.crc32.update(bytes);
+        self.md5.consume(bytes);
+        self.sha1.update(bytes);
+    }
+
+    #[must_use]
+    fn finish(self) -> Checksums {
+        Checksums {
+            crc32: self.crc32.finalize(),
+            md5: self.md5.compute().0,
+            sha1: self.sha1.digest().bytes(),
+        }
+    }
+}
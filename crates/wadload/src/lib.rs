@@ -9,6 +9,14 @@
     html_logo_url = "https://media.githubusercontent.com/media/jerome-trc/viletech/master/assets/viletech.png"
 )]
 
+mod classify;
+mod diff;
+mod ident;
+mod lossy;
+mod mapped;
+mod wad;
+mod write;
+
 use std::{
     io::{Read, Seek, SeekFrom},
     ops::Range,
@@ -16,6 +24,18 @@ use std::{
 
 use util::{read_id8, Id8};
 
+pub use self::{
+    classify::{classify, Classification, ClassifyWarning, MapFormat, MapGroup, Namespace},
+    diff::{diff, merge, LumpChange, LumpKey, WadDiff},
+    ident::{
+        guess_game, identify, identify_bytes, Checksums, Game, Identity, KnownIwad, KNOWN_IWADS,
+    },
+    lossy::{Diagnostic, LossyDirReader, LossyReader},
+    mapped::MappedWad,
+    wad::Wad,
+    write::Writer,
+};
+
 /// Whether this WAD is the basis of a game, or a "mod".
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -258,7 +278,7 @@ impl<R: Read + Seek> Iterator for Reader<R> {
 impl<R: Read + Seek> ExactSizeIterator for Reader<R> {}
 
 /// An entry in a WAD file.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Lump {
     name: Id8,
@@ -266,16 +286,56 @@ pub struct Lump {
 }
 
 impl Lump {
+    /// # Errors
+    /// Returns [`Error::InvalidLumpName`] if `name` is empty, is longer than
+    /// 8 bytes, or contains non-ASCII characters.
+    pub fn new(name: &str, bytes: impl Into<Box<[u8]>>) -> Result<Self, Error> {
+        Ok(Self {
+            name: write::validate_name(name)?,
+            bytes: bytes.into(),
+        })
+    }
+
     #[must_use]
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    /// # Errors
+    /// Returns [`Error::InvalidLumpName`] if `name` is empty, is longer than
+    /// 8 bytes, or contains non-ASCII characters.
+    pub fn set_name(&mut self, name: &str) -> Result<(), Error> {
+        self.name = write::validate_name(name)?;
+        Ok(())
+    }
+
     #[must_use]
     pub fn bytes(&self) -> &[u8] {
         &self.bytes
     }
 
+    #[must_use]
+    pub fn bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+
+    pub fn set_bytes(&mut self, bytes: impl Into<Box<[u8]>>) {
+        self.bytes = bytes.into();
+    }
+
+    /// Shorthand for checking if this lump has no data, as is the case with
+    /// markers like `S_START` and map headers like `MAP01`.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// The CRC-32 (ISO-HDLC, as used by zip) of this lump's data.
+    #[must_use]
+    pub fn crc32(&self) -> u32 {
+        crc32fast::hash(&self.bytes)
+    }
+
     #[must_use]
     pub fn into_inner(self) -> (Id8, Vec<u8>) {
         (self.name, self.bytes.into_vec())
@@ -307,7 +367,11 @@ pub enum Error {
     /// The contained index is that of a directory entry that could not be fully
     /// read, or had a negative offset or size.
     InvalidDirEntry(usize),
-    /// Can be raised when trying to read a header.
+    /// Lump names must be between 1 and 8 ASCII characters, with no NULs.
+    /// The contained name may be truncated.
+    InvalidLumpName(Id8),
+    /// Can be raised when trying to read a header, or when a [`Writer`] would
+    /// emit a lump or directory past the addressable range of a WAD.
     Oversize,
     /// The header prescribed `n` number of lumps and the directory is at a byte
     /// offset of `o`, but `(16 * n) + o` is past the length of readable data.
@@ -337,6 +401,9 @@ impl std::fmt::Display for Error {
                     "WAD directory entry {index} has a negative lump size or offset"
                 )
             }
+            Error::InvalidLumpName(name) => {
+                write!(f, "invalid lump name: `{name}`")
+            }
             Error::Oversize => {
                 write!(f, "WAD file is larger than prescribed by its header")
             }
@@ -347,6 +414,7 @@ impl std::fmt::Display for Error {
     }
 }
 
+pub(crate) const HEADER_SIZE: usize = 12;
 pub(crate) const DIR_ENTRY_SIZE: usize = 16;
 
 pub(crate) struct Header {
@@ -355,14 +423,9 @@ pub(crate) struct Header {
     lump_c: i32,
 }
 
-fn validate_impl<R: Read + Seek>(reader: &mut R) -> Result<Header, Error> {
-    let mut hbuf = [0; 12];
-
-    reader.read_exact(&mut hbuf).map_err(|err| Error::Io {
-        source: err,
-        context: "header read",
-    })?;
-
+/// Returns the WAD's kind, entry count, and directory offset;
+/// the latter two are guaranteed to be non-negative.
+fn parse_header(hbuf: [u8; HEADER_SIZE]) -> Result<(WadKind, i32, i32), Error> {
     let kind = match &hbuf[0..4] {
         b"IWAD" => WadKind::IWad,
         b"PWAD" => WadKind::PWad,
@@ -381,6 +444,19 @@ fn validate_impl<R: Read + Seek>(reader: &mut R) -> Result<Header, Error> {
         return Err(Error::InvalidDirOffset(dir_offs));
     }
 
+    Ok((kind, lump_c, dir_offs))
+}
+
+fn validate_impl<R: Read + Seek>(reader: &mut R) -> Result<Header, Error> {
+    let mut hbuf = [0; HEADER_SIZE];
+
+    reader.read_exact(&mut hbuf).map_err(|err| Error::Io {
+        source: err,
+        context: "header read",
+    })?;
+
+    let (kind, lump_c, dir_offs) = parse_header(hbuf)?;
+
     let expected_dir_len = (lump_c as usize)
         .checked_mul(DIR_ENTRY_SIZE)
         .ok_or(Error::Oversize)?;
@@ -416,10 +492,424 @@ fn validate_impl<R: Read + Seek>(reader: &mut R) -> Result<Header, Error> {
 
 #[cfg(test)]
 mod test {
-    use std::{io::BufReader, path::Path};
+    use std::{
+        io::{BufReader, Cursor},
+        path::Path,
+    };
 
     use super::*;
 
+    #[test]
+    fn write_roundtrip() {
+        let mut writer = Writer::new(Cursor::new(vec![]), WadKind::PWad).unwrap();
+
+        writer.write_marker("MAP01").unwrap();
+        writer
+            .write_lump("THINGS", &[0xB0, 0x06, 0x40, 0x04])
+            .unwrap();
+        writer
+            .write(&Lump::new("DEHACKED", b"Patch File for DeHackEd v3.0".to_vec()).unwrap())
+            .unwrap();
+
+        assert!(matches!(
+            writer.write_lump("TOOLONGNAME", &[]),
+            Err(Error::InvalidLumpName(_))
+        ));
+
+        let bytes = writer.finish().unwrap().into_inner();
+        assert_eq!(&bytes[0..4], b"PWAD");
+        assert_eq!(bytes.len(), HEADER_SIZE + 4 + 28 + (DIR_ENTRY_SIZE * 3));
+
+        let reader = Reader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.wad_kind(), WadKind::PWad);
+        assert_eq!(reader.lump_count(), 3);
+
+        let lumps = reader.map(|result| result.unwrap()).collect::<Vec<_>>();
+        assert_eq!(lumps[0].0.name.as_str(), "MAP01");
+        assert!(lumps[0].1.is_empty());
+        assert_eq!(lumps[1].0.name.as_str(), "THINGS");
+        assert_eq!(lumps[1].1, &[0xB0, 0x06, 0x40, 0x04]);
+        assert_eq!(lumps[2].0.name.as_str(), "DEHACKED");
+        assert_eq!(lumps[2].1, b"Patch File for DeHackEd v3.0");
+    }
+
+    #[test]
+    fn classification() {
+        let names = [
+            "MAP01", "THINGS", "LINEDEFS", "SIDEDEFS", "VERTEXES", "SECTORS", "BEHAVIOR", "MAP02",
+            "TEXTMAP", "ZNODES", "ENDMAP", "S_START", "SS_START", "TROOA1", "SS_END", "S_END",
+            "FF_START", "F1_START", "FLOOR0_1", "F1_END", "C_START", "WATERMAP", "C_END", "F_END",
+            "P_END", "TX_START", "BRICK",
+        ];
+
+        let classified = classify(names);
+
+        assert_eq!(
+            classified.maps,
+            [
+                MapGroup {
+                    header: 0,
+                    components: 1..7,
+                    format: MapFormat::Hexen,
+                },
+                MapGroup {
+                    header: 7,
+                    components: 8..11,
+                    format: MapFormat::Udmf,
+                },
+            ]
+        );
+
+        assert_eq!(classified.namespaces[1], Namespace::Global);
+        assert_eq!(classified.namespaces[13], Namespace::Sprites);
+        assert_eq!(classified.namespaces[15], Namespace::Sprites);
+        assert_eq!(classified.namespaces[18], Namespace::Flats);
+        assert_eq!(classified.namespaces[21], Namespace::Colormaps);
+        assert_eq!(classified.namespaces[26], Namespace::Textures);
+
+        assert_eq!(
+            classified.warnings,
+            [
+                ClassifyWarning::NestedMarker {
+                    index: 20,
+                    namespace: Namespace::Colormaps,
+                    outer: Namespace::Flats,
+                },
+                ClassifyWarning::UnopenedEnd {
+                    index: 24,
+                    namespace: Namespace::Patches,
+                },
+                ClassifyWarning::UnclosedStart {
+                    index: 25,
+                    namespace: Namespace::Textures,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_merge() {
+        fn wad(lumps: &[(&str, &[u8])]) -> Wad {
+            let mut ret = Wad::new(WadKind::PWad);
+
+            for (name, bytes) in lumps {
+                ret.push(Lump::new(name, bytes.to_vec()).unwrap());
+            }
+
+            ret
+        }
+
+        let base = wad(&[
+            ("MAP01", &[]),
+            ("THINGS", &[0x00]),
+            ("LINEDEFS", &[0x01]),
+            ("MAP02", &[]),
+            ("THINGS", &[0x02]),
+            ("DEHACKED", &[0x03]),
+            ("S_START", &[]),
+            ("TROOA1", &[0x04]),
+            ("S_END", &[]),
+        ]);
+
+        let patch = wad(&[
+            ("DEHACKED", &[0x03]),
+            ("MAP02", &[]),
+            ("THINGS", &[0x05]),
+            ("SS_START", &[]),
+            ("TROOB1", &[0x06]),
+            ("SS_END", &[]),
+            ("FF_START", &[]),
+            ("FLOOR0_1", &[0x07]),
+            ("FF_END", &[]),
+        ]);
+
+        let d = diff(&base, &patch);
+
+        let key = |namespace, map: Option<&str>, name| LumpKey {
+            namespace,
+            map: map.map(util::id8_truncated),
+            name: util::id8_truncated(name),
+            occurrence: 0,
+        };
+
+        assert_eq!(d.changes.len(), 8);
+        assert!(
+            matches!(&d.changes[0], LumpChange::Moved { key: k, .. } if *k == key(Namespace::Global, None, "DEHACKED"))
+        );
+        assert!(
+            matches!(&d.changes[1], LumpChange::Modified { key: k, moved: false, .. } if *k == key(Namespace::Global, Some("MAP02"), "THINGS"))
+        );
+        assert!(
+            matches!(&d.changes[2], LumpChange::Added { key: k, .. } if *k == key(Namespace::Sprites, None, "TROOB1"))
+        );
+        assert!(
+            matches!(&d.changes[3], LumpChange::Added { key: k, .. } if *k == key(Namespace::Flats, None, "FLOOR0_1"))
+        );
+        assert!(matches!(
+            &d.changes[4],
+            LumpChange::Removed { index: 0, .. }
+        ));
+        assert!(diff(&base, &base).is_empty());
+
+        let merged = merge(&base, [&patch]);
+        let names = merged.lumps().iter().map(|l| l.name()).collect::<Vec<_>>();
+
+        assert_eq!(
+            names,
+            [
+                "MAP01", "THINGS", "LINEDEFS", "MAP02", "THINGS", "DEHACKED", "S_START", "TROOA1",
+                "TROOB1", "S_END", "FF_START", "FLOOR0_1", "FF_END",
+            ]
+        );
+
+        assert_eq!(merged[4].bytes(), &[0x05]);
+    }
+
+    #[test]
+    fn identification() {
+        let abc = Checksums::compute(b"abc");
+        assert_eq!(abc, Checksums::read(&b"abc"[..]).unwrap());
+        assert_eq!(abc.crc32, 0x352441C2);
+        assert_eq!(abc.md5[..4], [0x90, 0x01, 0x50, 0x98]);
+        assert_eq!(abc.sha1[..4], [0xA9, 0x99, 0x3E, 0x36]);
+
+        let tnt = KNOWN_IWADS
+            .iter()
+            .find(|k| k.file_name == "TNT.WAD" && k.version == "1.9")
+            .unwrap();
+        assert_eq!(KnownIwad::find(&tnt.checksums), Some(tnt));
+
+        // Real files whose checksums are known go through `identify`,
+        // which must hash the whole stream regardless of its position.
+        for (rel, crc32, md5) in [
+            (
+                "sample/freedoom2/map01.wad",
+                0x094D6884,
+                [0x35, 0xF2, 0x7F, 0x56],
+            ),
+            (
+                "sample/pwads/analysis_test.wad",
+                0x546D2D96,
+                [0xCE, 0x25, 0x0E, 0x0F],
+            ),
+        ] {
+            let bytes = std::fs::read(Path::new(env!("CARGO_WORKSPACE_DIR")).join(rel)).unwrap();
+            let checksums = Checksums::read(&bytes[..]).unwrap();
+            assert_eq!(checksums.crc32, crc32, "{rel}");
+            assert_eq!(checksums.md5[..4], md5, "{rel}");
+            assert_eq!(KnownIwad::find(&checksums), None, "{rel}");
+
+            let mut cursor = Cursor::new(bytes);
+            cursor.seek(SeekFrom::End(0)).unwrap();
+            let identity = identify(&mut cursor).unwrap();
+            assert!(!matches!(identity, Some(Identity::Known(_))), "{rel}");
+            assert_eq!(identify_bytes(cursor.get_ref()).unwrap(), identity, "{rel}");
+        }
+
+        for (names, expected) in [
+            (&["MAP01", "THINGS", "ENDOOM"][..], Some(Game::Doom2)),
+            (
+                &["ENDOOM", "MAP01", "THINGS", "CAMO1"],
+                Some(Game::Plutonia),
+            ),
+            (
+                &["ENDOOM", "E1M1", "THINGS", "FREEDOOM"],
+                Some(Game::Freedoom1),
+            ),
+            (&["ENDOOM", "E1M1", "E2M1", "E3M1"], Some(Game::Doom)),
+            (
+                &["ENDOOM", "E1M1", "E2M1", "E4M1"],
+                Some(Game::UltimateDoom),
+            ),
+            (&["ENDTEXT", "E1M1", "THINGS"], Some(Game::Heretic)),
+            (&["MAP01", "THINGS", "BEHAVIOR"], Some(Game::Hexen)),
+            (&["MAP01", "THINGS", "LINEDEFS"], None),
+        ] {
+            assert_eq!(guess_game(names), expected, "{names:?}");
+        }
+
+        let mut writer = Writer::new(Cursor::new(vec![]), WadKind::IWad).unwrap();
+        writer.write_lump("ENDOOM", &[0x00; 4000]).unwrap();
+        writer.write_marker("MAP01").unwrap();
+        writer.write_lump("THINGS", &[]).unwrap();
+        writer.write_lump("REDTNT2", &[0x01]).unwrap();
+        let mut cursor = writer.finish().unwrap();
+
+        assert_eq!(
+            identify(&mut cursor).unwrap(),
+            Some(Identity::Guessed(Game::Tnt))
+        );
+
+        assert_eq!(
+            identify_bytes(cursor.get_ref()).unwrap(),
+            Some(Identity::Guessed(Game::Tnt))
+        );
+    }
+
+    #[test]
+    fn lossy() {
+        let mut bytes = Vec::from(*b"PWAD");
+        bytes.extend(4_i32.to_le_bytes());
+        bytes.extend(20_i32.to_le_bytes());
+        bytes.extend([0xAA; 8]);
+
+        for (offs, size, name) in [
+            (12_i32, 8_i32, b"GOOD\0\0\0\0"),
+            (16, 2, b"OVERLAP\0"),
+            (-1, 4, b"NEGATIVE"),
+            (60, 100, b"CLAMPED\0"),
+        ] {
+            bytes.extend(offs.to_le_bytes());
+            bytes.extend(size.to_le_bytes());
+            bytes.extend(name);
+        }
+
+        bytes.extend([0xBB; 4]);
+
+        assert!(matches!(
+            Reader::new(Cursor::new(bytes.clone())),
+            Err(Error::DataMalformed(_))
+        ));
+
+        let reader = LossyReader::new(Cursor::new(bytes)).unwrap();
+
+        assert_eq!(
+            reader.diagnostics(),
+            &[
+                Diagnostic::TrailingData { len: 4 },
+                Diagnostic::NegativeEntry { index: 2 },
+                Diagnostic::EntryClamped {
+                    index: 3,
+                    original: 60..160,
+                    clamped: 60..88,
+                },
+                Diagnostic::Overlap { index: 1, other: 0 },
+            ]
+        );
+
+        let lumps = reader.map(|result| result.unwrap()).collect::<Vec<_>>();
+        assert_eq!(lumps.len(), 3);
+        assert_eq!(lumps[0].0.name.as_str(), "GOOD");
+        assert_eq!(lumps[0].1, [0xAA; 8]);
+        assert_eq!(lumps[1].1, [0xAA; 2]);
+        assert_eq!(lumps[2].0.name.as_str(), "CLAMPED");
+        assert_eq!(lumps[2].1.len(), 28);
+    }
+
+    #[test]
+    fn mapped() {
+        let mut writer = Writer::new(Cursor::new(vec![]), WadKind::IWad).unwrap();
+        writer.write_lump("PLAYPAL", &[0x00, 0x01, 0x02]).unwrap();
+        writer.write_marker("F_START").unwrap();
+        writer.write_lump("PLAYPAL", &[0x03]).unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let mapped = MappedWad::new(&bytes).unwrap();
+        assert_eq!(mapped.wad_kind(), WadKind::IWad);
+        assert_eq!(mapped.lump_count(), 3);
+        assert_eq!(mapped.lookup("playpal").unwrap(), &[0x03]);
+
+        let (entry, data) = mapped.get(0).unwrap();
+        assert_eq!(entry.name.as_str(), "PLAYPAL");
+        assert_eq!(data, &[0x00, 0x01, 0x02]);
+        assert!(std::ptr::eq(data.as_ptr(), bytes[HEADER_SIZE..].as_ptr()));
+
+        let via_reader = Reader::new(Cursor::new(bytes.as_slice()))
+            .unwrap()
+            .map(|result| result.unwrap().1)
+            .collect::<Vec<_>>();
+
+        assert!(mapped
+            .iter()
+            .zip(via_reader)
+            .all(|((_, mapped), read)| mapped == read.as_slice()));
+    }
+
+    #[test]
+    fn wad_edit() {
+        let mut wad = Wad::new(WadKind::PWad);
+
+        for (name, bytes) in [
+            ("PLAYPAL", &[0x00, 0x01][..]),
+            ("S_START", &[]),
+            ("TROOA1", &[0x02]),
+            ("TROOB1", &[0x03]),
+            ("S_END", &[]),
+            ("FF_START", &[]),
+            ("FLOOR0_1", &[0x04]),
+            ("F_END", &[]),
+            ("PLAYPAL", &[0x05, 0x06]),
+        ] {
+            wad.push(Lump::new(name, bytes.to_vec()).unwrap());
+        }
+
+        let bytes = wad.write(Cursor::new(vec![])).unwrap().into_inner();
+        let reread = Wad::read(Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(reread, wad);
+        let rewritten = reread.write(Cursor::new(vec![])).unwrap().into_inner();
+        assert_eq!(rewritten, bytes);
+
+        assert_eq!(wad.find("playpal"), Some(8));
+        assert_eq!(wad.lookup("PLAYPAL").unwrap().bytes(), &[0x05, 0x06]);
+        assert_eq!(wad.find_from(0, "PLAYPAL"), Some(0));
+
+        let sprites = wad
+            .between(&["S_START", "SS_START"], &["S_END", "SS_END"])
+            .map(|lump| lump.name())
+            .collect::<Vec<_>>();
+        assert_eq!(sprites, ["TROOA1", "TROOB1"]);
+        assert_eq!(
+            wad.marker_ranges(&["F_START", "FF_START"], &["F_END", "FF_END"])
+                .collect::<Vec<_>>(),
+            [6..7]
+        );
+
+        wad.rename(2, "TROOC1").unwrap();
+        assert!(wad.rename(2, "").is_err());
+        wad.move_lump(8, 0);
+        assert_eq!(wad[0].bytes(), &[0x05, 0x06]);
+        assert_eq!(wad.find("PLAYPAL"), Some(1));
+        wad.move_lump(0, 8);
+        assert_eq!(wad.find("PLAYPAL"), Some(8));
+        let removed = wad.remove(3);
+        assert_eq!(removed.name(), "TROOB1");
+        assert_eq!(wad.lookup("TROOC1").unwrap().bytes(), &[0x02]);
+    }
+
+    #[test]
+    fn wad_layout() {
+        // Data is out of directory order, padded, and shared between two entries;
+        // a marker has a non-zero offset, and a name has junk after its NUL.
+        let mut bytes = Vec::from(*b"IWAD");
+        bytes.extend(3_i32.to_le_bytes());
+        bytes.extend(24_i32.to_le_bytes());
+        bytes.extend([0xAA; 4]);
+        bytes.extend([0xEE; 4]);
+        bytes.extend([0xBB; 4]);
+
+        for (offs, size, name) in [
+            (20_i32, 4_i32, b"SECOND\0\0"),
+            (12, 4, b"FIRST\0\x7F\x7F"),
+            (16, 0, b"MARKER\0\0"),
+        ] {
+            bytes.extend(offs.to_le_bytes());
+            bytes.extend(size.to_le_bytes());
+            bytes.extend(name);
+        }
+
+        let mut wad = Wad::read(Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(wad.lookup("FIRST").unwrap().bytes(), &[0xAA; 4]);
+        let rewritten = wad.write(Cursor::new(vec![])).unwrap().into_inner();
+        assert_eq!(rewritten, bytes);
+
+        wad.get_mut(0).unwrap().bytes_mut()[0] = 0xCC;
+        let rewritten = wad.write(Cursor::new(vec![])).unwrap().into_inner();
+        assert_ne!(rewritten.len(), bytes.len());
+        let reread = Wad::read(Cursor::new(rewritten)).unwrap();
+        assert_eq!(reread, wad);
+        assert_eq!(reread[0].bytes(), &[0xCC, 0xBB, 0xBB, 0xBB]);
+    }
+
     #[test]
     fn smoke() {
         let sample = Path::new(env!("CARGO_WORKSPACE_DIR")).join("sample/freedoom1.wad");
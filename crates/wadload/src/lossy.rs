@@ -0,0 +1,388 @@
+//! [`LossyDirReader`] and [`LossyReader`].
+
+use std::{
+    io::{Read, Seek, SeekFrom},
+    ops::Range,
+};
+
+use util::read_id8;
+
+use crate::{parse_header, DirEntry, Error, WadKind, DIR_ENTRY_SIZE, HEADER_SIZE};
+
+/// A lenient counterpart to [`crate::DirReader`] for damaged WADs.
+///
+/// The header's magic number, entry count, and directory offset must still be
+/// legible, but anything else that [`crate::DirReader`] would reject (or not
+/// notice) is reported as a [`Diagnostic`] and worked around where possible:
+/// - trailing data after the directory is ignored;
+/// - a directory cut off by the end of the file is read as far as it goes;
+/// - entries with a negative offset or size, or which start past the end of the
+///   file, are skipped;
+/// - entries which end past the end of the file are clamped;
+/// - overlapping entries are yielded as-is.
+///
+/// The whole directory is read and checked upon construction,
+/// so all diagnostics are available before iteration begins.
+#[derive(Debug)]
+pub struct LossyDirReader<R: Read + Seek> {
+    reader: R,
+    kind: WadKind,
+    entries: std::vec::IntoIter<DirEntry>,
+    diags: Vec<Diagnostic>,
+}
+
+impl<R: Read + Seek> LossyDirReader<R> {
+    /// Note that `reader` can be at any position when passed.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidKind`] if the 4-byte magic number at the start of the
+    ///   header is not IWAD or PWAD (ASCII), or is not present in full.
+    /// - [`Error::InvalidEntryCount`] if the `i32` after the magic number
+    ///   is negative, or not present in full.
+    /// - [`Error::InvalidDirOffset`] if the `i32` after the entry count
+    ///   is negative, or not present in full.
+    /// - [`Error::Io`] if the underlying reader fails to seek.
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let mut hbuf = [0; HEADER_SIZE];
+
+        reader.read_exact(&mut hbuf).map_err(|err| Error::Io {
+            source: err,
+            context: "header read",
+        })?;
+
+        let (kind, lump_c, dir_offs) = parse_header(hbuf)?;
+        let (lump_c, dir_offs) = (lump_c as usize, dir_offs as usize);
+        let mut diags = vec![];
+
+        let file_len = reader.seek(SeekFrom::End(0)).map_err(|err| Error::Io {
+            source: err,
+            context: "data length validation",
+        })? as usize;
+
+        let dir_end = dir_offs.saturating_add(lump_c.saturating_mul(DIR_ENTRY_SIZE));
+
+        let readable_c = if dir_end > file_len {
+            let readable = file_len.saturating_sub(dir_offs) / DIR_ENTRY_SIZE;
+
+            diags.push(Diagnostic::DirectoryTruncated {
+                expected: lump_c,
+                actual: readable,
+            });
+
+            readable
+        } else {
+            if dir_end < file_len {
+                diags.push(Diagnostic::TrailingData {
+                    len: file_len - dir_end,
+                });
+            }
+
+            lump_c
+        };
+
+        let mut dbuf = vec![0; readable_c * DIR_ENTRY_SIZE];
+
+        if readable_c > 0 {
+            reader
+                .seek(SeekFrom::Start(dir_offs as u64))
+                .map_err(|err| Error::Io {
+                    source: err,
+                    context: "directory read",
+                })?;
+
+            reader.read_exact(&mut dbuf).map_err(|err| Error::Io {
+                source: err,
+                context: "directory read",
+            })?;
+        }
+
+        let mut entries = Vec::with_capacity(readable_c);
+        let mut indices = Vec::with_capacity(readable_c);
+
+        for (index, ebuf) in dbuf.chunks_exact(DIR_ENTRY_SIZE).enumerate() {
+            let offs = i32::from_le_bytes([ebuf[0], ebuf[1], ebuf[2], ebuf[3]]);
+            let size = i32::from_le_bytes([ebuf[4], ebuf[5], ebuf[6], ebuf[7]]);
+
+            if offs < 0 || size < 0 {
+                diags.push(Diagnostic::NegativeEntry { index });
+                continue;
+            }
+
+            let span = (offs as usize)..((offs as usize) + (size as usize));
+
+            let span = if span.is_empty() {
+                span
+            } else if span.start >= file_len {
+                diags.push(Diagnostic::EntryOutOfRange { index, span });
+                continue;
+            } else if span.end > file_len {
+                let clamped = span.start..file_len;
+
+                diags.push(Diagnostic::EntryClamped {
+                    index,
+                    original: span,
+                    clamped: clamped.clone(),
+                });
+
+                clamped
+            } else {
+                span
+            };
+
+            let name = match read_id8([
+                ebuf[8], ebuf[9], ebuf[10], ebuf[11], ebuf[12], ebuf[13], ebuf[14], ebuf[15],
+            ]) {
+                Some(n) => n,
+                None => {
+                    diags.push(Diagnostic::EmptyName { index });
+                    Default::default()
+                }
+            };
+
+            entries.push(DirEntry { name, span });
+            indices.push(index);
+        }
+
+        find_overlaps(&entries, &indices, &mut diags);
+
+        Ok(Self {
+            reader,
+            kind,
+            entries: entries.into_iter(),
+            diags,
+        })
+    }
+
+    /// Every anomaly encountered while reading the directory, in the order in
+    /// which they were found (overlaps are found last).
+    #[must_use]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diags
+    }
+
+    /// Gets a reference to the underlying reader.
+    #[must_use]
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    #[must_use]
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Consumes this directory reader, returning the underlying value.
+    #[must_use]
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Is this an IWAD or a PWAD?
+    #[must_use]
+    pub fn wad_kind(&self) -> WadKind {
+        self.kind
+    }
+
+    /// Returns the number of salvageable lumps left to be yielded.
+    #[must_use]
+    pub fn lump_count(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+impl<R: Read + Seek> Iterator for LossyDirReader<R> {
+    type Item = DirEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<R: Read + Seek> ExactSizeIterator for LossyDirReader<R> {}
+
+/// A thin wrapper around [`LossyDirReader`] that maps its output, returning both
+/// [`DirEntry`] and a `Vec<u8>`.
+#[derive(Debug)]
+pub struct LossyReader<R: Read + Seek> {
+    inner: LossyDirReader<R>,
+}
+
+impl<R: Read + Seek> LossyReader<R> {
+    /// See [`LossyDirReader::new`] for caveats; this just wraps that function.
+    pub fn new(reader: R) -> Result<Self, Error> {
+        LossyDirReader::new(reader).map(|r| Self { inner: r })
+    }
+
+    /// See [`LossyDirReader::diagnostics`].
+    #[must_use]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        self.inner.diagnostics()
+    }
+
+    /// Consumes this reader, returning the underlying value.
+    #[must_use]
+    pub fn into_inner(self) -> R {
+        self.inner.reader
+    }
+
+    /// Is this an IWAD or a PWAD?
+    #[must_use]
+    pub fn wad_kind(&self) -> WadKind {
+        self.inner.wad_kind()
+    }
+
+    /// Returns the number of salvageable lumps left to be yielded.
+    #[must_use]
+    pub fn lump_count(&self) -> usize {
+        self.inner.lump_count()
+    }
+}
+
+impl<R: Read + Seek> Iterator for LossyReader<R> {
+    type Item = Result<(DirEntry, Vec<u8>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.inner.next()?;
+
+        if let Err(err) = self
+            .inner
+            .reader
+            .seek(SeekFrom::Start(entry.span.start as u64))
+        {
+            return Some(Err(Error::Io {
+                source: err,
+                context: "streaming read",
+            }));
+        }
+
+        let mut buf = vec![0; entry.span.len()];
+
+        match self.inner.reader.read_exact(&mut buf) {
+            Ok(()) => Some(Ok((entry, buf))),
+            Err(err) => Some(Err(Error::Io {
+                source: err,
+                context: "streaming read",
+            })),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<R: Read + Seek> ExactSizeIterator for LossyReader<R> {}
+
+/// An anomaly found and worked around by a [`LossyDirReader`].
+///
+/// All indices are those of entries in the directory as written in the file,
+/// including ones which were skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Diagnostic {
+    /// The file has `len` bytes of unaccounted-for data after the directory.
+    TrailingData { len: usize },
+    /// The header prescribed `expected` entries, but the file ends
+    /// after `actual` complete entries.
+    DirectoryTruncated { expected: usize, actual: usize },
+    /// The entry had a negative offset or size, and was skipped.
+    NegativeEntry { index: usize },
+    /// The entry starts at or past the end of the file, and was skipped.
+    EntryOutOfRange { index: usize, span: Range<usize> },
+    /// The entry ends past the end of the file, and was cut short.
+    EntryClamped {
+        index: usize,
+        original: Range<usize>,
+        clamped: Range<usize>,
+    },
+    /// The entry's name starts with a NUL byte, and was replaced with an
+    /// empty string.
+    EmptyName { index: usize },
+    /// The data of the entry at `index` shares bytes with that of the entry at
+    /// `other`, which comes earlier in the file (not necessarily in the directory).
+    Overlap { index: usize, other: usize },
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TrailingData { len } => {
+                write!(f, "{len} bytes of trailing data after the WAD directory")
+            }
+            Self::DirectoryTruncated { expected, actual } => {
+                write!(
+                    f,
+                    "WAD directory prescribes {expected} entries but only {actual} are present"
+                )
+            }
+            Self::NegativeEntry { index } => {
+                write!(
+                    f,
+                    "WAD directory entry {index} has a negative lump size or offset"
+                )
+            }
+            Self::EntryOutOfRange { index, span } => {
+                write!(
+                    f,
+                    "WAD directory entry {index} starts past the end of the file ({span:?})"
+                )
+            }
+            Self::EntryClamped {
+                index,
+                original,
+                clamped,
+            } => {
+                write!(
+                    f,
+                    "WAD directory entry {index} ends past the end of the file \
+                    ({original:?} clamped to {clamped:?})"
+                )
+            }
+            Self::EmptyName { index } => {
+                write!(f, "WAD directory entry {index} has an empty name")
+            }
+            Self::Overlap { index, other } => {
+                write!(
+                    f,
+                    "WAD directory entry {index} overlaps the data of entry {other}"
+                )
+            }
+        }
+    }
+}
+
+/// Sweeps over every non-empty span in order of start position, tracking the
+/// entry which reaches furthest so that nested overlaps are also caught.
+fn find_overlaps(entries: &[DirEntry], indices: &[usize], diags: &mut Vec<Diagnostic>) {
+    let mut order = (0..entries.len())
+        .filter(|i| !entries[*i].span.is_empty())
+        .collect::<Vec<_>>();
+
+    order.sort_by_key(|i| (entries[*i].span.start, indices[*i]));
+
+    let mut furthest: Option<usize> = None;
+
+    for i in order {
+        let span = &entries[i].span;
+
+        if let Some(f) = furthest {
+            if span.start < entries[f].span.end {
+                diags.push(Diagnostic::Overlap {
+                    index: indices[i],
+                    other: indices[f],
+                });
+            }
+
+            if span.end <= entries[f].span.end {
+                continue;
+            }
+        }
+
+        furthest = Some(i);
+    }
+}
@@ -0,0 +1,134 @@
+//! [`MappedWad`].
+
+use std::io::Cursor;
+
+use util::read_id8;
+
+use crate::{classify, validate_impl, Classification, DirEntry, Error, WadKind, DIR_ENTRY_SIZE};
+
+/// Zero-copy access to a WAD which is already entirely in memory, such as via
+/// a memory map or [`include_bytes`].
+///
+/// The directory is read and checked once upon construction; thereafter,
+/// lump data is always borrowed straight out of the original slice.
+#[derive(Debug, Clone)]
+pub struct MappedWad<'b> {
+    bytes: &'b [u8],
+    kind: WadKind,
+    entries: Vec<DirEntry>,
+}
+
+impl<'b> MappedWad<'b> {
+    /// # Errors
+    /// See [`crate::DirReader::new`]. Additionally, [`Error::InvalidDirEntry`]
+    /// is returned if any entry's offset or size is negative, or if its data
+    /// does not lie entirely within `bytes`.
+    pub fn new(bytes: &'b [u8]) -> Result<Self, Error> {
+        let header = validate_impl(&mut Cursor::new(bytes))?;
+        let dir_offs = header.dir_offs as usize;
+        let lump_c = header.lump_c as usize;
+        let dir = &bytes[dir_offs..(dir_offs + (lump_c * DIR_ENTRY_SIZE))];
+        let mut entries = Vec::with_capacity(lump_c);
+
+        for (i, ebuf) in dir.chunks_exact(DIR_ENTRY_SIZE).enumerate() {
+            let offs = i32::from_le_bytes([ebuf[0], ebuf[1], ebuf[2], ebuf[3]]);
+            let size = i32::from_le_bytes([ebuf[4], ebuf[5], ebuf[6], ebuf[7]]);
+
+            if offs < 0 || size < 0 {
+                return Err(Error::InvalidDirEntry(i));
+            }
+
+            let span = (offs as usize)..((offs as usize) + (size as usize));
+
+            if !span.is_empty() && span.end > bytes.len() {
+                return Err(Error::InvalidDirEntry(i));
+            }
+
+            let name = read_id8([
+                ebuf[8], ebuf[9], ebuf[10], ebuf[11], ebuf[12], ebuf[13], ebuf[14], ebuf[15],
+            ])
+            .unwrap_or_default();
+
+            entries.push(DirEntry { name, span });
+        }
+
+        Ok(Self {
+            bytes,
+            kind: header.kind,
+            entries,
+        })
+    }
+
+    /// Is this an IWAD or a PWAD?
+    #[must_use]
+    pub fn wad_kind(&self) -> WadKind {
+        self.kind
+    }
+
+    /// Computes in `O(1)` time.
+    #[must_use]
+    pub fn lump_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entire WAD, as originally given to [`Self::new`].
+    #[must_use]
+    pub fn as_bytes(&self) -> &'b [u8] {
+        self.bytes
+    }
+
+    /// The directory, in its original order.
+    #[must_use]
+    pub fn entries(&self) -> &[DirEntry] {
+        &self.entries
+    }
+
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<(&DirEntry, &'b [u8])> {
+        self.entries
+            .get(index)
+            .map(|entry| (entry, self.data_of(entry)))
+    }
+
+    /// Returns the index of the last lump named `name` (ASCII case-insensitive),
+    /// following the same convention as [`crate::Wad::find`].
+    #[must_use]
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .rposition(|entry| entry.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Returns the data of the last lump named `name` (ASCII case-insensitive).
+    #[must_use]
+    pub fn lookup(&self, name: &str) -> Option<&'b [u8]> {
+        self.find(name).map(|i| self.data_of(&self.entries[i]))
+    }
+
+    /// Yields every directory entry and its data, in directory order.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (&DirEntry, &'b [u8])> + '_ {
+        self.entries
+            .iter()
+            .map(|entry| (entry, self.data_of(entry)))
+    }
+
+    /// Shorthand for passing every entry's name to [`classify`].
+    #[must_use]
+    pub fn classify(&self) -> Classification {
+        classify(self.entries.iter().map(|entry| entry.name.as_str()))
+    }
+
+    #[must_use]
+    fn data_of(&self, entry: &DirEntry) -> &'b [u8] {
+        if entry.span.is_empty() {
+            return &[];
+        }
+
+        &self.bytes[entry.span.clone()]
+    }
+}
@@ -0,0 +1,381 @@
+//! [`Wad`].
+
+use std::{
+    io::{Cursor, Read, Seek, SeekFrom, Write},
+    ops::Range,
+};
+
+use crate::{classify, Classification, Error, Lump, Reader, WadKind, Writer};
+
+/// An owned, editable, in-memory WAD archive.
+///
+/// Lumps are kept in directory order. Name lookups follow the same convention
+/// as the Doom engine's `W_CheckNumForName`: they are ASCII case-insensitive,
+/// and if multiple lumps share a name, the last one wins.
+///
+/// A `Wad` acquired via [`Self::read`] remembers how its file was laid out
+/// (lump offsets, gaps and padding, shared lump data, the raw directory), and
+/// is written back out byte-for-byte by [`Self::write`] if left untouched.
+/// Any mutable access discards this, after which output is laid out by [`Writer`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Wad {
+    kind: WadKind,
+    lumps: Vec<Lump>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    layout: Option<Layout>,
+}
+
+/// See [`Wad::read`].
+#[derive(Debug, Clone)]
+struct Layout {
+    /// The length of the whole file.
+    len: usize,
+    /// Parallel to [`Wad::lumps`].
+    spans: Vec<Range<usize>>,
+    /// Every part of the file not covered by a lump's data (i.e. the header,
+    /// the directory, and anything in between lumps), with its offset.
+    filler: Vec<(usize, Box<[u8]>)>,
+}
+
+impl Wad {
+    #[must_use]
+    pub fn new(kind: WadKind) -> Self {
+        Self {
+            kind,
+            lumps: vec![],
+            layout: None,
+        }
+    }
+
+    /// Reads every lump out of `reader`, which must start at the WAD's header.
+    /// See [`crate::DirReader::new`] for caveats.
+    pub fn read<R: Read + Seek>(mut reader: R) -> Result<Self, Error> {
+        let mut bytes = vec![];
+
+        reader
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| reader.read_to_end(&mut bytes))
+            .map_err(|err| Error::Io {
+                source: err,
+                context: "whole-file read",
+            })?;
+
+        let reader = Reader::new(Cursor::new(bytes.as_slice()))?;
+        let kind = reader.wad_kind();
+        let mut lumps = Vec::with_capacity(reader.lump_count());
+        let mut spans = Vec::with_capacity(reader.lump_count());
+
+        for result in reader {
+            let (entry, data) = result?;
+            spans.push(entry.span.clone());
+            lumps.push(Lump::from((entry, data)));
+        }
+
+        let mut covered = spans
+            .iter()
+            .filter(|span| !span.is_empty())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        covered.sort_by_key(|span| span.start);
+
+        let mut filler = vec![];
+        let mut cursor = 0;
+
+        for span in covered {
+            if span.start > cursor {
+                filler.push((cursor, bytes[cursor..span.start].into()));
+            }
+
+            cursor = cursor.max(span.end);
+        }
+
+        if cursor < bytes.len() {
+            filler.push((cursor, bytes[cursor..].into()));
+        }
+
+        Ok(Self {
+            kind,
+            lumps,
+            layout: Some(Layout {
+                len: bytes.len(),
+                spans,
+                filler,
+            }),
+        })
+    }
+
+    /// Emits this archive in full, returning `writer` positioned after the end
+    /// of the output. This goes through a [`Writer`] unless the archive was
+    /// read via [`Self::read`] and has not been changed since.
+    pub fn write<W: Write + Seek>(&self, mut writer: W) -> Result<W, Error> {
+        if let Some(layout) = &self.layout {
+            let mut bytes = vec![0; layout.len];
+
+            for (offs, filler) in &layout.filler {
+                bytes[*offs..(*offs + filler.len())].copy_from_slice(filler);
+            }
+
+            for (lump, span) in self.lumps.iter().zip(&layout.spans) {
+                if !span.is_empty() {
+                    bytes[span.clone()].copy_from_slice(lump.bytes());
+                }
+            }
+
+            writer
+                .write_all(&bytes)
+                .and_then(|_| writer.flush())
+                .map_err(|err| Error::Io {
+                    source: err,
+                    context: "whole-file write",
+                })?;
+
+            return Ok(writer);
+        }
+
+        let mut writer = Writer::new(writer, self.kind)?;
+
+        for lump in &self.lumps {
+            writer.write(lump)?;
+        }
+
+        writer.finish()
+    }
+
+    /// Is this an IWAD or a PWAD?
+    #[must_use]
+    pub fn wad_kind(&self) -> WadKind {
+        self.kind
+    }
+
+    pub fn set_wad_kind(&mut self, kind: WadKind) {
+        self.layout = None;
+        self.kind = kind;
+    }
+
+    /// Computes in `O(1)` time.
+    #[must_use]
+    pub fn lump_count(&self) -> usize {
+        self.lumps.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lumps.is_empty()
+    }
+
+    /// All lumps, in directory order.
+    #[must_use]
+    pub fn lumps(&self) -> &[Lump] {
+        &self.lumps
+    }
+
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&Lump> {
+        self.lumps.get(index)
+    }
+
+    #[must_use]
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Lump> {
+        self.lumps_mut().get_mut(index)
+    }
+
+    /// Appends `lump` to the end of the directory.
+    pub fn push(&mut self, lump: Lump) {
+        self.lumps_mut().push(lump);
+    }
+
+    /// Panics if `index` is greater than [`Self::lump_count`].
+    pub fn insert(&mut self, index: usize, lump: Lump) {
+        self.lumps_mut().insert(index, lump);
+    }
+
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> Lump {
+        self.lumps_mut().remove(index)
+    }
+
+    /// Replaces the lumps in `range` with those yielded by `replace_with`.
+    /// Panics if `range` is out of bounds.
+    pub fn splice<I>(&mut self, range: Range<usize>, replace_with: I)
+    where
+        I: IntoIterator<Item = Lump>,
+    {
+        self.lumps_mut().splice(range, replace_with);
+    }
+
+    /// Keeps only the lumps for which `predicate` returns `true`.
+    pub fn retain<F>(&mut self, predicate: F)
+    where
+        F: FnMut(&Lump) -> bool,
+    {
+        self.lumps_mut().retain(predicate);
+    }
+
+    /// Panics if `index` is out of bounds.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidLumpName`] if `name` is empty, is longer than
+    /// 8 bytes, or contains non-ASCII characters.
+    pub fn rename(&mut self, index: usize, name: &str) -> Result<(), Error> {
+        self.lumps_mut()[index].set_name(name)
+    }
+
+    /// Takes the lump at `from` out of the directory and re-inserts it such that
+    /// it ends up at `to`, shifting everything in between.
+    /// Panics if either index is out of bounds.
+    pub fn move_lump(&mut self, from: usize, to: usize) {
+        if from < to {
+            self.lumps_mut()[from..=to].rotate_left(1);
+        } else if from > to {
+            self.lumps_mut()[to..=from].rotate_right(1);
+        }
+    }
+
+    /// Panics if either index is out of bounds.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.lumps_mut().swap(a, b);
+    }
+
+    /// Returns the index of the last lump named `name` (ASCII case-insensitive).
+    #[must_use]
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.lumps
+            .iter()
+            .rposition(|lump| lump.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Returns the index of the first lump named `name` (ASCII case-insensitive)
+    /// at or after `start`.
+    #[must_use]
+    pub fn find_from(&self, start: usize, name: &str) -> Option<usize> {
+        self.lumps
+            .get(start..)?
+            .iter()
+            .position(|lump| lump.name().eq_ignore_ascii_case(name))
+            .map(|i| i + start)
+    }
+
+    /// Returns the last lump named `name` (ASCII case-insensitive).
+    #[must_use]
+    pub fn lookup(&self, name: &str) -> Option<&Lump> {
+        self.find(name).map(|i| &self.lumps[i])
+    }
+
+    /// Returns the last lump named `name` (ASCII case-insensitive).
+    #[must_use]
+    pub fn lookup_mut(&mut self, name: &str) -> Option<&mut Lump> {
+        self.find(name).map(|i| &mut self.lumps_mut()[i])
+    }
+
+    /// Yields the index range of the lumps between every pair of markers,
+    /// exclusive of the markers themselves. A range opens at any lump named in
+    /// `start` and closes at the next lump named in `end`; names are compared
+    /// ASCII case-insensitively. A start marker with no end marker following it
+    /// yields nothing.
+    ///
+    /// For example, sprites are between `S_START`/`SS_START` and
+    /// `S_END`/`SS_END`, and flats are between `F_START`/`FF_START` and
+    /// `F_END`/`FF_END`.
+    pub fn marker_ranges<'w>(
+        &'w self,
+        start: &'w [&'w str],
+        end: &'w [&'w str],
+    ) -> impl Iterator<Item = Range<usize>> + 'w {
+        let mut cursor = 0;
+
+        std::iter::from_fn(move || {
+            let s = self.lumps[cursor..]
+                .iter()
+                .position(|lump| name_in(lump, start))?
+                + cursor
+                + 1;
+
+            let e = self.lumps[s..].iter().position(|lump| name_in(lump, end))? + s;
+
+            cursor = e + 1;
+            Some(s..e)
+        })
+    }
+
+    /// Yields every lump in every range yielded by [`Self::marker_ranges`].
+    pub fn between<'w>(
+        &'w self,
+        start: &'w [&'w str],
+        end: &'w [&'w str],
+    ) -> impl Iterator<Item = &'w Lump> + 'w {
+        self.marker_ranges(start, end)
+            .flat_map(|range| self.lumps[range].iter())
+    }
+
+    /// Shorthand for passing every lump's name to [`classify`].
+    #[must_use]
+    pub fn classify(&self) -> Classification {
+        classify(self.lumps.iter().map(|lump| lump.name()))
+    }
+
+    #[must_use]
+    pub fn into_lumps(self) -> Vec<Lump> {
+        self.lumps
+    }
+
+    /// Discards the layout remembered by [`Self::read`].
+    #[must_use]
+    fn lumps_mut(&mut self) -> &mut Vec<Lump> {
+        self.layout = None;
+        &mut self.lumps
+    }
+}
+
+/// Ignores whether either archive remembers the layout of a file.
+impl PartialEq for Wad {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind && self.lumps == other.lumps
+    }
+}
+
+impl Eq for Wad {}
+
+impl std::ops::Index<usize> for Wad {
+    type Output = Lump;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.lumps[index]
+    }
+}
+
+impl std::ops::IndexMut<usize> for Wad {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.lumps_mut()[index]
+    }
+}
+
+impl Extend<Lump> for Wad {
+    fn extend<T: IntoIterator<Item = Lump>>(&mut self, iter: T) {
+        self.lumps_mut().extend(iter);
+    }
+}
+
+impl IntoIterator for Wad {
+    type Item = Lump;
+    type IntoIter = std::vec::IntoIter<Lump>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.lumps.into_iter()
+    }
+}
+
+impl<'w> IntoIterator for &'w Wad {
+    type Item = &'w Lump;
+    type IntoIter = std::slice::Iter<'w, Lump>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.lumps.iter()
+    }
+}
+
+#[must_use]
+fn name_in(lump: &Lump, names: &[&str]) -> bool {
+    names.iter().any(|n| lump.name().eq_ignore_ascii_case(n))
+}
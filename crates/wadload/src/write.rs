@@ -0,0 +1,217 @@
+//! [`Writer`].
+
+use std::io::{Seek, SeekFrom, Write};
+
+use util::Id8;
+
+use crate::{DirEntry, Error, Lump, WadKind, DIR_ENTRY_SIZE, HEADER_SIZE};
+
+/// Emits a complete WAD file to a [`std::io::Write`] implementation.
+///
+/// Lumps are written out immediately, in the order in which they are given.
+/// The header's entry count and directory offset are not known until the last
+/// lump has been written, so the header starts out with placeholder values and
+/// gets filled in after the fact by [`Writer::finish`]. If a writer is dropped
+/// without being finished, the output is not a valid WAD.
+///
+/// All offsets are relative to the position of the underlying writer when
+/// [`Writer::new`] was called, so it is safe to emit a WAD into the middle of
+/// another stream.
+#[derive(Debug)]
+pub struct Writer<W: Write + Seek> {
+    writer: W,
+    kind: WadKind,
+    /// The position of the header's first byte in the underlying stream.
+    start: u64,
+    /// Relative to `start`.
+    cursor: usize,
+    dir: Vec<DirEntry>,
+}
+
+impl<W: Write + Seek> Writer<W> {
+    /// Writes a placeholder header to `writer` at its current position.
+    pub fn new(mut writer: W, kind: WadKind) -> Result<Self, Error> {
+        let start = writer.stream_position().map_err(|err| Error::Io {
+            source: err,
+            context: "header write",
+        })?;
+
+        writer
+            .write_all(&header_bytes(kind, 0, 0))
+            .map_err(|err| Error::Io {
+                source: err,
+                context: "header write",
+            })?;
+
+        Ok(Self {
+            writer,
+            kind,
+            start,
+            cursor: HEADER_SIZE,
+            dir: vec![],
+        })
+    }
+
+    /// Appends `bytes` to the data section and records a directory entry for it.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidLumpName`] if `name` is empty, is longer than 8 bytes,
+    ///   or contains non-ASCII characters.
+    /// - [`Error::Oversize`] if the lump would end past the addressable range
+    ///   of a WAD (i.e. `i32::MAX`).
+    pub fn write_lump(&mut self, name: &str, bytes: &[u8]) -> Result<(), Error> {
+        let name = validate_name(name)?;
+
+        let end = self
+            .cursor
+            .checked_add(bytes.len())
+            .filter(|end| *end <= (i32::MAX as usize))
+            .ok_or(Error::Oversize)?;
+
+        self.writer.write_all(bytes).map_err(|err| Error::Io {
+            source: err,
+            context: "lump write",
+        })?;
+
+        self.dir.push(DirEntry {
+            name,
+            span: self.cursor..end,
+        });
+
+        self.cursor = end;
+
+        Ok(())
+    }
+
+    /// Shorthand for calling [`Self::write_lump`] with the contents of `lump`.
+    pub fn write(&mut self, lump: &Lump) -> Result<(), Error> {
+        self.write_lump(lump.name(), lump.bytes())
+    }
+
+    /// Shorthand for calling [`Self::write_lump`] with no data,
+    /// e.g. for `S_START` or a map header like `MAP01`.
+    pub fn write_marker(&mut self, name: &str) -> Result<(), Error> {
+        self.write_lump(name, &[])
+    }
+
+    /// Writes the directory, then seeks back to fill in the header's entry count
+    /// and directory offset. Upon return, the underlying writer is positioned
+    /// at the end of the directory.
+    ///
+    /// # Errors
+    /// - [`Error::Oversize`] if the directory would end past the addressable
+    ///   range of a WAD.
+    pub fn finish(mut self) -> Result<W, Error> {
+        let dir_offs = self.cursor;
+
+        let lump_c = i32::try_from(self.dir.len()).map_err(|_| Error::Oversize)?;
+
+        let dir_end = self
+            .dir
+            .len()
+            .checked_mul(DIR_ENTRY_SIZE)
+            .and_then(|dir_len| dir_offs.checked_add(dir_len))
+            .ok_or(Error::Oversize)?;
+
+        if dir_end > (i32::MAX as usize) {
+            return Err(Error::Oversize);
+        }
+
+        for entry in &self.dir {
+            let mut ebuf = [0; DIR_ENTRY_SIZE];
+            ebuf[0..4].copy_from_slice(&(entry.span.start as i32).to_le_bytes());
+            ebuf[4..8].copy_from_slice(&(entry.span.len() as i32).to_le_bytes());
+            ebuf[8..(8 + entry.name.len())].copy_from_slice(entry.name.as_bytes());
+
+            self.writer.write_all(&ebuf).map_err(|err| Error::Io {
+                source: err,
+                context: "directory write",
+            })?;
+        }
+
+        self.writer
+            .seek(SeekFrom::Start(self.start))
+            .map_err(|err| Error::Io {
+                source: err,
+                context: "header fill-in",
+            })?;
+
+        self.writer
+            .write_all(&header_bytes(self.kind, lump_c, dir_offs as i32))
+            .map_err(|err| Error::Io {
+                source: err,
+                context: "header fill-in",
+            })?;
+
+        self.writer
+            .seek(SeekFrom::Start(self.start + (dir_end as u64)))
+            .map_err(|err| Error::Io {
+                source: err,
+                context: "header fill-in",
+            })?;
+
+        self.writer.flush().map_err(|err| Error::Io {
+            source: err,
+            context: "header fill-in",
+        })?;
+
+        Ok(self.writer)
+    }
+
+    /// Gets a reference to the underlying writer.
+    ///
+    /// It is inadvisable to directly write to the underlying writer.
+    #[must_use]
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Is this an IWAD or a PWAD?
+    #[must_use]
+    pub fn wad_kind(&self) -> WadKind {
+        self.kind
+    }
+
+    /// Returns the number of lumps written so far.
+    #[must_use]
+    pub fn lump_count(&self) -> usize {
+        self.dir.len()
+    }
+
+    /// Returns the directory entries for every lump written so far.
+    #[must_use]
+    pub fn entries(&self) -> &[DirEntry] {
+        &self.dir
+    }
+}
+
+#[must_use]
+fn header_bytes(kind: WadKind, lump_c: i32, dir_offs: i32) -> [u8; HEADER_SIZE] {
+    let mut ret = [0; HEADER_SIZE];
+
+    ret[0..4].copy_from_slice(match kind {
+        WadKind::IWad => b"IWAD",
+        WadKind::PWad => b"PWAD",
+    });
+
+    ret[4..8].copy_from_slice(&lump_c.to_le_bytes());
+    ret[8..12].copy_from_slice(&dir_offs.to_le_bytes());
+    ret
+}
+
+/// Lump names must be between 1 and 8 ASCII characters, with no NULs.
+pub(crate) fn validate_name(name: &str) -> Result<Id8, Error> {
+    if name.is_empty() || name.len() > 8 || !name.is_ascii() || name.contains('\0') {
+        let mut truncated = Id8::new();
+
+        for c in name.chars() {
+            if truncated.try_push(c).is_err() {
+                break;
+            }
+        }
+
+        return Err(Error::InvalidLumpName(truncated));
+    }
+
+    Ok(util::id8_truncated(name))
+}